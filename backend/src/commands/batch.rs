@@ -0,0 +1,71 @@
+//! Batch Execution Tauri Commands
+//!
+//! Provides Tauri commands for running one command on many hosts.
+
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::SshServiceState;
+use crate::models::{BatchJob, BatchJobRequest, BatchJobSummary};
+use crate::services::batch::BatchEvent;
+
+/// Start a batch job. Each host's result is emitted as a `batch-progress`
+/// event tagged with the returned job ID as soon as it finishes, followed by
+/// one `batch-finished` event with the job summary. Listen before invoking.
+#[tauri::command]
+pub async fn ssh_batch_start(
+    app: AppHandle,
+    state: State<'_, SshServiceState>,
+    request: BatchJobRequest,
+) -> Result<String, String> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let job_id = state
+        .0
+        .start_batch(request, tx)
+        .map_err(|e| e.to_string())?;
+
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            let _ = match event {
+                BatchEvent::Progress(progress) => app.emit("batch-progress", progress),
+                BatchEvent::Finished(summary) => app.emit("batch-finished", summary),
+            };
+        }
+    });
+
+    Ok(job_id)
+}
+
+/// Cancel a running batch job
+#[tauri::command]
+pub async fn ssh_batch_cancel(
+    state: State<'_, SshServiceState>,
+    job_id: String,
+) -> Result<(), String> {
+    state.0.cancel_batch(&job_id).map_err(|e| e.to_string())
+}
+
+/// List running and stored batch jobs, newest first
+#[tauri::command]
+pub async fn ssh_batch_list(
+    state: State<'_, SshServiceState>,
+) -> Result<Vec<BatchJobSummary>, String> {
+    state.0.list_batches().map_err(|e| e.to_string())
+}
+
+/// Get a batch job with its host results
+#[tauri::command]
+pub async fn ssh_batch_get(
+    state: State<'_, SshServiceState>,
+    job_id: String,
+) -> Result<BatchJob, String> {
+    state.0.get_batch(&job_id).map_err(|e| e.to_string())
+}
+
+/// Delete a stored batch job
+#[tauri::command]
+pub async fn ssh_batch_delete(
+    state: State<'_, SshServiceState>,
+    job_id: String,
+) -> Result<(), String> {
+    state.0.delete_batch(&job_id).map_err(|e| e.to_string())
+}
@@ -0,0 +1,101 @@
+//! Broadcast Input Tauri Commands
+//!
+//! Provides Tauri commands for sending terminal input to groups of SSH sessions.
+
+use tauri::State;
+
+use crate::commands::SshServiceState;
+use crate::models::{BroadcastGroupInfo, BroadcastReport, TerminalSize};
+
+/// Create a broadcast group from existing sessions
+#[tauri::command]
+pub async fn ssh_broadcast_create(
+    state: State<'_, SshServiceState>,
+    name: String,
+    session_ids: Vec<String>,
+) -> Result<BroadcastGroupInfo, String> {
+    state
+        .0
+        .create_broadcast_group(name, session_ids)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Add a session to a broadcast group
+#[tauri::command]
+pub async fn ssh_broadcast_join(
+    state: State<'_, SshServiceState>,
+    group_id: String,
+    session_id: String,
+) -> Result<BroadcastGroupInfo, String> {
+    state
+        .0
+        .join_broadcast_group(&group_id, &session_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Remove a session from a broadcast group
+#[tauri::command]
+pub async fn ssh_broadcast_leave(
+    state: State<'_, SshServiceState>,
+    group_id: String,
+    session_id: String,
+) -> Result<BroadcastGroupInfo, String> {
+    state
+        .0
+        .leave_broadcast_group(&group_id, &session_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Delete a broadcast group
+#[tauri::command]
+pub async fn ssh_broadcast_delete(
+    state: State<'_, SshServiceState>,
+    group_id: String,
+) -> Result<(), String> {
+    state
+        .0
+        .delete_broadcast_group(&group_id)
+        .map_err(|e| e.to_string())
+}
+
+/// List broadcast groups
+#[tauri::command]
+pub async fn ssh_broadcast_list(
+    state: State<'_, SshServiceState>,
+) -> Result<Vec<BroadcastGroupInfo>, String> {
+    Ok(state.0.list_broadcast_groups())
+}
+
+/// Send base64 encoded data to every session of a broadcast group
+#[tauri::command]
+pub async fn ssh_broadcast_send_data(
+    state: State<'_, SshServiceState>,
+    group_id: String,
+    data: String,
+) -> Result<BroadcastReport, String> {
+    let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &data)
+        .map_err(|e| e.to_string())?;
+
+    state
+        .0
+        .broadcast_data(&group_id, &bytes)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Resize the terminal of every session of a broadcast group
+#[tauri::command]
+pub async fn ssh_broadcast_resize(
+    state: State<'_, SshServiceState>,
+    group_id: String,
+    cols: u32,
+    rows: u32,
+) -> Result<BroadcastReport, String> {
+    state
+        .0
+        .broadcast_resize(&group_id, TerminalSize { cols, rows })
+        .await
+        .map_err(|e| e.to_string())
+}
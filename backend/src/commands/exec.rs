@@ -0,0 +1,55 @@
+//! Remote Command Execution Tauri Commands
+//!
+//! Provides Tauri commands for running non-interactive commands over SSH.
+
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::SshServiceState;
+use crate::models::{ExecRequest, ExecResult};
+use crate::services::exec::ExecEvent;
+
+/// Run a command and wait for it to exit
+#[tauri::command]
+pub async fn ssh_exec(
+    state: State<'_, SshServiceState>,
+    request: ExecRequest,
+) -> Result<ExecResult, String> {
+    state.0.exec(&request).await.map_err(|e| e.to_string())
+}
+
+/// Run a command in the background. Output is emitted as `ssh-exec-output`
+/// events tagged with the returned exec ID, followed by one
+/// `ssh-exec-finished` event. Listen before invoking so no output is missed.
+#[tauri::command]
+pub async fn ssh_exec_stream(
+    app: AppHandle,
+    state: State<'_, SshServiceState>,
+    request: ExecRequest,
+) -> Result<String, String> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let exec_id = state
+        .0
+        .exec_stream(request, tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            let _ = match event {
+                ExecEvent::Output(output) => app.emit("ssh-exec-output", output),
+                ExecEvent::Finished(finished) => app.emit("ssh-exec-finished", finished),
+            };
+        }
+    });
+
+    Ok(exec_id)
+}
+
+/// Cancel a streaming command
+#[tauri::command]
+pub async fn ssh_exec_cancel(
+    state: State<'_, SshServiceState>,
+    exec_id: String,
+) -> Result<(), String> {
+    state.0.cancel_exec(&exec_id).map_err(|e| e.to_string())
+}
@@ -0,0 +1,53 @@
+//! Local Shell Tauri Commands
+//!
+//! Provides Tauri commands for terminals running a shell on this machine.
+//! Once open, a local shell session is driven by the SSH session commands
+//! and streams the same events.
+
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::SshServiceState;
+use crate::models::{LocalShellInfo, LocalShellRequest};
+use crate::services::terminal_output;
+
+/// Open a local shell session
+#[tauri::command]
+pub async fn local_shell_open(
+    app: AppHandle,
+    state: State<'_, SshServiceState>,
+    request: LocalShellRequest,
+) -> Result<String, String> {
+    let service = &state.0;
+
+    // Create the output stream, bounded and coalesced into frames
+    let (tx, mut rx) = terminal_output::channel();
+
+    let session_id = service
+        .open_local_shell(&request, tx)
+        .map_err(|e| e.to_string())?;
+
+    // Spawn task to forward shell output to frontend, as for SSH sessions
+    let session_id_clone = session_id.clone();
+    let service = service.clone();
+    tokio::spawn(async move {
+        while let Some(frame) = rx.next_frame().await {
+            let _ = app.emit(
+                &format!("ssh-data-{}", session_id_clone),
+                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &frame),
+            );
+        }
+        service.output_ended(&session_id_clone);
+        // The service reports the shell exiting, with its exit status
+    });
+
+    Ok(session_id)
+}
+
+/// List the shells installed on this machine, the default one first
+#[tauri::command]
+pub async fn local_shell_list(
+    state: State<'_, SshServiceState>,
+) -> Result<Vec<LocalShellInfo>, String> {
+    let service = &state.0;
+    Ok(service.list_local_shells())
+}
@@ -0,0 +1,45 @@
+//! Host Metrics Tauri Commands
+//!
+//! Provides Tauri commands for monitoring the resource usage of remote hosts.
+
+use tauri::State;
+
+use crate::commands::SshServiceState;
+use crate::models::{MetricsMonitorInfo, MetricsRequest, MetricsSample};
+
+/// Start collecting the host metrics of a session. Samples are emitted as
+/// `ssh-metrics-{sessionId}` events; `ssh-metrics-stopped-{sessionId}` is
+/// emitted once the collector stops. Starting a running collector only
+/// returns its information.
+#[tauri::command]
+pub async fn ssh_metrics_start(
+    state: State<'_, SshServiceState>,
+    request: MetricsRequest,
+) -> Result<MetricsMonitorInfo, String> {
+    state
+        .0
+        .start_metrics(&request)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Stop collecting the host metrics of a session
+#[tauri::command]
+pub async fn ssh_metrics_stop(
+    state: State<'_, SshServiceState>,
+    session_id: String,
+) -> Result<(), String> {
+    state.0.stop_metrics(&session_id).map_err(|e| e.to_string())
+}
+
+/// Get the recent metrics samples of a session, oldest first
+#[tauri::command]
+pub async fn ssh_metrics_history(
+    state: State<'_, SshServiceState>,
+    session_id: String,
+) -> Result<Vec<MetricsSample>, String> {
+    state
+        .0
+        .metrics_history(&session_id)
+        .map_err(|e| e.to_string())
+}
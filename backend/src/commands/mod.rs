@@ -2,14 +2,34 @@
 //!
 //! This module contains all Tauri command handlers.
 
+pub mod batch;
+pub mod broadcast;
 pub mod crypto;
 pub mod database;
+pub mod exec;
+pub mod local_shell;
+pub mod metrics;
+pub mod recording;
+pub mod scrollback;
 pub mod sftp;
 pub mod ssh;
+pub mod ssh_config;
+pub mod telnet;
+pub mod tunnel;
 pub mod utils;
 
+pub use batch::*;
+pub use broadcast::*;
 pub use crypto::*;
 pub use database::*;
+pub use exec::*;
+pub use local_shell::*;
+pub use metrics::*;
+pub use recording::*;
+pub use scrollback::*;
 pub use sftp::*;
 pub use ssh::*;
+pub use ssh_config::*;
+pub use telnet::*;
+pub use tunnel::*;
 pub use utils::*;
@@ -0,0 +1,90 @@
+//! Session Recording Tauri Commands
+//!
+//! Provides Tauri commands for recording SSH sessions and managing recordings.
+
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::SshServiceState;
+use crate::models::{RecordingInfo, RecordingOptions};
+
+/// Start recording a connected SSH session
+#[tauri::command]
+pub async fn ssh_recording_start(
+    state: State<'_, SshServiceState>,
+    session_id: String,
+    options: Option<RecordingOptions>,
+) -> Result<RecordingInfo, String> {
+    state
+        .0
+        .start_recording(&session_id, options.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Stop recording an SSH session
+#[tauri::command]
+pub async fn ssh_recording_stop(
+    state: State<'_, SshServiceState>,
+    session_id: String,
+) -> Result<(), String> {
+    state
+        .0
+        .stop_recording(&session_id)
+        .map_err(|e| e.to_string())
+}
+
+/// List stored recordings
+#[tauri::command]
+pub async fn ssh_recording_list(
+    state: State<'_, SshServiceState>,
+) -> Result<Vec<RecordingInfo>, String> {
+    state.0.list_recordings().map_err(|e| e.to_string())
+}
+
+/// Delete a stored recording
+#[tauri::command]
+pub async fn ssh_recording_delete(
+    state: State<'_, SshServiceState>,
+    recording_id: String,
+) -> Result<(), String> {
+    state
+        .0
+        .delete_recording(&recording_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Replay a recording. Frames are emitted as `recording-replay` events tagged
+/// with the returned replay ID, followed by `recording-replay-finished`.
+/// Listen before invoking so the first frames are not missed.
+#[tauri::command]
+pub async fn ssh_recording_replay(
+    app: AppHandle,
+    state: State<'_, SshServiceState>,
+    recording_id: String,
+    speed: Option<f64>,
+) -> Result<String, String> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let replay_id = state
+        .0
+        .replay_recording(&recording_id, speed.unwrap_or(1.0), tx)
+        .map_err(|e| e.to_string())?;
+
+    let replay_id_clone = replay_id.clone();
+    tokio::spawn(async move {
+        while let Some(frame) = rx.recv().await {
+            let _ = app.emit("recording-replay", frame);
+        }
+        let _ = app.emit("recording-replay-finished", replay_id_clone);
+    });
+
+    Ok(replay_id)
+}
+
+/// Stop a running replay
+#[tauri::command]
+pub async fn ssh_recording_replay_stop(
+    state: State<'_, SshServiceState>,
+    replay_id: String,
+) -> Result<(), String> {
+    state.0.stop_replay(&replay_id).map_err(|e| e.to_string())
+}
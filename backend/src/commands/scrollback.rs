@@ -0,0 +1,62 @@
+//! Scrollback Tauri Commands
+//!
+//! Provides Tauri commands for the terminal output kept on the backend.
+
+use tauri::State;
+
+use crate::commands::SshServiceState;
+use crate::models::{
+    ScrollbackExportRequest, ScrollbackLines, ScrollbackMatch, ScrollbackSearchRequest,
+    ScrollbackSnapshot,
+};
+
+/// Get the latest output of a terminal session, e.g. to restore a tab that
+/// reattaches to it
+#[tauri::command]
+pub async fn ssh_scrollback_fetch(
+    state: State<'_, SshServiceState>,
+    session_id: String,
+) -> Result<ScrollbackSnapshot, String> {
+    state.0.scrollback(&session_id).map_err(|e| e.to_string())
+}
+
+/// Search the scrollback text of a terminal session for plain text or a
+/// regular expression
+#[tauri::command]
+pub async fn ssh_scrollback_search(
+    state: State<'_, SshServiceState>,
+    request: ScrollbackSearchRequest,
+) -> Result<Vec<ScrollbackMatch>, String> {
+    state
+        .0
+        .search_scrollback(&request)
+        .map_err(|e| e.to_string())
+}
+
+/// Get up to `count` lines of the scrollback text of a terminal session,
+/// from line number `start` on
+#[tauri::command]
+pub async fn ssh_scrollback_lines(
+    state: State<'_, SshServiceState>,
+    session_id: String,
+    start: u64,
+    count: usize,
+) -> Result<ScrollbackLines, String> {
+    state
+        .0
+        .scrollback_lines(&session_id, start, count)
+        .map_err(|e| e.to_string())
+}
+
+/// Export the scrollback text of a terminal session to a text or HTML file
+#[tauri::command]
+pub async fn ssh_scrollback_export(
+    state: State<'_, SshServiceState>,
+    request: ScrollbackExportRequest,
+) -> Result<(), String> {
+    state
+        .0
+        .export_scrollback(&request)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -7,50 +7,108 @@ use futures::StreamExt;
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter, State};
 
-use crate::models::{SshConnectRequest, SshSessionInfo, TerminalSize};
-use crate::services::SshService;
+use crate::models::{
+    ConnectError, ConnectErrorKind, ConnectionDiagnostics, KnownHostEntry, SessionStatus,
+    SshConnectRequest, SshSessionInfo, SshStatusEvent, TerminalSize,
+};
+use crate::services::diagnostics;
+use crate::services::terminal_output;
+use crate::services::{SshEvent, SshService};
 
 /// SSH service state wrapper
 pub struct SshServiceState(pub Arc<SshService>);
 
+/// Forward SSH service events to the frontend
+pub fn spawn_ssh_event_forwarder(app: AppHandle, service: &SshService) {
+    let (tx, mut rx) = mpsc::unbounded::<SshEvent>();
+    service.set_event_sender(tx);
+
+    tauri::async_runtime::spawn(async move {
+        while let Some(event) = rx.next().await {
+            match event {
+                SshEvent::HostKeyPrompt(prompt) => {
+                    let _ = app.emit("ssh-host-key-prompt", prompt);
+                }
+                SshEvent::KeyboardInteractivePrompt(prompt) => {
+                    let _ = app.emit("ssh-auth-prompt", prompt);
+                }
+                SshEvent::Status(status) => {
+                    let _ = app.emit(&format!("ssh-status-{}", status.session_id), status);
+                }
+                SshEvent::Metrics(sample) => {
+                    let _ = app.emit(&format!("ssh-metrics-{}", sample.session_id), sample);
+                }
+                SshEvent::MetricsStopped(stopped) => {
+                    let _ = app.emit(
+                        &format!("ssh-metrics-stopped-{}", stopped.session_id),
+                        stopped,
+                    );
+                }
+            }
+        }
+    });
+}
+
 /// Connect to SSH server
 #[tauri::command]
 pub async fn ssh_connect(
     app: AppHandle,
     state: State<'_, SshServiceState>,
     request: SshConnectRequest,
-) -> Result<String, String> {
+) -> Result<String, ConnectError> {
     let service = &state.0;
 
-    // Create channel for data streaming
-    let (tx, mut rx) = mpsc::unbounded::<Vec<u8>>();
+    // Create the output stream, bounded and coalesced into frames
+    let (tx, mut rx) = terminal_output::channel();
 
     // Connect based on auth type
     let session_id = match request.auth_type.as_str() {
         "password" => service
             .connect_with_password(request, tx)
             .await
-            .map_err(|e| e.to_string())?,
+            .map_err(|e| diagnostics::classify(&e))?,
         "key" => service
             .connect_with_key(request, tx)
             .await
-            .map_err(|e| e.to_string())?,
-        _ => return Err("Unsupported authentication type".to_string()),
+            .map_err(|e| diagnostics::classify(&e))?,
+        "interactive" => service
+            .connect_interactive(request, tx)
+            .await
+            .map_err(|e| diagnostics::classify(&e))?,
+        "agent" => service
+            .connect_with_agent(request, tx)
+            .await
+            .map_err(|e| diagnostics::classify(&e))?,
+        _ => {
+            return Err(ConnectError::new(
+                ConnectErrorKind::Config,
+                "Unsupported authentication type",
+            ))
+        }
     };
 
     // Spawn task to forward SSH data to frontend
     let session_id_clone = session_id.clone();
     let app_clone = app.clone();
+    let service = service.clone();
     tokio::spawn(async move {
-        while let Some(data) = rx.next().await {
+        while let Some(frame) = rx.next_frame().await {
             // Emit data event to frontend
             let _ = app_clone.emit(
                 &format!("ssh-data-{}", session_id_clone),
-                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &data),
+                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &frame),
             );
         }
+        service.output_ended(&session_id_clone);
         // Session ended, emit disconnect event
-        let _ = app_clone.emit(&format!("ssh-status-{}", session_id_clone), "disconnected");
+        let _ = app_clone.emit(
+            &format!("ssh-status-{}", session_id_clone),
+            SshStatusEvent {
+                session_id: session_id_clone,
+                status: SessionStatus::Disconnected,
+                message: None,
+            },
+        );
     });
 
     Ok(session_id)
@@ -133,12 +191,22 @@ pub async fn ssh_is_connected(state: State<'_, SshServiceState>, session_id: Str
 pub async fn ssh_test_connection(
     state: State<'_, SshServiceState>,
     request: SshConnectRequest,
-) -> Result<(), String> {
+) -> Result<(), ConnectError> {
     let service = &state.0;
     service
         .test_connection(&request)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| diagnostics::classify(&e))
+}
+
+/// Test SSH connection stage by stage and report on each
+#[tauri::command]
+pub async fn ssh_diagnose_connection(
+    state: State<'_, SshServiceState>,
+    request: SshConnectRequest,
+) -> Result<ConnectionDiagnostics, String> {
+    let service = &state.0;
+    Ok(service.diagnose_connection(&request).await)
 }
 
 /// Reconnect SSH session
@@ -147,44 +215,86 @@ pub async fn ssh_reconnect(
     app: AppHandle,
     state: State<'_, SshServiceState>,
     session_id: String,
-) -> Result<String, String> {
+) -> Result<String, ConnectError> {
     let service = &state.0;
 
-    // Create channel for data streaming
-    let (tx, mut rx) = mpsc::unbounded::<Vec<u8>>();
+    // Create the output stream, bounded and coalesced into frames
+    let (tx, mut rx) = terminal_output::channel();
 
     // Reconnect
     let new_session_id = service
         .reconnect(&session_id, tx)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| diagnostics::classify(&e))?;
 
     // Spawn task to forward SSH data to frontend
     let new_session_id_clone = new_session_id.clone();
     let app_clone = app.clone();
+    let service = service.clone();
     tokio::spawn(async move {
-        while let Some(data) = rx.next().await {
+        while let Some(frame) = rx.next_frame().await {
             let _ = app_clone.emit(
                 &format!("ssh-data-{}", new_session_id_clone),
-                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &data),
+                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &frame),
             );
         }
-        let _ = app_clone.emit(&format!("ssh-status-{}", new_session_id_clone), "disconnected");
+        service.output_ended(&new_session_id_clone);
+        let _ = app_clone.emit(
+            &format!("ssh-status-{}", new_session_id_clone),
+            SshStatusEvent {
+                session_id: new_session_id_clone,
+                status: SessionStatus::Disconnected,
+                message: None,
+            },
+        );
     });
 
     Ok(new_session_id)
 }
 
-/// Execute a command on the remote server
+/// Answer a host key prompt (accept and remember, or reject)
 #[tauri::command]
-pub async fn ssh_exec_command(
+pub async fn ssh_host_key_respond(
     state: State<'_, SshServiceState>,
-    session_id: String,
-    command: String,
-) -> Result<String, String> {
-    let service = &state.0;
-    service
-        .exec_command(&session_id, &command)
-        .await
+    request_id: String,
+    accept: bool,
+) -> Result<(), String> {
+    state
+        .0
+        .respond_host_key(&request_id, accept)
+        .map_err(|e| e.to_string())
+}
+
+/// Answer a keyboard-interactive prompt (omit `responses` to cancel)
+#[tauri::command]
+pub async fn ssh_auth_prompt_respond(
+    state: State<'_, SshServiceState>,
+    request_id: String,
+    responses: Option<Vec<String>>,
+) -> Result<(), String> {
+    state
+        .0
+        .respond_keyboard_interactive(&request_id, responses)
+        .map_err(|e| e.to_string())
+}
+
+/// List known_hosts entries
+#[tauri::command]
+pub async fn ssh_known_hosts_list(
+    state: State<'_, SshServiceState>,
+) -> Result<Vec<KnownHostEntry>, String> {
+    Ok(state.0.list_known_hosts())
+}
+
+/// Remove recorded host keys so the host can be trusted again
+#[tauri::command]
+pub async fn ssh_known_hosts_remove(
+    state: State<'_, SshServiceState>,
+    host: String,
+    port: u16,
+) -> Result<usize, String> {
+    state
+        .0
+        .remove_known_host(&host, port)
         .map_err(|e| e.to_string())
 }
@@ -0,0 +1,17 @@
+//! SSH Config Import Tauri Commands
+//!
+//! Provides Tauri commands for importing connections from OpenSSH client config.
+
+use crate::models::SshConfigImport;
+use crate::services::ssh_config;
+
+/// Read an OpenSSH client config (`~/.ssh/config` unless `path` is given)
+/// and return the connections it describes for preview. Nothing is saved;
+/// the frontend stores the entries the user picks.
+#[tauri::command]
+pub async fn ssh_config_import_preview(path: Option<String>) -> Result<SshConfigImport, String> {
+    tokio::task::spawn_blocking(move || ssh_config::import(path.as_deref()))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,45 @@
+//! Telnet Tauri Commands
+//!
+//! Provides Tauri commands for telnet terminals. Once open, a telnet session
+//! is driven by the SSH session commands and streams the same events.
+
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::SshServiceState;
+use crate::models::{ConnectError, TelnetConnectRequest};
+use crate::services::diagnostics;
+use crate::services::terminal_output;
+
+/// Open a telnet session
+#[tauri::command]
+pub async fn telnet_connect(
+    app: AppHandle,
+    state: State<'_, SshServiceState>,
+    request: TelnetConnectRequest,
+) -> Result<String, ConnectError> {
+    let service = &state.0;
+
+    // Create the output stream, bounded and coalesced into frames
+    let (tx, mut rx) = terminal_output::channel();
+
+    let session_id = service
+        .connect_telnet(&request, tx)
+        .await
+        .map_err(|e| diagnostics::classify(&e))?;
+
+    // Spawn task to forward telnet output to frontend, as for SSH sessions
+    let session_id_clone = session_id.clone();
+    let service = service.clone();
+    tokio::spawn(async move {
+        while let Some(frame) = rx.next_frame().await {
+            let _ = app.emit(
+                &format!("ssh-data-{}", session_id_clone),
+                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &frame),
+            );
+        }
+        service.output_ended(&session_id_clone);
+        // The service reports the connection closing, with the reason
+    });
+
+    Ok(session_id)
+}
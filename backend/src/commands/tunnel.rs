@@ -0,0 +1,69 @@
+//! Port Forwarding Tauri Commands
+//!
+//! Provides Tauri commands for managing SSH tunnels.
+
+use tauri::State;
+
+use crate::commands::SshServiceState;
+use crate::models::{DynamicForwardRequest, LocalForwardRequest, RemoteForwardRequest, TunnelInfo};
+
+/// Open a local port forward on an existing SSH session
+#[tauri::command]
+pub async fn ssh_tunnel_create_local(
+    state: State<'_, SshServiceState>,
+    request: LocalForwardRequest,
+) -> Result<TunnelInfo, String> {
+    state
+        .0
+        .create_local_tunnel(request)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Open a remote port forward: the server listens and forwards to a local address
+#[tauri::command]
+pub async fn ssh_tunnel_create_remote(
+    state: State<'_, SshServiceState>,
+    request: RemoteForwardRequest,
+) -> Result<TunnelInfo, String> {
+    state
+        .0
+        .create_remote_tunnel(request)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Open a dynamic port forward: a local SOCKS5 server tunnelled through the session
+#[tauri::command]
+pub async fn ssh_tunnel_create_dynamic(
+    state: State<'_, SshServiceState>,
+    request: DynamicForwardRequest,
+) -> Result<TunnelInfo, String> {
+    state
+        .0
+        .create_dynamic_tunnel(request)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// List tunnels with their traffic counters (all sessions if `session_id` is omitted)
+#[tauri::command]
+pub async fn ssh_tunnel_list(
+    state: State<'_, SshServiceState>,
+    session_id: Option<String>,
+) -> Result<Vec<TunnelInfo>, String> {
+    Ok(state.0.list_tunnels(session_id.as_deref()))
+}
+
+/// Close a tunnel
+#[tauri::command]
+pub async fn ssh_tunnel_close(
+    state: State<'_, SshServiceState>,
+    tunnel_id: String,
+) -> Result<(), String> {
+    state
+        .0
+        .close_tunnel(&tunnel_id)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -7,7 +7,6 @@ pub mod models;
 pub mod services;
 
 use std::sync::Arc;
-#[cfg(debug_assertions)]
 use tauri::Manager;
 
 use commands::{CryptoServiceState, DatabaseServiceState, SftpServiceState, SshServiceState};
@@ -20,6 +19,7 @@ pub fn run() {
     let sftp_service = Arc::new(SftpService::new());
     let database_service = Arc::new(DatabaseService::new());
     let crypto_service = Arc::new(CryptoService::new());
+    let ssh_service_setup = ssh_service.clone();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
@@ -39,8 +39,59 @@ pub fn run() {
             commands::ssh_get_all_sessions,
             commands::ssh_is_connected,
             commands::ssh_test_connection,
+            commands::ssh_diagnose_connection,
             commands::ssh_reconnect,
-            commands::ssh_exec_command,
+            commands::ssh_host_key_respond,
+            commands::ssh_auth_prompt_respond,
+            commands::ssh_known_hosts_list,
+            commands::ssh_known_hosts_remove,
+            // SSH config import commands
+            commands::ssh_config_import_preview,
+            // Local shell commands
+            commands::local_shell_open,
+            commands::local_shell_list,
+            // Telnet commands
+            commands::telnet_connect,
+            // Scrollback commands
+            commands::ssh_scrollback_fetch,
+            commands::ssh_scrollback_search,
+            commands::ssh_scrollback_lines,
+            commands::ssh_scrollback_export,
+            // Port forwarding commands
+            commands::ssh_tunnel_create_local,
+            commands::ssh_tunnel_create_remote,
+            commands::ssh_tunnel_create_dynamic,
+            commands::ssh_tunnel_list,
+            commands::ssh_tunnel_close,
+            // Broadcast input commands
+            commands::ssh_broadcast_create,
+            commands::ssh_broadcast_join,
+            commands::ssh_broadcast_leave,
+            commands::ssh_broadcast_delete,
+            commands::ssh_broadcast_list,
+            commands::ssh_broadcast_send_data,
+            commands::ssh_broadcast_resize,
+            // Remote command execution commands
+            commands::ssh_exec,
+            commands::ssh_exec_stream,
+            commands::ssh_exec_cancel,
+            // Batch execution commands
+            commands::ssh_batch_start,
+            commands::ssh_batch_cancel,
+            commands::ssh_batch_list,
+            commands::ssh_batch_get,
+            commands::ssh_batch_delete,
+            // Host metrics commands
+            commands::ssh_metrics_start,
+            commands::ssh_metrics_stop,
+            commands::ssh_metrics_history,
+            // Session recording commands
+            commands::ssh_recording_start,
+            commands::ssh_recording_stop,
+            commands::ssh_recording_list,
+            commands::ssh_recording_delete,
+            commands::ssh_recording_replay,
+            commands::ssh_recording_replay_stop,
             // SFTP commands
             commands::sftp_open,
             commands::sftp_close,
@@ -91,10 +142,14 @@ pub fn run() {
             commands::decrypt_storage,
             commands::is_storage_encrypted,
         ])
-        .setup(|app| {
-            // Silence unused variable warning in release mode
-            #[cfg(not(debug_assertions))]
-            let _ = &app;
+        .setup(move |app| {
+            // Persistent SSH state (known_hosts, ...) lives in the app data directory
+            let data_dir = app.path().app_data_dir()?;
+            if let Err(e) = ssh_service_setup.set_data_dir(data_dir) {
+                log::error!("Failed to load SSH data: {}", e);
+            }
+            commands::spawn_ssh_event_forwarder(app.handle().clone(), &ssh_service_setup);
+
             #[cfg(debug_assertions)]
             {
                 app.handle().plugin(
@@ -0,0 +1,143 @@
+//! Batch execution models
+//!
+//! Defines data structures for running one command on many hosts.
+
+use serde::{Deserialize, Serialize};
+
+use super::exec::ExecResult;
+use super::ssh::SshConnectRequest;
+
+/// Host a batch command runs on: an open session, or connection settings
+/// for a connection opened on demand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTarget {
+    /// Display name, e.g. the saved connection name
+    pub name: String,
+    /// Run on the connection of this open session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Connect with these settings; an open connection to the same
+    /// user@host:port is reused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<SshConnectRequest>,
+}
+
+fn default_concurrency() -> usize {
+    10
+}
+
+fn default_host_timeout_ms() -> u64 {
+    60_000
+}
+
+/// Request to run a command on many hosts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchJobRequest {
+    pub command: String,
+    /// Written to the command's standard input on every host
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin: Option<String>,
+    pub targets: Vec<BatchTarget>,
+    /// Hosts worked on at the same time
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// Time allowed per host, connecting included (milliseconds)
+    #[serde(default = "default_host_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+/// Batch job state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchJobStatus {
+    Running,
+    Completed,
+    Cancelled,
+}
+
+/// Outcome of a batch command on one host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchHostResult {
+    /// Position of the target in the request
+    pub index: usize,
+    pub name: String,
+    pub host: String,
+    /// Command output and exit status, absent if the command did not run
+    #[serde(flatten)]
+    pub result: Option<ExecResult>,
+    /// Why the command did not run (e.g. connection failure)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchHostResult {
+    /// Whether the command ran and exited with status 0
+    pub fn succeeded(&self) -> bool {
+        self.result
+            .as_ref()
+            .is_some_and(|result| result.status.exit_code == Some(0))
+    }
+}
+
+/// Batch job with the results received so far
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchJob {
+    pub job_id: String,
+    pub command: String,
+    pub status: BatchJobStatus,
+    /// Start time (RFC 3339)
+    pub started_at: String,
+    /// End time (RFC 3339)
+    pub finished_at: Option<String>,
+    /// Number of targets
+    pub total: usize,
+    /// Host results in the order they finished
+    pub results: Vec<BatchHostResult>,
+}
+
+/// Batch job without its host results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchJobSummary {
+    pub job_id: String,
+    pub command: String,
+    pub status: BatchJobStatus,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub total: usize,
+    /// Hosts where the command exited with status 0
+    pub succeeded: usize,
+    /// Hosts finished otherwise
+    pub failed: usize,
+}
+
+impl BatchJob {
+    pub fn summary(&self) -> BatchJobSummary {
+        let succeeded = self.results.iter().filter(|r| r.succeeded()).count();
+        BatchJobSummary {
+            job_id: self.job_id.clone(),
+            command: self.command.clone(),
+            status: self.status,
+            started_at: self.started_at.clone(),
+            finished_at: self.finished_at.clone(),
+            total: self.total,
+            succeeded,
+            failed: self.results.len() - succeeded,
+        }
+    }
+}
+
+/// Progress of a running batch job, sent as each host finishes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchProgressEvent {
+    pub job_id: String,
+    /// Hosts finished so far
+    pub completed: usize,
+    pub total: usize,
+    pub result: BatchHostResult,
+}
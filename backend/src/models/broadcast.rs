@@ -0,0 +1,31 @@
+//! Broadcast input models
+//!
+//! Defines data structures for sending input to several terminal sessions at once.
+
+use serde::{Deserialize, Serialize};
+
+/// Broadcast group information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastGroupInfo {
+    pub group_id: String,
+    pub name: String,
+    /// Member sessions in the order they joined
+    pub session_ids: Vec<String>,
+}
+
+/// A member session that could not be reached
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastFailure {
+    pub session_id: String,
+    pub error: String,
+}
+
+/// Outcome of sending data or a resize to a broadcast group
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastReport {
+    pub delivered: Vec<String>,
+    pub failed: Vec<BroadcastFailure>,
+}
@@ -2,7 +2,7 @@
 //!
 //! Defines the data structures for various connection types.
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 /// Module type for categorizing connections
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -21,6 +21,7 @@ pub enum SshAuthType {
     Password,
     Key,
     Interactive,
+    Agent,
 }
 
 /// Proxy type for SSH connections
@@ -45,6 +46,28 @@ pub struct JumpHostConfig {
     pub private_key: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub passphrase: Option<String>,
+    /// OpenSSH certificate for the private key (contents of `*-cert.pub`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<String>,
+}
+
+/// Deserialize a jump host chain, also accepting the legacy single `jumpHost` object
+pub(crate) fn deserialize_jump_hosts<'de, D>(deserializer: D) -> Result<Vec<JumpHostConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(JumpHostConfig),
+        Many(Vec<JumpHostConfig>),
+    }
+
+    Ok(match Option::<OneOrMany>::deserialize(deserializer)? {
+        Some(OneOrMany::One(jump)) => vec![jump],
+        Some(OneOrMany::Many(jumps)) => jumps,
+        None => Vec::new(),
+    })
 }
 
 /// Proxy configuration
@@ -60,6 +83,98 @@ pub struct ProxyConfig {
     pub password: Option<String>,
 }
 
+/// SSH algorithm preferences, most preferred first. An empty list keeps the
+/// default for that kind; legacy devices may need older algorithms enabled.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlgorithmPreferences {
+    /// Key exchange algorithms (`diffie-hellman-group14-sha1`...)
+    #[serde(default)]
+    pub kex: Vec<String>,
+    /// Host key algorithms (`ssh-rsa`...)
+    #[serde(default)]
+    pub host_key: Vec<String>,
+    /// Ciphers (`aes128-cbc`...)
+    #[serde(default)]
+    pub cipher: Vec<String>,
+    /// MAC algorithms (`hmac-sha1`...)
+    #[serde(default)]
+    pub mac: Vec<String>,
+}
+
+/// SSH keepalive settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeepaliveSettings {
+    /// Seconds of silence before a keepalive is sent, 0 disables keepalives
+    #[serde(default = "default_keepalive_interval")]
+    pub interval: u64,
+    /// Unanswered keepalives after which the server is considered dead
+    #[serde(default = "default_keepalive_max")]
+    pub max_missed: usize,
+}
+
+fn default_keepalive_interval() -> u64 {
+    30
+}
+
+fn default_keepalive_max() -> usize {
+    3
+}
+
+impl Default for KeepaliveSettings {
+    fn default() -> Self {
+        Self {
+            interval: default_keepalive_interval(),
+            max_missed: default_keepalive_max(),
+        }
+    }
+}
+
+/// Automatic reconnect policy with exponential backoff
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconnectPolicy {
+    #[serde(default = "default_reconnect_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first attempt, doubled for every further attempt
+    #[serde(default = "default_reconnect_initial_delay")]
+    pub initial_delay_ms: u64,
+    #[serde(default = "default_reconnect_max_delay")]
+    pub max_delay_ms: u64,
+}
+
+fn default_reconnect_attempts() -> u32 {
+    5
+}
+
+fn default_reconnect_initial_delay() -> u64 {
+    1000
+}
+
+fn default_reconnect_max_delay() -> u64 {
+    30000
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_reconnect_attempts(),
+            initial_delay_ms: default_reconnect_initial_delay(),
+            max_delay_ms: default_reconnect_max_delay(),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Backoff before the given attempt (starting at 1)
+    pub fn delay(&self, attempt: u32) -> std::time::Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(32);
+        let delay = self.initial_delay_ms.saturating_mul(factor);
+        std::time::Duration::from_millis(delay.min(self.max_delay_ms))
+    }
+}
+
 /// Terminal display settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -110,10 +225,27 @@ pub struct SshConnectionConfig {
     pub private_key: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub passphrase: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub jump_host: Option<JumpHostConfig>,
+    /// OpenSSH certificate for the private key (contents of `*-cert.pub`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<String>,
+    #[serde(
+        default,
+        alias = "jumpHost",
+        deserialize_with = "deserialize_jump_hosts",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub jump_hosts: Vec<JumpHostConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub proxy: Option<ProxyConfig>,
     #[serde(default)]
+    pub agent_forwarding: bool,
+    #[serde(default)]
+    pub keepalive: KeepaliveSettings,
+    /// Algorithm preferences, used for every hop of the connection
+    #[serde(default)]
+    pub algorithms: AlgorithmPreferences,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_reconnect: Option<ReconnectPolicy>,
+    #[serde(default)]
     pub terminal_settings: TerminalSettings,
 }
@@ -0,0 +1,113 @@
+//! Connection diagnostics models
+//!
+//! Defines the typed connection errors and the staged report of a
+//! connection test.
+
+use serde::{Deserialize, Serialize};
+
+use super::ssh::NegotiatedAlgorithms;
+
+/// Category of a failed connection attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConnectErrorKind {
+    /// Invalid connection settings (unreadable key, unknown algorithm...)
+    Config,
+    /// The host name could not be resolved
+    Resolve,
+    /// The host refused the TCP connection
+    Refused,
+    /// The host or network could not be reached
+    Unreachable,
+    Timeout,
+    /// The SOCKS5/HTTP proxy failed
+    Proxy,
+    /// The peer did not speak SSH
+    Protocol,
+    /// No algorithm of some kind in common with the server
+    KeyExchange,
+    /// The server's host key was not trusted
+    HostKey,
+    /// The server rejected the credentials
+    Auth,
+    /// The server closed the connection
+    Disconnected,
+    Other,
+}
+
+/// Connection error with its category
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectError {
+    pub kind: ConnectErrorKind,
+    pub message: String,
+    /// Jump host the error happened on, absent for the target
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hop: Option<String>,
+}
+
+impl ConnectError {
+    pub fn new(kind: ConnectErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            hop: None,
+        }
+    }
+}
+
+/// Stage of connecting to a host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticStage {
+    JumpHosts,
+    Resolve,
+    TcpConnect,
+    Banner,
+    KeyExchange,
+    HostKey,
+    Authentication,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StageStatus {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+/// Outcome of one stage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageReport {
+    pub stage: DiagnosticStage,
+    pub status: StageStatus,
+    /// Time the stage took, absent if it was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// What the stage found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ConnectError>,
+}
+
+/// Staged report of a connection test. Stages after the first failure are
+/// not run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionDiagnostics {
+    pub host: String,
+    pub port: u16,
+    pub success: bool,
+    pub stages: Vec<StageReport>,
+    /// Version line the server sent (`SSH-2.0-OpenSSH_9.6`...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub algorithms: Option<NegotiatedAlgorithms>,
+    /// Error that ended the test
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ConnectError>,
+}
@@ -0,0 +1,75 @@
+//! Remote command execution models
+//!
+//! Defines data structures for running non-interactive commands over SSH.
+
+use serde::{Deserialize, Serialize};
+
+/// Request to run a command on the connection of an SSH session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecRequest {
+    pub session_id: String,
+    pub command: String,
+    /// Written to the command's standard input, which is closed afterwards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin: Option<String>,
+    /// Kill the command if it has not exited after this many milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+}
+
+/// How a command ended
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecStatus {
+    /// Exit code, absent if the server did not report one (e.g. killed by a signal)
+    pub exit_code: Option<u32>,
+    /// Name of the signal that terminated the command, e.g. "KILL"
+    pub exit_signal: Option<String>,
+    pub duration_ms: u64,
+    /// The command was stopped because it exceeded its timeout
+    pub timed_out: bool,
+    /// The command was stopped by a cancel request
+    pub cancelled: bool,
+}
+
+/// Result of a command run to completion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecResult {
+    pub stdout: String,
+    pub stderr: String,
+    /// Output past the limit of what is kept was dropped
+    #[serde(default)]
+    pub truncated: bool,
+    #[serde(flatten)]
+    pub status: ExecStatus,
+}
+
+/// Output stream of a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecStream {
+    Stdout,
+    Stderr,
+}
+
+/// Incremental output of a streaming command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecOutputEvent {
+    pub exec_id: String,
+    pub stream: ExecStream,
+    pub data: String,
+}
+
+/// Final event of a streaming command, carrying either its status or an error
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecFinishedEvent {
+    pub exec_id: String,
+    #[serde(flatten)]
+    pub status: Option<ExecStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
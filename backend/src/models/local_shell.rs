@@ -0,0 +1,45 @@
+//! Local shell models
+//!
+//! Defines data structures for terminal sessions running a shell on the
+//! local machine.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::ssh::TerminalSize;
+
+fn default_term() -> String {
+    "xterm-256color".to_string()
+}
+
+/// Request to open a local shell session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalShellRequest {
+    /// Path or name of the shell, the user's default shell if absent
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Working directory, the home directory if absent
+    pub cwd: Option<String>,
+    /// Value of TERM
+    #[serde(default = "default_term")]
+    pub term: String,
+    /// Variables added to the environment inherited from the app
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub terminal_size: TerminalSize,
+}
+
+/// Shell available on the local machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalShellInfo {
+    /// Display name (`bash`, `zsh`, `PowerShell`...)
+    pub name: String,
+    pub path: String,
+    /// Whether this is the user's default shell
+    pub default: bool,
+}
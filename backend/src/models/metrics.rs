@@ -0,0 +1,119 @@
+//! Host metrics models
+//!
+//! Defines data structures for the resource usage sampled from remote hosts.
+
+use serde::{Deserialize, Serialize};
+
+fn default_interval_secs() -> u64 {
+    2
+}
+
+/// Request to collect metrics on the host of an SSH session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsRequest {
+    pub session_id: String,
+    /// Seconds between samples
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+}
+
+/// Metrics collector of a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsMonitorInfo {
+    pub session_id: String,
+    pub interval_secs: u64,
+    /// Samples kept in the history
+    pub samples: usize,
+}
+
+/// CPU usage since the previous sample
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CpuMetrics {
+    /// Busy time of all cores, 0-100
+    pub usage_percent: f64,
+    /// Time spent waiting for I/O, 0-100
+    pub iowait_percent: f64,
+    /// Busy time of each core, 0-100
+    pub cores: Vec<f64>,
+}
+
+/// Memory usage from /proc/meminfo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryMetrics {
+    pub total_bytes: u64,
+    /// Memory available to new programs without swapping
+    pub available_bytes: u64,
+    pub used_bytes: u64,
+    pub usage_percent: f64,
+    pub swap_total_bytes: u64,
+    pub swap_used_bytes: u64,
+}
+
+/// Usage of a mounted filesystem from `df`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskMetrics {
+    pub filesystem: String,
+    pub mount_point: String,
+    pub total_bytes: u64,
+    pub used_bytes: u64,
+    pub available_bytes: u64,
+    pub usage_percent: f64,
+}
+
+/// Traffic of a network interface from /proc/net/dev
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkMetrics {
+    pub interface: String,
+    /// Bytes received since the interface came up
+    pub rx_bytes: u64,
+    /// Bytes sent since the interface came up
+    pub tx_bytes: u64,
+    /// Receive rate, absent on the first sample or after a counter reset
+    pub rx_bytes_per_sec: Option<f64>,
+    /// Send rate, absent on the first sample or after a counter reset
+    pub tx_bytes_per_sec: Option<f64>,
+}
+
+/// System load from /proc/loadavg
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadMetrics {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+    /// Runnable tasks
+    pub running: u32,
+    /// All tasks
+    pub tasks: u32,
+}
+
+/// One sample of a host's resource usage. Parts the host did not report are
+/// absent; CPU usage is absent on the first sample since it needs two.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsSample {
+    pub session_id: String,
+    /// Time the sample was received (milliseconds since the Unix epoch)
+    pub timestamp: i64,
+    pub uptime_secs: Option<f64>,
+    pub cpu: Option<CpuMetrics>,
+    pub memory: Option<MemoryMetrics>,
+    pub load: Option<LoadMetrics>,
+    pub disks: Vec<DiskMetrics>,
+    pub network: Vec<NetworkMetrics>,
+}
+
+/// Sent once when a metrics collector stops, with the reason if it failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsStoppedEvent {
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -2,12 +2,34 @@
 //!
 //! This module contains all data structures used across the application.
 
+pub mod batch;
+pub mod broadcast;
 pub mod connection;
 pub mod database;
+pub mod diagnostics;
+pub mod exec;
+pub mod local_shell;
+pub mod metrics;
+pub mod recording;
+pub mod scrollback;
 pub mod sftp;
 pub mod ssh;
+pub mod ssh_config;
+pub mod telnet;
+pub mod tunnel;
 
+pub use batch::*;
+pub use broadcast::*;
 pub use connection::*;
 pub use database::*;
+pub use diagnostics::*;
+pub use exec::*;
+pub use local_shell::*;
+pub use metrics::*;
+pub use recording::*;
+pub use scrollback::*;
 pub use sftp::*;
 pub use ssh::*;
+pub use ssh_config::*;
+pub use telnet::*;
+pub use tunnel::*;
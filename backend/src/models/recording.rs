@@ -0,0 +1,44 @@
+//! Session recording models
+//!
+//! Defines data structures for asciicast v2 terminal recordings.
+
+use serde::{Deserialize, Serialize};
+
+/// Recording options for an SSH session
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingOptions {
+    /// Also record keystrokes sent to the session (may capture secrets)
+    #[serde(default)]
+    pub record_input: bool,
+}
+
+/// Recording file information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingInfo {
+    pub recording_id: String,
+    pub title: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    /// Start time (RFC 3339)
+    pub started_at: Option<String>,
+    /// Time of the last recorded event in seconds
+    pub duration: f64,
+    /// File size in bytes
+    pub size: u64,
+    /// Whether the recording is still being written
+    pub active: bool,
+}
+
+/// One event of a replayed recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayFrame {
+    pub replay_id: String,
+    /// Seconds since the start of the recording
+    pub time: f64,
+    /// asciicast event code: "o" output, "r" resize ("COLSxROWS")
+    pub kind: String,
+    pub data: String,
+}
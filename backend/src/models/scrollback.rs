@@ -0,0 +1,78 @@
+//! Scrollback models
+//!
+//! Defines data structures for the terminal output kept on the backend.
+
+use serde::{Deserialize, Serialize};
+
+/// Recent output of a terminal session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollbackSnapshot {
+    /// Output bytes, base64 encoded like `ssh-data-{id}` events
+    pub data: String,
+    /// Position of the first byte in the session's output
+    pub offset: u64,
+    /// Length of the session's output so far, which `data` ends at
+    pub end: u64,
+}
+
+/// Search of a session's scrollback text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollbackSearchRequest {
+    pub session_id: String,
+    pub query: String,
+    /// Whether `query` is a regular expression rather than plain text
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Most matches returned, oldest first (default 1000)
+    pub max_results: Option<usize>,
+}
+
+/// Match of a scrollback search. Matches do not span lines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollbackMatch {
+    /// Number of the line in the session's output, counted from 0
+    pub line: u64,
+    /// Character offsets of the match in the line, end exclusive
+    pub start: usize,
+    pub end: usize,
+    /// Text of the line
+    pub text: String,
+}
+
+/// Lines of a session's scrollback text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollbackLines {
+    /// Number of the first line returned
+    pub start: u64,
+    /// Number of the oldest line still kept
+    pub first_line: u64,
+    /// Number of lines so far; the last one may still be being written
+    pub total_lines: u64,
+    pub lines: Vec<String>,
+}
+
+/// File format of an exported scrollback
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollbackExportFormat {
+    /// Plain text without escape sequences
+    Text,
+    /// Standalone page keeping the colors and text attributes
+    Html,
+}
+
+/// Export of a session's scrollback to a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollbackExportRequest {
+    pub session_id: String,
+    /// Local file to write
+    pub path: String,
+    pub format: ScrollbackExportFormat,
+}
@@ -2,6 +2,8 @@
 //!
 //! Defines data structures for SSH sessions and events.
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 /// SSH session status
@@ -24,6 +26,45 @@ pub struct SshSessionInfo {
     pub connected_at: Option<String>,
     pub host: String,
     pub username: String,
+    /// Certificate presented with key authentication
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<CertificateInfo>,
+    /// Algorithms negotiated with the host, absent until connected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub algorithms: Option<NegotiatedAlgorithms>,
+}
+
+/// Algorithms negotiated with a host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NegotiatedAlgorithms {
+    pub kex: String,
+    pub host_key: String,
+    pub cipher_client_to_server: String,
+    pub cipher_server_to_client: String,
+    /// Absent for AEAD ciphers, which authenticate the data themselves
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac_client_to_server: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac_server_to_client: Option<String>,
+    /// Negotiated algorithms considered weak (SHA-1, MD5, CBC modes...)
+    pub weak: Vec<String>,
+}
+
+/// OpenSSH user certificate details
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateInfo {
+    pub key_id: String,
+    pub serial: u64,
+    /// Users the certificate is valid for; empty means any
+    pub principals: Vec<String>,
+    /// Start of validity (RFC 3339), absent if valid since forever
+    pub valid_after: Option<String>,
+    /// End of validity (RFC 3339), absent if it never expires
+    pub valid_before: Option<String>,
+    /// SHA256 fingerprint of the signing CA key
+    pub ca_fingerprint: String,
 }
 
 /// Terminal size configuration
@@ -40,8 +81,65 @@ impl Default for TerminalSize {
     }
 }
 
+fn default_term() -> String {
+    "xterm-256color".to_string()
+}
+
+/// Lines of scrollback kept per session unless configured otherwise
+pub const DEFAULT_SCROLLBACK_LINES: usize = 10_000;
+
+/// Fewest lines of scrollback a session can ask for
+pub const MIN_SCROLLBACK_LINES: usize = 100;
+
+/// Most lines of scrollback a session can ask for; lines are kept in memory
+/// for as long as the session is open
+pub const MAX_SCROLLBACK_LINES: usize = 100_000;
+
+fn default_scrollback_lines() -> usize {
+    DEFAULT_SCROLLBACK_LINES
+}
+
+/// Terminal type, environment and PTY modes of a session's shell
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalOptions {
+    /// Terminal type sent with the PTY request (`TERM` on the host)
+    #[serde(default = "default_term")]
+    pub term: String,
+    /// Environment variables for the shell. Servers only set the names their
+    /// `AcceptEnv` allows and silently ignore the others.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// PTY modes by their RFC 4254 name (`ECHO`, `VERASE`, `TTY_OP_ISPEED`...)
+    #[serde(default)]
+    pub pty_modes: BTreeMap<String, u32>,
+    /// Commands typed into the shell once it has started, in order
+    #[serde(default)]
+    pub startup_commands: Vec<String>,
+    /// Lines of output kept on the backend for search and export, from
+    /// `MIN_SCROLLBACK_LINES` to `MAX_SCROLLBACK_LINES`
+    #[serde(default = "default_scrollback_lines")]
+    pub scrollback_lines: usize,
+}
+
+impl Default for TerminalOptions {
+    fn default() -> Self {
+        Self {
+            term: default_term(),
+            env: BTreeMap::new(),
+            pty_modes: BTreeMap::new(),
+            startup_commands: Vec::new(),
+            scrollback_lines: default_scrollback_lines(),
+        }
+    }
+}
+
 // JumpHostConfig is defined in connection.rs
-use super::connection::JumpHostConfig;
+use super::connection::{
+    deserialize_jump_hosts, AlgorithmPreferences, JumpHostConfig, KeepaliveSettings, ProxyConfig,
+    ReconnectPolicy,
+};
+use super::recording::RecordingOptions;
 
 /// SSH connect request from frontend
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,10 +156,38 @@ pub struct SshConnectRequest {
     pub private_key: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub passphrase: Option<String>,
+    /// OpenSSH certificate for the private key (contents of `*-cert.pub`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<String>,
+    /// Jump hosts in hop order (first entry is dialed directly)
+    #[serde(
+        default,
+        alias = "jumpHost",
+        deserialize_with = "deserialize_jump_hosts",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub jump_hosts: Vec<JumpHostConfig>,
+    /// SOCKS5/HTTP proxy used to reach the first hop
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub jump_host: Option<JumpHostConfig>,
+    pub proxy: Option<ProxyConfig>,
+    /// Let the remote host use the local ssh-agent (opt-in)
+    #[serde(default)]
+    pub agent_forwarding: bool,
+    #[serde(default)]
+    pub keepalive: KeepaliveSettings,
+    /// Algorithm preferences, used for every hop of the connection
+    #[serde(default)]
+    pub algorithms: AlgorithmPreferences,
+    /// Reconnect automatically when the connection drops (opt-in)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_reconnect: Option<ReconnectPolicy>,
+    /// Record the session to an asciicast file (opt-in)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recording: Option<RecordingOptions>,
     #[serde(default)]
     pub terminal_size: TerminalSize,
+    #[serde(default)]
+    pub terminal: TerminalOptions,
 }
 
 /// SSH data event for streaming
@@ -73,9 +199,66 @@ pub struct SshDataEvent {
 
 /// SSH status event
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SshStatusEvent {
     pub session_id: String,
     pub status: SessionStatus,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
 }
+
+/// Host key verification status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostKeyStatus {
+    Unknown,
+    Changed,
+}
+
+/// Host key prompt sent to the frontend when a server key is not trusted yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostKeyPromptEvent {
+    pub request_id: String,
+    pub host: String,
+    pub port: u16,
+    pub key_type: String,
+    pub fingerprint: String,
+    pub status: HostKeyStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_fingerprint: Option<String>,
+}
+
+/// Entry of the known_hosts store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownHostEntry {
+    pub line: usize,
+    pub hosts: String,
+    pub hashed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+    pub key_type: String,
+    pub fingerprint: String,
+}
+
+/// Single keyboard-interactive prompt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyboardInteractivePrompt {
+    pub prompt: String,
+    /// Whether the answer may be displayed while typing
+    pub echo: bool,
+}
+
+/// Keyboard-interactive challenge sent to the frontend (OTP, 2FA, ...)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyboardInteractivePromptEvent {
+    pub request_id: String,
+    pub host: String,
+    pub username: String,
+    pub name: String,
+    pub instructions: String,
+    pub prompts: Vec<KeyboardInteractivePrompt>,
+}
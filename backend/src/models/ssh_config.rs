@@ -0,0 +1,29 @@
+//! SSH config import models
+//!
+//! Defines data structures for importing connections from OpenSSH client config.
+
+use serde::{Deserialize, Serialize};
+
+use super::connection::SshConnectionConfig;
+
+/// Connection built from one `Host` alias
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SshConfigEntry {
+    /// Alias the entry was built from
+    pub alias: String,
+    pub config: SshConnectionConfig,
+    /// Settings of this host that could not be imported
+    pub warnings: Vec<String>,
+}
+
+/// Preview of an SSH config import, nothing is saved yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SshConfigImport {
+    /// Config file that was read
+    pub path: String,
+    pub entries: Vec<SshConfigEntry>,
+    /// Problems not tied to one host (unreadable includes, unsupported Match criteria...)
+    pub warnings: Vec<String>,
+}
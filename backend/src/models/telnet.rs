@@ -0,0 +1,47 @@
+//! Telnet session models
+//!
+//! Defines data structures for terminal sessions over the telnet protocol.
+
+use serde::{Deserialize, Serialize};
+
+use super::ssh::TerminalSize;
+
+fn default_port() -> u16 {
+    23
+}
+
+fn default_term() -> String {
+    "xterm-256color".to_string()
+}
+
+/// Request to open a telnet session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelnetConnectRequest {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Answer to the login prompt, used when `login_script` is empty
+    pub username: Option<String>,
+    /// Answer to the password prompt, used when `login_script` is empty
+    pub password: Option<String>,
+    /// Prompts to answer in order once they appear
+    #[serde(default)]
+    pub login_script: Vec<TelnetLoginStep>,
+    /// Terminal type reported through TTYPE
+    #[serde(default = "default_term")]
+    pub term: String,
+    #[serde(default)]
+    pub terminal_size: TerminalSize,
+}
+
+/// Prompt of a scripted login and the line typed in reply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelnetLoginStep {
+    /// Texts the output must end with (case-insensitive, trailing spaces
+    /// ignored), e.g. `["login:", "username:"]`
+    pub prompts: Vec<String>,
+    /// Sent followed by Enter
+    pub answer: String,
+}
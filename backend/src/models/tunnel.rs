@@ -0,0 +1,93 @@
+//! Port forwarding models
+//!
+//! Defines data structures for SSH tunnels.
+
+use serde::{Deserialize, Serialize};
+
+/// Tunnel kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelKind {
+    /// Local listener forwarded to a host reachable from the server (`ssh -L`)
+    Local,
+    /// Server-side listener forwarded to a local address (`ssh -R`)
+    Remote,
+    /// Local SOCKS5 server, destinations chosen per connection (`ssh -D`)
+    Dynamic,
+}
+
+fn default_bind_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+/// Request to open a local port forward
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalForwardRequest {
+    pub session_id: String,
+    /// Local address to listen on
+    #[serde(default = "default_bind_host")]
+    pub bind_host: String,
+    /// Local port to listen on, 0 picks a free port
+    #[serde(default)]
+    pub bind_port: u16,
+    /// Destination as seen from the SSH server
+    pub remote_host: String,
+    pub remote_port: u16,
+}
+
+fn default_remote_bind_host() -> String {
+    "localhost".to_string()
+}
+
+/// Request to open a remote port forward
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteForwardRequest {
+    pub session_id: String,
+    /// Address the server listens on; an empty string means all interfaces
+    /// (subject to the server's `GatewayPorts` setting)
+    #[serde(default = "default_remote_bind_host")]
+    pub remote_bind_host: String,
+    /// Port the server listens on, 0 lets the server pick one
+    #[serde(default)]
+    pub remote_bind_port: u16,
+    /// Local destination of forwarded connections
+    pub local_host: String,
+    pub local_port: u16,
+}
+
+/// Request to open a dynamic (SOCKS5) port forward
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DynamicForwardRequest {
+    pub session_id: String,
+    /// Local address the SOCKS5 server listens on
+    #[serde(default = "default_bind_host")]
+    pub bind_host: String,
+    /// Local port to listen on, 0 picks a free port
+    #[serde(default)]
+    pub bind_port: u16,
+}
+
+/// Tunnel information with live traffic counters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TunnelInfo {
+    pub tunnel_id: String,
+    pub session_id: String,
+    pub kind: TunnelKind,
+    /// Listening address: local for `Local`, on the server for `Remote`
+    pub bind_host: String,
+    pub bind_port: u16,
+    /// Where accepted connections are forwarded to (empty for `Dynamic`)
+    pub target_host: String,
+    pub target_port: u16,
+    /// Bytes sent over the SSH connection
+    pub bytes_sent: u64,
+    /// Bytes received over the SSH connection
+    pub bytes_received: u64,
+    pub active_connections: u64,
+    pub total_connections: u64,
+    pub created_at: String,
+}
@@ -0,0 +1,499 @@
+//! SSH Algorithm Preferences
+//!
+//! Applies per-connection algorithm preferences to the russh client config
+//! and works out which algorithms a connection negotiated. russh does not
+//! report them, so the server's first KEXINIT is captured from the transport
+//! stream and the RFC 4253 rule (the client's first choice the server also
+//! supports) is applied to it.
+
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use anyhow::Result;
+use parking_lot::Mutex;
+use russh::{cipher, kex, mac, Preferred};
+use russh_keys::key;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::watch;
+
+use crate::models::{AlgorithmPreferences, ConnectErrorKind, NegotiatedAlgorithms};
+use crate::services::diagnostics::fail;
+
+/// SSH_MSG_KEXINIT
+const MSG_KEXINIT: u8 = 20;
+
+/// Bytes read before giving up on finding the KEXINIT
+const MAX_CAPTURE: usize = 64 * 1024;
+
+/// Ciphers that authenticate the data themselves, so no MAC is negotiated
+const AEAD_CIPHERS: &[&str] = &[
+    "chacha20-poly1305@openssh.com",
+    "aes128-gcm@openssh.com",
+    "aes256-gcm@openssh.com",
+];
+
+/// Build the russh algorithm preferences of a connection
+pub fn preferred(preferences: &AlgorithmPreferences) -> Result<Preferred> {
+    let mut preferred = Preferred::default();
+    if !preferences.kex.is_empty() {
+        let mut kex = resolve(&preferences.kex, kex::ALL_KEX_ALGORITHMS, "key exchange")?;
+        // Keep the extension negotiation and strict key exchange markers
+        kex.extend([
+            kex::EXTENSION_SUPPORT_AS_CLIENT,
+            kex::EXTENSION_OPENSSH_STRICT_KEX_AS_CLIENT,
+        ]);
+        preferred.kex = kex.into();
+    }
+    if !preferences.host_key.is_empty() {
+        preferred.key = resolve(&preferences.host_key, key::ALL_KEY_TYPES, "host key")?.into();
+    }
+    if !preferences.cipher.is_empty() {
+        preferred.cipher = resolve(&preferences.cipher, cipher::ALL_CIPHERS, "cipher")?.into();
+    }
+    if !preferences.mac.is_empty() {
+        preferred.mac = resolve(&preferences.mac, mac::ALL_MAC_ALGORITHMS, "MAC")?.into();
+    }
+    Ok(preferred)
+}
+
+/// Look up algorithm names among those russh implements
+fn resolve<N: AsRef<str> + Copy>(names: &[String], all: &[&N], kind: &str) -> Result<Vec<N>> {
+    names
+        .iter()
+        .map(|name| {
+            all.iter()
+                .find(|algorithm| algorithm.as_ref() == name.as_str())
+                .map(|algorithm| **algorithm)
+                .ok_or_else(|| {
+                    fail(
+                        ConnectErrorKind::Config,
+                        format!("Unsupported {} algorithm {}", kind, name),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Algorithm lists of one side of a key exchange, most preferred first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KexInit {
+    pub kex: Vec<String>,
+    pub host_key: Vec<String>,
+    pub cipher_client_to_server: Vec<String>,
+    pub cipher_server_to_client: Vec<String>,
+    pub mac_client_to_server: Vec<String>,
+    pub mac_server_to_client: Vec<String>,
+}
+
+impl KexInit {
+    /// The lists the client offers with `preferred`
+    pub fn client(preferred: &Preferred) -> Self {
+        fn names<N: AsRef<str>>(names: &[N]) -> Vec<String> {
+            names.iter().map(|name| name.as_ref().to_string()).collect()
+        }
+        let cipher = names(&preferred.cipher);
+        let mac = names(&preferred.mac);
+        Self {
+            kex: names(&preferred.kex),
+            host_key: names(&preferred.key),
+            cipher_client_to_server: cipher.clone(),
+            cipher_server_to_client: cipher,
+            mac_client_to_server: mac.clone(),
+            mac_server_to_client: mac,
+        }
+    }
+
+    /// Parse a KEXINIT payload (message number first)
+    fn parse(payload: &[u8]) -> Option<Self> {
+        if payload.first() != Some(&MSG_KEXINIT) {
+            return None;
+        }
+        // Skip the message number and the 16-byte cookie
+        let mut rest = payload.get(17..)?;
+        let mut next = || {
+            let len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+            let list = std::str::from_utf8(rest.get(4..4 + len)?).ok()?;
+            rest = &rest[4 + len..];
+            Some(
+                list.split(',')
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>(),
+            )
+        };
+        Some(Self {
+            kex: next()?,
+            host_key: next()?,
+            cipher_client_to_server: next()?,
+            cipher_server_to_client: next()?,
+            mac_client_to_server: next()?,
+            mac_server_to_client: next()?,
+        })
+    }
+}
+
+/// Apply the RFC 4253 negotiation rule. Fails with a description of the
+/// first kind of algorithm the sides have none in common of.
+pub fn negotiate(client: &KexInit, server: &KexInit) -> Result<NegotiatedAlgorithms, String> {
+    fn first(kind: &str, client: &[String], server: &[String]) -> Result<String, String> {
+        client
+            .iter()
+            // Markers for protocol extensions, not algorithms
+            .filter(|name| !name.starts_with("ext-info-") && !name.starts_with("kex-strict-"))
+            .find(|name| server.contains(name))
+            .cloned()
+            .ok_or_else(|| {
+                format!(
+                    "No common {} algorithm; the server offers {}",
+                    kind,
+                    server.join(", ")
+                )
+            })
+    }
+    fn mac(cipher: &str, client: &[String], server: &[String]) -> Result<Option<String>, String> {
+        if AEAD_CIPHERS.contains(&cipher) {
+            return Ok(None);
+        }
+        first("MAC", client, server).map(Some)
+    }
+
+    let kex = first("key exchange", &client.kex, &server.kex)?;
+    let host_key = first("host key", &client.host_key, &server.host_key)?;
+    let cipher_client_to_server = first(
+        "cipher",
+        &client.cipher_client_to_server,
+        &server.cipher_client_to_server,
+    )?;
+    let cipher_server_to_client = first(
+        "cipher",
+        &client.cipher_server_to_client,
+        &server.cipher_server_to_client,
+    )?;
+    let mac_client_to_server = mac(
+        &cipher_client_to_server,
+        &client.mac_client_to_server,
+        &server.mac_client_to_server,
+    )?;
+    let mac_server_to_client = mac(
+        &cipher_server_to_client,
+        &client.mac_server_to_client,
+        &server.mac_server_to_client,
+    )?;
+
+    let mut weak: Vec<String> = [
+        Some(&kex),
+        Some(&host_key),
+        Some(&cipher_client_to_server),
+        Some(&cipher_server_to_client),
+        mac_client_to_server.as_ref(),
+        mac_server_to_client.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|name| is_weak(name))
+    .cloned()
+    .collect();
+    weak.dedup();
+
+    Ok(NegotiatedAlgorithms {
+        kex,
+        host_key,
+        cipher_client_to_server,
+        cipher_server_to_client,
+        mac_client_to_server,
+        mac_server_to_client,
+        weak,
+    })
+}
+
+/// Whether an algorithm relies on broken or deprecated primitives
+fn is_weak(name: &str) -> bool {
+    // `ssh-rsa` signs with SHA-1; `rsa-sha2-*` use the same keys safely
+    name == "ssh-rsa"
+        || name == "ssh-dss"
+        || name == "none"
+        || name.ends_with("-cbc")
+        || name.starts_with("arcfour")
+        || name.contains("sha1")
+        || name.contains("md5")
+}
+
+/// Receives the server's first KEXINIT from a transport stream
+#[derive(Clone)]
+pub struct KexCapture {
+    state: Arc<Mutex<CaptureState>>,
+    /// Set once the capture stops watching the stream
+    done: Arc<watch::Sender<bool>>,
+}
+
+impl Default for KexCapture {
+    fn default() -> Self {
+        Self {
+            state: Arc::default(),
+            done: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct CaptureState {
+    buffer: Vec<u8>,
+    done: bool,
+    version: Option<String>,
+    server: Option<KexInit>,
+}
+
+impl KexCapture {
+    /// Watch the data read from `stream`
+    pub fn wrap<S>(&self, stream: S) -> KexSniffer<S> {
+        KexSniffer {
+            inner: stream,
+            capture: self.clone(),
+        }
+    }
+
+    /// The server's KEXINIT, once it has been received
+    pub fn server(&self) -> Option<KexInit> {
+        self.state.lock().server.clone()
+    }
+
+    /// The server's version line, once it has been received
+    pub fn version(&self) -> Option<String> {
+        self.state.lock().version.clone()
+    }
+
+    /// Whether the capture has stopped watching the stream
+    pub fn finished(&self) -> bool {
+        self.state.lock().done
+    }
+
+    /// Wait until the capture has stopped watching the stream
+    pub async fn wait(&self) {
+        // The sender lives as long as `self`, so this only returns once done
+        let _ = self.done.subscribe().wait_for(|done| *done).await;
+    }
+
+    fn feed(&self, data: &[u8]) {
+        let mut state = self.state.lock();
+        if state.done || data.is_empty() {
+            return;
+        }
+        state.buffer.extend_from_slice(data);
+        if state.version.is_none() {
+            state.version = find_version(&state.buffer);
+        }
+        if let Some(server) = find_kex_init(&state.buffer) {
+            state.server = server;
+            state.done = true;
+        } else if state.buffer.len() > MAX_CAPTURE {
+            state.done = true;
+        }
+        if state.done {
+            state.buffer = Vec::new();
+            self.done.send_replace(true);
+        }
+    }
+}
+
+fn find_version(data: &[u8]) -> Option<String> {
+    data.split(|&b| b == b'\n')
+        .rev()
+        // The last piece is not a complete line yet
+        .skip(1)
+        .find(|line| line.starts_with(b"SSH-"))
+        .map(|line| String::from_utf8_lossy(line).trim_end().to_string())
+}
+
+/// Find the KEXINIT that follows the server's version line. `None` if more
+/// data is needed, `Some(None)` if the first packet is something else.
+fn find_kex_init(data: &[u8]) -> Option<Option<KexInit>> {
+    // Servers may send other lines before the version line
+    let mut offset = 0;
+    loop {
+        let end = offset + data[offset..].iter().position(|&b| b == b'\n')?;
+        let line = &data[offset..end];
+        offset = end + 1;
+        if line.starts_with(b"SSH-") {
+            break;
+        }
+    }
+
+    // Packets are unencrypted until the first key exchange completes
+    let packet = &data[offset..];
+    let len = u32::from_be_bytes(packet.get(..4)?.try_into().ok()?) as usize;
+    let padding = *packet.get(4)? as usize;
+    let body = packet.get(5..4 + len)?;
+    Some(
+        body.len()
+            .checked_sub(padding)
+            .and_then(|payload| KexInit::parse(&body[..payload])),
+    )
+}
+
+/// Transport stream that hands the data it reads to a `KexCapture`
+pub struct KexSniffer<S> {
+    inner: S,
+    capture: KexCapture,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for KexSniffer<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            self.capture.feed(&buf.filled()[filled..]);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for KexSniffer<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    fn list(names: &str) -> Vec<String> {
+        names.split(',').map(str::to_string).collect()
+    }
+
+    /// Version line and KEXINIT packet as a server sends them
+    fn server_hello(init: &KexInit) -> Vec<u8> {
+        let mut payload = vec![MSG_KEXINIT];
+        payload.extend([7; 16]);
+        for names in [
+            &init.kex,
+            &init.host_key,
+            &init.cipher_client_to_server,
+            &init.cipher_server_to_client,
+            &init.mac_client_to_server,
+            &init.mac_server_to_client,
+            &Vec::new(),
+            &Vec::new(),
+            &Vec::new(),
+            &Vec::new(),
+        ] {
+            let names = names.join(",");
+            payload.extend((names.len() as u32).to_be_bytes());
+            payload.extend(names.as_bytes());
+        }
+        payload.extend([0; 5]);
+
+        let padding = 8 - (payload.len() + 5) % 8 + 4;
+        let mut data = b"Welcome\r\nSSH-2.0-Cisco-1.25\r\n".to_vec();
+        data.extend(((payload.len() + padding + 1) as u32).to_be_bytes());
+        data.push(padding as u8);
+        data.extend(payload);
+        data.extend(vec![0; padding]);
+        data
+    }
+
+    fn legacy_server() -> KexInit {
+        KexInit {
+            kex: list("diffie-hellman-group14-sha1,diffie-hellman-group1-sha1"),
+            host_key: list("ssh-rsa"),
+            cipher_client_to_server: list("aes128-cbc,3des-cbc"),
+            cipher_server_to_client: list("aes128-cbc,3des-cbc"),
+            mac_client_to_server: list("hmac-sha1"),
+            mac_server_to_client: list("hmac-sha1"),
+        }
+    }
+
+    #[test]
+    fn test_capture_kex_init() {
+        let server = legacy_server();
+        let data = server_hello(&server);
+
+        // The packet arrives in pieces
+        let capture = KexCapture::default();
+        assert!(capture.wait().now_or_never().is_none());
+        for chunk in data.chunks(7) {
+            assert!(capture.server().is_none());
+            capture.feed(chunk);
+        }
+        assert_eq!(capture.server(), Some(server));
+        assert_eq!(capture.version().as_deref(), Some("SSH-2.0-Cisco-1.25"));
+        assert!(capture.finished());
+        capture.wait().now_or_never().unwrap();
+
+        // Later data (the encrypted session) is ignored
+        capture.feed(b"\x00\x00\x00\x0cgarbage");
+        assert!(capture.server().is_some());
+
+        assert_eq!(find_kex_init(b"SSH-2.0-OpenSSH_9.6\r\n\x00\x00"), None);
+        assert_eq!(
+            find_kex_init(b"SSH-2.0-x\r\n\x00\x00\x00\x0c\x04\x15aaaaaaaaaaa"),
+            Some(None)
+        );
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let client = KexInit {
+            kex: list("curve25519-sha256,diffie-hellman-group14-sha1,ext-info-c"),
+            host_key: list("ssh-ed25519,rsa-sha2-256,ssh-rsa"),
+            cipher_client_to_server: list("chacha20-poly1305@openssh.com,aes128-cbc"),
+            cipher_server_to_client: list("chacha20-poly1305@openssh.com,aes128-cbc"),
+            mac_client_to_server: list("hmac-sha2-256,hmac-sha1"),
+            mac_server_to_client: list("hmac-sha2-256,hmac-sha1"),
+        };
+        let negotiated = negotiate(&client, &legacy_server()).unwrap();
+        assert_eq!(negotiated.kex, "diffie-hellman-group14-sha1");
+        assert_eq!(negotiated.host_key, "ssh-rsa");
+        assert_eq!(negotiated.cipher_client_to_server, "aes128-cbc");
+        assert_eq!(
+            negotiated.mac_server_to_client.as_deref(),
+            Some("hmac-sha1")
+        );
+        assert_eq!(
+            negotiated.weak,
+            list("diffie-hellman-group14-sha1,ssh-rsa,aes128-cbc,hmac-sha1")
+        );
+
+        // AEAD ciphers need no MAC
+        let mut modern = legacy_server();
+        modern.kex.push("curve25519-sha256".to_string());
+        modern.host_key.push("ssh-ed25519".to_string());
+        modern.cipher_server_to_client = list("chacha20-poly1305@openssh.com");
+        let negotiated = negotiate(&client, &modern).unwrap();
+        assert_eq!(negotiated.kex, "curve25519-sha256");
+        assert_eq!(negotiated.host_key, "ssh-ed25519");
+        assert_eq!(negotiated.mac_server_to_client, None);
+        assert_eq!(
+            negotiated.mac_client_to_server.as_deref(),
+            Some("hmac-sha1")
+        );
+
+        // Nothing in common
+        let mut strict = client.clone();
+        strict.kex = list("curve25519-sha256,ext-info-c");
+        assert_eq!(
+            negotiate(&strict, &legacy_server()).unwrap_err(),
+            "No common key exchange algorithm; the server offers \
+             diffie-hellman-group14-sha1, diffie-hellman-group1-sha1"
+        );
+    }
+}
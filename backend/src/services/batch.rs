@@ -0,0 +1,245 @@
+//! Batch Execution Jobs
+//!
+//! Tracks jobs that run one command on many hosts and stores finished jobs as
+//! JSON files for later review. The hosts themselves are worked on by the SSH
+//! service, which reports each result here as it comes in.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::models::{
+    BatchHostResult, BatchJob, BatchJobStatus, BatchJobSummary, BatchProgressEvent,
+};
+
+/// Event of a running batch job
+pub enum BatchEvent {
+    Progress(BatchProgressEvent),
+    Finished(BatchJobSummary),
+}
+
+struct RunningJob {
+    job: BatchJob,
+    cancel: CancellationToken,
+}
+
+/// Manages running batch jobs and stored job results
+#[derive(Default)]
+pub struct BatchManager {
+    dir: parking_lot::RwLock<Option<PathBuf>>,
+    /// job_id -> job in progress
+    running: parking_lot::Mutex<HashMap<String, RunningJob>>,
+}
+
+impl BatchManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the directory finished jobs are stored in
+    pub fn set_dir(&self, dir: PathBuf) -> Result<()> {
+        std::fs::create_dir_all(&dir)?;
+        *self.dir.write() = Some(dir);
+        Ok(())
+    }
+
+    fn dir(&self) -> Result<PathBuf> {
+        self.dir
+            .read()
+            .clone()
+            .ok_or_else(|| anyhow!("Batch job storage is not available"))
+    }
+
+    fn path_of(&self, job_id: &str) -> Result<PathBuf> {
+        // Job IDs are UUIDs, which also keeps them from escaping the directory
+        Uuid::parse_str(job_id).map_err(|_| anyhow!("Invalid batch job ID"))?;
+        Ok(self.dir()?.join(format!("{}.json", job_id)))
+    }
+
+    /// Register a new job; returns its ID and the token that cancels it
+    pub fn start(&self, command: &str, total: usize) -> Result<(String, CancellationToken)> {
+        // Fail now rather than after the job when its results cannot be kept
+        self.dir()?;
+
+        let job_id = Uuid::new_v4().to_string();
+        let cancel = CancellationToken::new();
+        let job = BatchJob {
+            job_id: job_id.clone(),
+            command: command.to_string(),
+            status: BatchJobStatus::Running,
+            started_at: Utc::now().to_rfc3339(),
+            finished_at: None,
+            total,
+            results: Vec::new(),
+        };
+        self.running.lock().insert(
+            job_id.clone(),
+            RunningJob {
+                job,
+                cancel: cancel.clone(),
+            },
+        );
+        Ok((job_id, cancel))
+    }
+
+    /// Add the result of one host; returns the number of hosts finished
+    pub fn record(&self, job_id: &str, result: BatchHostResult) -> usize {
+        match self.running.lock().get_mut(job_id) {
+            Some(running) => {
+                running.job.results.push(result);
+                running.job.results.len()
+            }
+            None => 0,
+        }
+    }
+
+    /// Mark a job as finished and store it
+    pub fn finish(&self, job_id: &str) -> Option<BatchJobSummary> {
+        let RunningJob { mut job, cancel } = self.running.lock().remove(job_id)?;
+        job.status = if cancel.is_cancelled() {
+            BatchJobStatus::Cancelled
+        } else {
+            BatchJobStatus::Completed
+        };
+        job.finished_at = Some(Utc::now().to_rfc3339());
+
+        if let Err(e) = self.store(&job) {
+            log::error!("Failed to store batch job {}: {}", job_id, e);
+        }
+        Some(job.summary())
+    }
+
+    fn store(&self, job: &BatchJob) -> Result<()> {
+        std::fs::write(self.path_of(&job.job_id)?, serde_json::to_vec_pretty(job)?)?;
+        Ok(())
+    }
+
+    /// Stop a running job; hosts already finished keep their results
+    pub fn cancel(&self, job_id: &str) -> Result<()> {
+        let running = self.running.lock();
+        let job = running
+            .get(job_id)
+            .ok_or_else(|| anyhow!("Batch job not found"))?;
+        job.cancel.cancel();
+        Ok(())
+    }
+
+    /// List running and stored jobs, newest first
+    pub fn list(&self) -> Result<Vec<BatchJobSummary>> {
+        let mut jobs: Vec<BatchJobSummary> = self
+            .running
+            .lock()
+            .values()
+            .map(|running| running.job.summary())
+            .collect();
+
+        for entry in std::fs::read_dir(self.dir()?)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            // Skip files that are not readable jobs
+            if let Ok(job) = read_job(&path) {
+                jobs.push(job.summary());
+            }
+        }
+        jobs.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        Ok(jobs)
+    }
+
+    /// Get a job with its host results
+    pub fn get(&self, job_id: &str) -> Result<BatchJob> {
+        if let Some(running) = self.running.lock().get(job_id) {
+            return Ok(running.job.clone());
+        }
+        read_job(&self.path_of(job_id)?).map_err(|_| anyhow!("Batch job not found"))
+    }
+
+    /// Delete a stored job
+    pub fn delete(&self, job_id: &str) -> Result<()> {
+        if self.running.lock().contains_key(job_id) {
+            return Err(anyhow!("Batch job is still running"));
+        }
+        std::fs::remove_file(self.path_of(job_id)?)?;
+        Ok(())
+    }
+}
+
+fn read_job(path: &std::path::Path) -> Result<BatchJob> {
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ExecResult, ExecStatus};
+
+    fn host_result(index: usize, exit_code: Option<u32>) -> BatchHostResult {
+        BatchHostResult {
+            index,
+            name: format!("web{}", index),
+            host: format!("10.0.0.{}", index),
+            result: exit_code.map(|code| ExecResult {
+                stdout: "ok\n".to_string(),
+                stderr: String::new(),
+                truncated: false,
+                status: ExecStatus {
+                    exit_code: Some(code),
+                    ..Default::default()
+                },
+            }),
+            error: exit_code
+                .is_none()
+                .then(|| "Connection refused".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_job_lifecycle() {
+        let dir = std::env::temp_dir().join(format!("opsbot-batch-{}", Uuid::new_v4()));
+        let manager = BatchManager::new();
+        manager.set_dir(dir.clone()).unwrap();
+
+        let (job_id, _cancel) = manager.start("uptime", 3).unwrap();
+        assert_eq!(manager.record(&job_id, host_result(1, Some(0))), 1);
+        assert_eq!(manager.record(&job_id, host_result(0, Some(2))), 2);
+        assert_eq!(manager.record(&job_id, host_result(2, None)), 3);
+        assert!(manager.delete(&job_id).is_err());
+
+        let summary = manager.finish(&job_id).unwrap();
+        assert_eq!(summary.status, BatchJobStatus::Completed);
+        assert_eq!((summary.succeeded, summary.failed), (1, 2));
+
+        // The stored job round-trips, including the flattened exec result
+        let job = manager.get(&job_id).unwrap();
+        assert_eq!(job.results.len(), 3);
+        assert_eq!(job.results[0].result.as_ref().unwrap().stdout, "ok\n");
+        assert!(job.results[2].result.is_none());
+        assert_eq!(manager.list().unwrap().len(), 1);
+
+        manager.delete(&job_id).unwrap();
+        assert!(manager.list().unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cancelled_job() {
+        let dir = std::env::temp_dir().join(format!("opsbot-batch-{}", Uuid::new_v4()));
+        let manager = BatchManager::new();
+        manager.set_dir(dir.clone()).unwrap();
+
+        let (job_id, cancel) = manager.start("uptime", 2).unwrap();
+        manager.cancel(&job_id).unwrap();
+        assert!(cancel.is_cancelled());
+        assert_eq!(
+            manager.finish(&job_id).unwrap().status,
+            BatchJobStatus::Cancelled
+        );
+        assert!(manager.finish(&job_id).is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+//! Broadcast Input Groups
+//!
+//! Keeps track of which terminal sessions belong to which broadcast group, so
+//! that input typed once can be sent to every member (like cluster-ssh).
+//! Delivery itself is done by the SSH service.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use uuid::Uuid;
+
+use crate::models::{BroadcastFailure, BroadcastGroupInfo, BroadcastReport};
+
+struct BroadcastGroup {
+    name: String,
+    members: Vec<String>,
+}
+
+impl BroadcastGroup {
+    fn info(&self, group_id: &str) -> BroadcastGroupInfo {
+        BroadcastGroupInfo {
+            group_id: group_id.to_string(),
+            name: self.name.clone(),
+            session_ids: self.members.clone(),
+        }
+    }
+}
+
+/// Broadcast group membership
+#[derive(Default)]
+pub struct BroadcastGroups {
+    /// group_id -> group
+    groups: parking_lot::RwLock<HashMap<String, BroadcastGroup>>,
+}
+
+impl BroadcastGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a group; duplicate session IDs are ignored
+    pub fn create(&self, name: String, session_ids: Vec<String>) -> BroadcastGroupInfo {
+        let mut members: Vec<String> = Vec::with_capacity(session_ids.len());
+        for session_id in session_ids {
+            if !members.contains(&session_id) {
+                members.push(session_id);
+            }
+        }
+
+        let group_id = Uuid::new_v4().to_string();
+        let group = BroadcastGroup { name, members };
+        let info = group.info(&group_id);
+        self.groups.write().insert(group_id, group);
+        info
+    }
+
+    /// Delete a group
+    pub fn delete(&self, group_id: &str) -> Result<()> {
+        self.groups
+            .write()
+            .remove(group_id)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("Broadcast group not found"))
+    }
+
+    /// Add a session to a group; joining twice has no effect
+    pub fn join(&self, group_id: &str, session_id: &str) -> Result<BroadcastGroupInfo> {
+        let mut groups = self.groups.write();
+        let group = groups
+            .get_mut(group_id)
+            .ok_or_else(|| anyhow!("Broadcast group not found"))?;
+        if !group.members.iter().any(|id| id == session_id) {
+            group.members.push(session_id.to_string());
+        }
+        Ok(group.info(group_id))
+    }
+
+    /// Remove a session from a group
+    pub fn leave(&self, group_id: &str, session_id: &str) -> Result<BroadcastGroupInfo> {
+        let mut groups = self.groups.write();
+        let group = groups
+            .get_mut(group_id)
+            .ok_or_else(|| anyhow!("Broadcast group not found"))?;
+        group.members.retain(|id| id != session_id);
+        Ok(group.info(group_id))
+    }
+
+    /// Remove a session from every group, e.g. when it is closed
+    pub fn remove_session(&self, session_id: &str) {
+        for group in self.groups.write().values_mut() {
+            group.members.retain(|id| id != session_id);
+        }
+    }
+
+    /// Current members of a group
+    pub fn members(&self, group_id: &str) -> Result<Vec<String>> {
+        self.groups
+            .read()
+            .get(group_id)
+            .map(|group| group.members.clone())
+            .ok_or_else(|| anyhow!("Broadcast group not found"))
+    }
+
+    /// List all groups
+    pub fn list(&self) -> Vec<BroadcastGroupInfo> {
+        let mut groups: Vec<BroadcastGroupInfo> = self
+            .groups
+            .read()
+            .iter()
+            .map(|(group_id, group)| group.info(group_id))
+            .collect();
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        groups
+    }
+}
+
+/// Pair each member with the outcome of delivering to it
+pub fn report(members: Vec<String>, results: Vec<Result<()>>) -> BroadcastReport {
+    let mut report = BroadcastReport::default();
+    for (session_id, result) in members.into_iter().zip(results) {
+        match result {
+            Ok(()) => report.delivered.push(session_id),
+            Err(e) => report.failed.push(BroadcastFailure {
+                session_id,
+                error: e.to_string(),
+            }),
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_membership() {
+        let groups = BroadcastGroups::new();
+        let info = groups.create(
+            "web".to_string(),
+            vec!["a".to_string(), "b".to_string(), "a".to_string()],
+        );
+        assert_eq!(info.session_ids, ["a", "b"]);
+
+        groups.join(&info.group_id, "c").unwrap();
+        groups.join(&info.group_id, "b").unwrap();
+        let left = groups.leave(&info.group_id, "a").unwrap();
+        assert_eq!(left.session_ids, ["b", "c"]);
+
+        groups.remove_session("c");
+        assert_eq!(groups.members(&info.group_id).unwrap(), ["b"]);
+
+        groups.delete(&info.group_id).unwrap();
+        assert!(groups.members(&info.group_id).is_err());
+        assert!(groups.join(&info.group_id, "a").is_err());
+    }
+}
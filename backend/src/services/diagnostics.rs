@@ -0,0 +1,292 @@
+//! Connection Diagnostics
+//!
+//! Sorts connection failures into categories (DNS, TCP, proxy, protocol, key
+//! exchange, host key, authentication...) so the frontend can tell them
+//! apart, and records the staged report of a diagnostic connection test.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use tokio::net::TcpStream;
+
+use crate::models::{
+    ConnectError, ConnectErrorKind, ConnectionDiagnostics, DiagnosticStage, StageReport,
+    StageStatus,
+};
+
+/// Time allowed for the TCP connection to one address
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connection failure of a known category, carried through `anyhow`
+#[derive(Debug, thiserror::Error)]
+#[error("{}", .0.message)]
+pub struct ConnectFailure(pub ConnectError);
+
+/// Error of the given category
+pub fn fail(kind: ConnectErrorKind, message: impl Into<String>) -> anyhow::Error {
+    ConnectFailure(ConnectError::new(kind, message)).into()
+}
+
+/// Categorize an error from connecting. Errors not raised through `fail`
+/// are sorted by their I/O or SSH cause.
+pub fn classify(error: &anyhow::Error) -> ConnectError {
+    if let Some(ConnectFailure(error)) = error.downcast_ref::<ConnectFailure>() {
+        return error.clone();
+    }
+    let kind = error
+        .chain()
+        .find_map(|cause| {
+            if let Some(e) = cause.downcast_ref::<io::Error>() {
+                return Some(io_kind(e));
+            }
+            match cause.downcast_ref::<russh::Error>()? {
+                russh::Error::IO(e) => Some(io_kind(e)),
+                russh::Error::UnknownKey => Some(ConnectErrorKind::HostKey),
+                russh::Error::NoCommonAlgo { .. } => Some(ConnectErrorKind::KeyExchange),
+                russh::Error::Disconnect | russh::Error::HUP => {
+                    Some(ConnectErrorKind::Disconnected)
+                }
+                _ => None,
+            }
+        })
+        .unwrap_or(ConnectErrorKind::Other);
+    ConnectError::new(kind, error.to_string())
+}
+
+/// Attribute an error to a jump host
+pub fn on_hop(error: anyhow::Error, hop: &str) -> anyhow::Error {
+    let mut error = classify(&error);
+    error.message = format!("{}: {}", hop, error.message);
+    error.hop = Some(hop.to_string());
+    ConnectFailure(error).into()
+}
+
+fn io_kind(error: &io::Error) -> ConnectErrorKind {
+    match error.kind() {
+        io::ErrorKind::ConnectionRefused => ConnectErrorKind::Refused,
+        io::ErrorKind::TimedOut => ConnectErrorKind::Timeout,
+        io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::BrokenPipe
+        | io::ErrorKind::UnexpectedEof => ConnectErrorKind::Disconnected,
+        _ => ConnectErrorKind::Unreachable,
+    }
+}
+
+/// Resolve a host name to the addresses to connect to
+pub async fn resolve(host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| {
+            fail(
+                ConnectErrorKind::Resolve,
+                format!("Could not resolve {}: {}", host, e),
+            )
+        })?
+        .collect();
+    if addresses.is_empty() {
+        return Err(fail(
+            ConnectErrorKind::Resolve,
+            format!("{} has no addresses", host),
+        ));
+    }
+    Ok(addresses)
+}
+
+/// Connect to the first address that accepts, in order
+pub async fn connect_tcp(addresses: &[SocketAddr]) -> Result<(TcpStream, SocketAddr)> {
+    let mut last_error = None;
+    for &address in addresses {
+        let error = match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await {
+            Ok(Ok(stream)) => return Ok((stream, address)),
+            Ok(Err(e)) => ConnectError::new(
+                io_kind(&e),
+                format!("Could not connect to {}: {}", address, e),
+            ),
+            Err(_) => ConnectError::new(
+                ConnectErrorKind::Timeout,
+                format!(
+                    "Connecting to {} timed out after {} s",
+                    address,
+                    CONNECT_TIMEOUT.as_secs()
+                ),
+            ),
+        };
+        last_error = Some(error);
+    }
+    Err(last_error
+        .map(|error| ConnectFailure(error).into())
+        .unwrap_or_else(|| fail(ConnectErrorKind::Resolve, "No addresses to connect to")))
+}
+
+/// Builds the staged report of a connection test
+pub struct Report {
+    diagnostics: ConnectionDiagnostics,
+}
+
+impl Report {
+    pub fn new(host: &str, port: u16) -> Self {
+        Self {
+            diagnostics: ConnectionDiagnostics {
+                host: host.to_string(),
+                port,
+                success: false,
+                stages: Vec::new(),
+                server_version: None,
+                algorithms: None,
+                error: None,
+            },
+        }
+    }
+
+    /// Run a stage and record its outcome. Returns `None` if it failed, in
+    /// which case the test ends.
+    pub async fn run<T>(
+        &mut self,
+        stage: DiagnosticStage,
+        future: impl Future<Output = Result<T>>,
+        describe: impl FnOnce(&T, Duration) -> String,
+    ) -> Option<T> {
+        let started = Instant::now();
+        let result = future.await;
+        let elapsed = started.elapsed();
+        match result {
+            Ok(value) => {
+                self.push(
+                    stage,
+                    StageStatus::Ok,
+                    Some(elapsed),
+                    describe(&value, elapsed),
+                );
+                Some(value)
+            }
+            Err(e) => {
+                let error = classify(&e);
+                self.diagnostics.stages.push(StageReport {
+                    stage,
+                    status: StageStatus::Failed,
+                    duration_ms: Some(elapsed.as_millis() as u64),
+                    detail: None,
+                    error: Some(error.clone()),
+                });
+                self.diagnostics.error = Some(error);
+                None
+            }
+        }
+    }
+
+    /// Record a stage that passed without doing anything
+    pub fn pass(&mut self, stage: DiagnosticStage, detail: impl Into<String>) {
+        self.push(stage, StageStatus::Ok, Some(Duration::ZERO), detail.into());
+    }
+
+    /// Record a stage that does not apply
+    pub fn skip(&mut self, stage: DiagnosticStage, detail: impl Into<String>) {
+        self.push(stage, StageStatus::Skipped, None, detail.into());
+    }
+
+    /// End the test with an error outside of any stage
+    pub fn fail(&mut self, error: &anyhow::Error) {
+        self.diagnostics.error = Some(classify(error));
+    }
+
+    pub fn diagnostics_mut(&mut self) -> &mut ConnectionDiagnostics {
+        &mut self.diagnostics
+    }
+
+    pub fn finish(mut self) -> ConnectionDiagnostics {
+        self.diagnostics.success = self.diagnostics.error.is_none();
+        self.diagnostics
+    }
+
+    fn push(
+        &mut self,
+        stage: DiagnosticStage,
+        status: StageStatus,
+        duration: Option<Duration>,
+        detail: String,
+    ) {
+        self.diagnostics.stages.push(StageReport {
+            stage,
+            status,
+            duration_ms: duration.map(|d| d.as_millis() as u64),
+            detail: Some(detail),
+            error: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_classify() {
+        let error = fail(ConnectErrorKind::Auth, "Password authentication failed");
+        assert_eq!(error.to_string(), "Password authentication failed");
+        assert_eq!(classify(&error).kind, ConnectErrorKind::Auth);
+
+        let refused: anyhow::Error = io::Error::from(io::ErrorKind::ConnectionRefused).into();
+        assert_eq!(classify(&refused).kind, ConnectErrorKind::Refused);
+        let reset = anyhow::Error::from(io::Error::from(io::ErrorKind::ConnectionReset))
+            .context("Handshake failed");
+        assert_eq!(classify(&reset).kind, ConnectErrorKind::Disconnected);
+        assert_eq!(
+            classify(&anyhow!("Something else")).kind,
+            ConnectErrorKind::Other
+        );
+
+        let error = on_hop(
+            fail(ConnectErrorKind::HostKey, "Host key was not trusted"),
+            "Jump host 1 (bastion)",
+        );
+        let error = classify(&error);
+        assert_eq!(error.kind, ConnectErrorKind::HostKey);
+        assert_eq!(error.hop.as_deref(), Some("Jump host 1 (bastion)"));
+        assert_eq!(
+            error.message,
+            "Jump host 1 (bastion): Host key was not trusted"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_stages() {
+        let mut report = Report::new("localhost", 1);
+        let addresses = report
+            .run(
+                DiagnosticStage::Resolve,
+                resolve("localhost", 1),
+                |addresses, _| format!("{} addresses", addresses.len()),
+            )
+            .await
+            .unwrap();
+
+        // Nothing listens on a port that was just released
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed = listener.local_addr().unwrap();
+        drop(listener);
+        let connected = report
+            .run(
+                DiagnosticStage::TcpConnect,
+                connect_tcp(&[closed]),
+                |_, _| String::new(),
+            )
+            .await;
+        assert!(connected.is_none());
+        assert!(!addresses.is_empty());
+
+        let diagnostics = report.finish();
+        assert!(!diagnostics.success);
+        assert_eq!(diagnostics.stages.len(), 2);
+        assert_eq!(diagnostics.stages[0].status, StageStatus::Ok);
+        assert_eq!(diagnostics.stages[1].status, StageStatus::Failed);
+        assert_eq!(
+            diagnostics.error.map(|e| e.kind),
+            Some(ConnectErrorKind::Refused)
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! Remote Command Execution
+//!
+//! Runs non-interactive commands on SSH exec channels, keeping stdout and
+//! stderr apart and reporting how the command ended. Streaming executions
+//! are tracked by ID so they can be cancelled.
+
+use std::collections::HashMap;
+use std::future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use russh::{client, Channel, ChannelMsg, Sig};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::models::{
+    ExecFinishedEvent, ExecOutputEvent, ExecRequest, ExecResult, ExecStatus, ExecStream,
+};
+use crate::services::recording::Utf8Stream;
+
+/// Output of each stream `run` keeps; batch jobs keep it for every host
+const MAX_OUTPUT: usize = 1024 * 1024;
+
+/// Event of a streaming execution
+pub enum ExecEvent {
+    Output(ExecOutputEvent),
+    Finished(ExecFinishedEvent),
+}
+
+/// Tracks streaming executions so they can be cancelled
+#[derive(Default)]
+pub struct ExecManager {
+    /// exec_id -> cancellation token
+    running: Arc<parking_lot::Mutex<HashMap<String, CancellationToken>>>,
+}
+
+impl ExecManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run a command in the background, sending its output and final status
+    /// to `tx`. Returns the exec ID used to cancel it.
+    pub fn spawn(
+        &self,
+        channel: Channel<client::Msg>,
+        request: ExecRequest,
+        tx: mpsc::UnboundedSender<ExecEvent>,
+    ) -> String {
+        let exec_id = Uuid::new_v4().to_string();
+        let cancel = CancellationToken::new();
+        self.running.lock().insert(exec_id.clone(), cancel.clone());
+
+        let running = self.running.clone();
+        let id = exec_id.clone();
+        tokio::spawn(async move {
+            let mut stdout = Utf8Stream::default();
+            let mut stderr = Utf8Stream::default();
+            let result = execute(channel, &request, cancel, |stream, data| {
+                let data = match stream {
+                    ExecStream::Stdout => stdout.decode(data),
+                    ExecStream::Stderr => stderr.decode(data),
+                };
+                if !data.is_empty() {
+                    let _ = tx.send(ExecEvent::Output(ExecOutputEvent {
+                        exec_id: id.clone(),
+                        stream,
+                        data,
+                    }));
+                }
+            })
+            .await;
+            running.lock().remove(&id);
+
+            let (status, error) = match result {
+                Ok(status) => (Some(status), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            let _ = tx.send(ExecEvent::Finished(ExecFinishedEvent {
+                exec_id: id,
+                status,
+                error,
+            }));
+        });
+
+        exec_id
+    }
+
+    /// Stop a running execution
+    pub fn cancel(&self, exec_id: &str) -> Result<()> {
+        let cancel = self
+            .running
+            .lock()
+            .remove(exec_id)
+            .ok_or_else(|| anyhow!("Execution not found"))?;
+        cancel.cancel();
+        Ok(())
+    }
+}
+
+/// Run a command to completion, or until `cancel` fires, and collect up to
+/// `MAX_OUTPUT` of each stream
+pub async fn run(
+    channel: Channel<client::Msg>,
+    request: &ExecRequest,
+    cancel: CancellationToken,
+) -> Result<ExecResult> {
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut truncated = false;
+    let status = execute(channel, request, cancel, |stream, data| {
+        let output = match stream {
+            ExecStream::Stdout => &mut stdout,
+            ExecStream::Stderr => &mut stderr,
+        };
+        let room = MAX_OUTPUT - output.len();
+        if data.len() > room {
+            truncated = true;
+        }
+        output.extend_from_slice(&data[..data.len().min(room)]);
+    })
+    .await?;
+
+    Ok(ExecResult {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        truncated,
+        status,
+    })
+}
+
+/// Run `request.command` on `channel`, passing output to `on_output`, until
+/// the command exits, times out or is cancelled
+async fn execute(
+    mut channel: Channel<client::Msg>,
+    request: &ExecRequest,
+    cancel: CancellationToken,
+    mut on_output: impl FnMut(ExecStream, &[u8]),
+) -> Result<ExecStatus> {
+    let started = Instant::now();
+    let timeout = async {
+        match request.timeout_ms {
+            Some(ms) => tokio::time::sleep(Duration::from_millis(ms)).await,
+            None => future::pending().await,
+        }
+    };
+
+    let mut status = ExecStatus::default();
+    tokio::select! {
+        result = drive(&mut channel, request, &mut status, &mut on_output) => result?,
+        _ = cancel.cancelled() => status.cancelled = true,
+        _ = timeout => status.timed_out = true,
+    }
+
+    if status.cancelled || status.timed_out {
+        // Not every server honours signals; closing the channel ends the command too
+        let _ = channel.signal(Sig::KILL).await;
+        let _ = channel.close().await;
+    }
+    status.duration_ms = started.elapsed().as_millis() as u64;
+    Ok(status)
+}
+
+/// Start the command, feed its stdin and read the channel until it closes
+async fn drive(
+    channel: &mut Channel<client::Msg>,
+    request: &ExecRequest,
+    status: &mut ExecStatus,
+    on_output: &mut impl FnMut(ExecStream, &[u8]),
+) -> Result<()> {
+    channel.exec(true, request.command.as_str()).await?;
+    if let Some(stdin) = &request.stdin {
+        channel.data(stdin.as_bytes()).await?;
+    }
+    channel.eof().await?;
+
+    loop {
+        match channel.wait().await {
+            Some(ChannelMsg::Data { data }) => on_output(ExecStream::Stdout, &data),
+            // Extended data type 1 is stderr (RFC 4254 section 5.2)
+            Some(ChannelMsg::ExtendedData { data, ext: 1 }) => on_output(ExecStream::Stderr, &data),
+            Some(ChannelMsg::ExitStatus { exit_status }) => status.exit_code = Some(exit_status),
+            Some(ChannelMsg::ExitSignal { signal_name, .. }) => {
+                status.exit_signal = Some(signal_name_of(signal_name))
+            }
+            Some(ChannelMsg::Failure) => {
+                return Err(anyhow!("Server refused to run the command"));
+            }
+            // The exit status usually follows EOF, so read until the channel closes
+            Some(ChannelMsg::Close) | None => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+fn signal_name_of(signal: Sig) -> String {
+    match signal {
+        Sig::Custom(name) => name,
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_server::TestServer;
+
+    fn request(command: &str) -> ExecRequest {
+        ExecRequest {
+            session_id: "test".to_string(),
+            command: command.to_string(),
+            stdin: None,
+            timeout_ms: None,
+        }
+    }
+
+    async fn run_on(server: &TestServer, request: &ExecRequest) -> Result<ExecResult> {
+        let (_handle, channel) = server.channel().await;
+        run(channel, request, CancellationToken::new()).await
+    }
+
+    #[test]
+    fn test_signal_name() {
+        assert_eq!(signal_name_of(Sig::KILL), "KILL");
+        assert_eq!(signal_name_of(Sig::Custom("XCPU".to_string())), "XCPU");
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let server = TestServer::start().await;
+
+        let result = run_on(&server, &request("echo hello")).await.unwrap();
+        assert_eq!(result.stdout, "hello\n");
+        assert_eq!(result.stderr, "");
+        assert_eq!(result.status.exit_code, Some(0));
+        assert!(!result.truncated);
+
+        // stdout and stderr are kept apart
+        let result = run_on(&server, &request("fail")).await.unwrap();
+        assert_eq!(result.stdout, "partial\n");
+        assert_eq!(result.stderr, "oops\n");
+        assert_eq!(result.status.exit_code, Some(3));
+
+        // stdin is written, then closed so the command can finish
+        let mut cat = request("cat");
+        cat.stdin = Some("line 1\nline 2\n".to_string());
+        let result = run_on(&server, &cat).await.unwrap();
+        assert_eq!(result.stdout, "line 1\nline 2\n");
+        assert_eq!(result.status.exit_code, Some(0));
+        let result = run_on(&server, &request("cat")).await.unwrap();
+        assert_eq!(result.stdout, "");
+        assert_eq!(result.status.exit_code, Some(0));
+
+        let result = run_on(&server, &request("crash")).await.unwrap();
+        assert_eq!(result.status.exit_code, None);
+        assert_eq!(result.status.exit_signal.as_deref(), Some("SEGV"));
+
+        let error = run_on(&server, &request("unknown")).await.unwrap_err();
+        assert!(error.to_string().contains("refused"));
+    }
+
+    #[tokio::test]
+    async fn test_run_output_limit() {
+        let server = TestServer::start().await;
+        let flood = request(&format!("flood {}", MAX_OUTPUT + 100_000));
+        let result = run_on(&server, &flood).await.unwrap();
+        assert_eq!(result.stdout.len(), MAX_OUTPUT);
+        assert!(result.truncated);
+        // The command still runs to the end
+        assert_eq!(result.status.exit_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_run_timeout() {
+        let server = TestServer::start().await;
+        let mut sleep = request("sleep");
+        sleep.timeout_ms = Some(100);
+        let result = run_on(&server, &sleep).await.unwrap();
+        assert!(result.status.timed_out);
+        assert!(!result.status.cancelled);
+        assert!(result.status.duration_ms >= 100);
+        assert_eq!(server.first_signal().await, "KILL");
+    }
+
+    #[tokio::test]
+    async fn test_spawn_cancel() {
+        let server = TestServer::start().await;
+        let manager = ExecManager::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        // Output is streamed per stream
+        let (_handle, channel) = server.channel().await;
+        let exec_id = manager.spawn(channel, request("fail"), tx.clone());
+        let mut output = Vec::new();
+        let finished = loop {
+            match rx.recv().await.unwrap() {
+                ExecEvent::Output(event) => {
+                    assert_eq!(event.exec_id, exec_id);
+                    output.push((event.stream, event.data));
+                }
+                ExecEvent::Finished(event) => break event,
+            }
+        };
+        output.sort_by_key(|(stream, _)| *stream == ExecStream::Stderr);
+        assert_eq!(
+            output,
+            [
+                (ExecStream::Stdout, "partial\n".to_string()),
+                (ExecStream::Stderr, "oops\n".to_string()),
+            ]
+        );
+        assert_eq!(finished.status.unwrap().exit_code, Some(3));
+        assert!(manager.cancel(&exec_id).is_err());
+
+        // A running command is stopped on request
+        let (_handle, channel) = server.channel().await;
+        let exec_id = manager.spawn(channel, request("sleep"), tx);
+        manager.cancel(&exec_id).unwrap();
+        let Some(ExecEvent::Finished(finished)) = rx.recv().await else {
+            panic!("expected the command to finish");
+        };
+        assert_eq!(finished.exec_id, exec_id);
+        let status = finished.status.unwrap();
+        assert!(status.cancelled);
+        assert_eq!(status.exit_code, None);
+        assert_eq!(server.first_signal().await, "KILL");
+    }
+}
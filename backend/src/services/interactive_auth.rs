@@ -0,0 +1,145 @@
+//! Keyboard-Interactive Authentication
+//!
+//! Relays keyboard-interactive prompts (OTP, 2FA, RADIUS challenges) to the
+//! frontend and waits for the user's answers.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use russh::client::{self, KeyboardInteractiveAuthResponse};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::models::{KeyboardInteractivePrompt, KeyboardInteractivePromptEvent};
+use crate::services::ssh_service::{SshClientHandler, SshEvent, SshEventSink};
+
+/// How long authentication waits for the user to answer a prompt
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Upper bound on challenge rounds, guards against misbehaving servers
+const MAX_ROUNDS: usize = 16;
+
+/// Pending keyboard-interactive prompts waiting for user answers
+pub struct InteractiveAuth {
+    pending: parking_lot::Mutex<HashMap<String, oneshot::Sender<Option<Vec<String>>>>>,
+    events: SshEventSink,
+}
+
+impl InteractiveAuth {
+    pub fn new(events: SshEventSink) -> Self {
+        Self {
+            pending: parking_lot::Mutex::new(HashMap::new()),
+            events,
+        }
+    }
+
+    /// Run keyboard-interactive authentication on `handle`.
+    ///
+    /// A stored password answers the first hidden "password" prompt so that
+    /// password + OTP setups only ask the user for the code.
+    pub async fn authenticate(
+        &self,
+        handle: &mut client::Handle<SshClientHandler>,
+        host: &str,
+        username: &str,
+        password: Option<&str>,
+    ) -> Result<bool> {
+        let mut password = password;
+        let mut response = handle
+            .authenticate_keyboard_interactive_start(username, None)
+            .await?;
+
+        for _ in 0..MAX_ROUNDS {
+            let (name, instructions, prompts) = match response {
+                KeyboardInteractiveAuthResponse::Success => return Ok(true),
+                KeyboardInteractiveAuthResponse::Failure => return Ok(false),
+                KeyboardInteractiveAuthResponse::InfoRequest {
+                    name,
+                    instructions,
+                    prompts,
+                } => (name, instructions, prompts),
+            };
+
+            let answers = if prompts.is_empty() {
+                Vec::new()
+            } else if let (Some(pw), [prompt]) = (password, prompts.as_slice()) {
+                if !prompt.echo && prompt.prompt.to_lowercase().contains("password") {
+                    password = None;
+                    vec![pw.to_string()]
+                } else {
+                    self.ask(host, username, name, instructions, &prompts)
+                        .await?
+                }
+            } else {
+                self.ask(host, username, name, instructions, &prompts)
+                    .await?
+            };
+
+            response = handle
+                .authenticate_keyboard_interactive_respond(answers)
+                .await?;
+        }
+
+        Err(anyhow!("Too many keyboard-interactive rounds"))
+    }
+
+    async fn ask(
+        &self,
+        host: &str,
+        username: &str,
+        name: String,
+        instructions: String,
+        prompts: &[client::Prompt],
+    ) -> Result<Vec<String>> {
+        let request_id = Uuid::new_v4().to_string();
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().insert(request_id.clone(), tx);
+
+        let delivered = self.events.emit(SshEvent::KeyboardInteractivePrompt(
+            KeyboardInteractivePromptEvent {
+                request_id: request_id.clone(),
+                host: host.to_string(),
+                username: username.to_string(),
+                name,
+                instructions,
+                prompts: prompts
+                    .iter()
+                    .map(|p| KeyboardInteractivePrompt {
+                        prompt: p.prompt.clone(),
+                        echo: p.echo,
+                    })
+                    .collect(),
+            },
+        ));
+
+        if !delivered {
+            self.pending.lock().remove(&request_id);
+            return Err(anyhow!(
+                "Keyboard-interactive authentication needs a user interface"
+            ));
+        }
+        let answers = tokio::time::timeout(PROMPT_TIMEOUT, rx).await;
+        self.pending.lock().remove(&request_id);
+
+        match answers {
+            Ok(Ok(Some(answers))) if answers.len() == prompts.len() => Ok(answers),
+            Ok(Ok(Some(_))) => Err(anyhow!("Number of answers does not match the prompts")),
+            Ok(Ok(None)) | Ok(Err(_)) => Err(anyhow!("Authentication cancelled by user")),
+            Err(_) => Err(anyhow!(
+                "Timed out waiting for keyboard-interactive answers"
+            )),
+        }
+    }
+
+    /// Answer a pending prompt; `None` cancels authentication
+    pub fn respond(&self, request_id: &str, answers: Option<Vec<String>>) -> Result<()> {
+        let tx = self
+            .pending
+            .lock()
+            .remove(request_id)
+            .ok_or_else(|| anyhow!("Authentication prompt not found or expired"))?;
+        let _ = tx.send(answers);
+        Ok(())
+    }
+}
@@ -0,0 +1,712 @@
+//! Known Hosts Store
+//!
+//! Trust-on-first-use host key database stored in OpenSSH `known_hosts` format.
+//! Plain, bracketed (`[host]:port`), wildcard and hashed (`|1|salt|hash`) host
+//! patterns are understood; new entries are always written hashed.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ring::digest;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::models::{HostKeyPromptEvent, HostKeyStatus, KnownHostEntry};
+use crate::services::ssh_service::{SshEvent, SshEventSink};
+
+/// How long a connection waits for the user to answer a host key prompt
+const HOST_KEY_PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Salt length used for hashed host names (same as OpenSSH)
+const HASH_SALT_LENGTH: usize = 20;
+
+/// Result of looking up a host key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostKeyCheck {
+    /// Key is recorded for this host
+    Trusted,
+    /// No key of this type is recorded for this host
+    Unknown,
+    /// A different key of the same type is recorded for this host
+    Changed { previous_fingerprint: String },
+    /// Key is marked `@revoked`
+    Revoked,
+}
+
+/// A single line of a known_hosts file
+#[derive(Debug, Clone)]
+enum Line {
+    Entry(Entry),
+    /// Comments, blank lines and anything we don't understand are kept verbatim
+    Other(String),
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    marker: Option<String>,
+    hosts: String,
+    key_type: String,
+    key: String,
+    comment: Option<String>,
+}
+
+impl Entry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let mut first = fields.next()?;
+        let marker = if first.starts_with('@') {
+            let marker = first.to_string();
+            first = fields.next()?;
+            Some(marker)
+        } else {
+            None
+        };
+        let key_type = fields.next()?.to_string();
+        let key = fields.next()?.to_string();
+        let comment: Vec<&str> = fields.collect();
+
+        Some(Self {
+            marker,
+            hosts: first.to_string(),
+            key_type,
+            key,
+            comment: if comment.is_empty() {
+                None
+            } else {
+                Some(comment.join(" "))
+            },
+        })
+    }
+
+    fn to_line(&self) -> String {
+        let mut line = String::new();
+        if let Some(marker) = &self.marker {
+            line.push_str(marker);
+            line.push(' ');
+        }
+        line.push_str(&self.hosts);
+        line.push(' ');
+        line.push_str(&self.key_type);
+        line.push(' ');
+        line.push_str(&self.key);
+        if let Some(comment) = &self.comment {
+            line.push(' ');
+            line.push_str(comment);
+        }
+        line
+    }
+
+    fn key_blob(&self) -> Option<Vec<u8>> {
+        BASE64.decode(&self.key).ok()
+    }
+
+    fn matches_host(&self, host_port: &str) -> bool {
+        match_host_patterns(host_port, &self.hosts)
+    }
+
+    /// Whether this line names the host explicitly rather than through a wildcard
+    fn names_host(&self, host_port: &str) -> bool {
+        self.marker.is_none()
+            && !self.hosts.contains(['*', '?', '!'])
+            && self.matches_host(host_port)
+    }
+
+    /// Take the patterns naming the host explicitly out of the host list.
+    /// Returns whether any were taken out.
+    fn remove_host(&mut self, host_port: &str) -> bool {
+        if self.marker.is_some() {
+            return false;
+        }
+        let patterns: Vec<&str> = self.hosts.split(',').collect();
+        let kept: Vec<&str> = patterns
+            .iter()
+            .copied()
+            .filter(|pattern| !names_pattern(host_port, pattern))
+            .collect();
+        if kept.len() == patterns.len() {
+            return false;
+        }
+        self.hosts = kept.join(",");
+        true
+    }
+
+    /// Whether any pattern is left that can match a host
+    fn has_hosts(&self) -> bool {
+        self.hosts
+            .split(',')
+            .any(|pattern| !pattern.is_empty() && !pattern.starts_with('!'))
+    }
+}
+
+/// In-memory view of a known_hosts file, optionally backed by a path on disk
+#[derive(Debug, Default)]
+pub struct KnownHosts {
+    path: Option<PathBuf>,
+    lines: Vec<Line>,
+}
+
+impl KnownHosts {
+    /// Create an empty store that is not persisted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a store from `path`; a missing file yields an empty store
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut store = Self::parse(&content);
+        store.path = Some(path.to_path_buf());
+        Ok(store)
+    }
+
+    /// Parse known_hosts content
+    pub fn parse(content: &str) -> Self {
+        let lines = content
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim();
+                if trimmed.is_empty() || trimmed.starts_with('#') {
+                    return Line::Other(line.to_string());
+                }
+                Entry::parse(trimmed)
+                    .map(Line::Entry)
+                    .unwrap_or_else(|| Line::Other(line.to_string()))
+            })
+            .collect();
+        Self { path: None, lines }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (usize, &Entry)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| match line {
+                Line::Entry(entry) => Some((i + 1, entry)),
+                Line::Other(_) => None,
+            })
+    }
+
+    /// Check a server key (raw SSH wire blob) against the recorded keys for a host
+    pub fn check(&self, host: &str, port: u16, key_type: &str, key_blob: &[u8]) -> HostKeyCheck {
+        let host_port = host_port(host, port);
+        let mut previous = None;
+
+        for (_, entry) in self.entries() {
+            if !entry.matches_host(&host_port) {
+                continue;
+            }
+            let Some(blob) = entry.key_blob() else {
+                continue;
+            };
+            match entry.marker.as_deref() {
+                Some("@revoked") => {
+                    if blob == key_blob {
+                        return HostKeyCheck::Revoked;
+                    }
+                }
+                // CA-signed host keys are not supported; ignore those lines
+                Some(_) => {}
+                None => {
+                    if blob == key_blob {
+                        return HostKeyCheck::Trusted;
+                    }
+                    if entry.key_type == key_type && previous.is_none() {
+                        previous = Some(fingerprint(&blob));
+                    }
+                }
+            }
+        }
+
+        match previous {
+            Some(previous_fingerprint) => HostKeyCheck::Changed {
+                previous_fingerprint,
+            },
+            None => HostKeyCheck::Unknown,
+        }
+    }
+
+    /// Record a key for a host, replacing any existing key of the same type
+    pub fn add(&mut self, host: &str, port: u16, key_type: &str, key_blob: &[u8]) -> Result<()> {
+        let host_port = host_port(host, port);
+        self.lines.retain(|line| match line {
+            // Lines shared with other hosts are kept; the new key still wins in `check`
+            Line::Entry(entry) => {
+                entry.key_type != key_type
+                    || entry.hosts.contains(',')
+                    || !entry.names_host(&host_port)
+            }
+            Line::Other(_) => true,
+        });
+        self.lines.push(Line::Entry(Entry {
+            marker: None,
+            hosts: hash_host(&host_port)?,
+            key_type: key_type.to_string(),
+            key: BASE64.encode(key_blob),
+            comment: None,
+        }));
+        self.save()
+    }
+
+    /// Forget all keys recorded for a host. On lines shared with other hosts
+    /// only the host's name is removed; a line goes once no host is left.
+    /// Returns the number of entries that named the host
+    pub fn remove(&mut self, host: &str, port: u16) -> Result<usize> {
+        let host_port = host_port(host, port);
+        let mut removed = 0;
+        self.lines.retain_mut(|line| match line {
+            Line::Entry(entry) => {
+                if !entry.remove_host(&host_port) {
+                    return true;
+                }
+                removed += 1;
+                entry.has_hosts()
+            }
+            Line::Other(_) => true,
+        });
+        if removed > 0 {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// List recorded entries
+    pub fn list(&self) -> Vec<KnownHostEntry> {
+        self.entries()
+            .map(|(line, entry)| KnownHostEntry {
+                line,
+                hosts: entry.hosts.clone(),
+                hashed: entry.hosts.starts_with("|1|"),
+                marker: entry.marker.clone(),
+                key_type: entry.key_type.clone(),
+                fingerprint: entry
+                    .key_blob()
+                    .map(|blob| fingerprint(&blob))
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    fn render(&self) -> String {
+        let mut content = String::new();
+        for line in &self.lines {
+            match line {
+                Line::Entry(entry) => content.push_str(&entry.to_line()),
+                Line::Other(other) => content.push_str(other),
+            }
+            content.push('\n');
+        }
+        content
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.render())?;
+        Ok(())
+    }
+}
+
+/// Host key verifier shared by all SSH client handlers.
+///
+/// Unknown or changed keys are sent to the frontend as a prompt; the
+/// connection waits until the user answers through `respond`.
+pub struct HostKeyVerifier {
+    store: parking_lot::RwLock<KnownHosts>,
+    pending: parking_lot::Mutex<HashMap<String, oneshot::Sender<bool>>>,
+    events: SshEventSink,
+}
+
+impl HostKeyVerifier {
+    pub fn new(events: SshEventSink) -> Self {
+        Self {
+            store: parking_lot::RwLock::new(KnownHosts::new()),
+            pending: parking_lot::Mutex::new(HashMap::new()),
+            events,
+        }
+    }
+
+    /// Switch to a known_hosts file on disk
+    pub fn load(&self, path: &Path) -> Result<()> {
+        *self.store.write() = KnownHosts::load(path)?;
+        Ok(())
+    }
+
+    /// Verify a server key, prompting the user when it is unknown or changed
+    pub async fn verify(self: &Arc<Self>, host: &str, port: u16, key_blob: &[u8]) -> Result<bool> {
+        let key_type =
+            key_type_from_blob(key_blob).ok_or_else(|| anyhow!("Malformed server host key"))?;
+        let check = self.store.read().check(host, port, &key_type, key_blob);
+
+        let (status, previous_fingerprint) = match check {
+            HostKeyCheck::Trusted => return Ok(true),
+            HostKeyCheck::Revoked => {
+                return Err(anyhow!("Host key for {}:{} has been revoked", host, port))
+            }
+            HostKeyCheck::Unknown => (HostKeyStatus::Unknown, None),
+            HostKeyCheck::Changed {
+                previous_fingerprint,
+            } => (HostKeyStatus::Changed, Some(previous_fingerprint)),
+        };
+
+        let request_id = Uuid::new_v4().to_string();
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().insert(request_id.clone(), tx);
+
+        let delivered = self
+            .events
+            .emit(SshEvent::HostKeyPrompt(HostKeyPromptEvent {
+                request_id: request_id.clone(),
+                host: host.to_string(),
+                port,
+                key_type: key_type.clone(),
+                fingerprint: fingerprint(key_blob),
+                status,
+                previous_fingerprint,
+            }));
+
+        let accepted = if delivered {
+            matches!(
+                tokio::time::timeout(HOST_KEY_PROMPT_TIMEOUT, rx).await,
+                Ok(Ok(true))
+            )
+        } else {
+            false
+        };
+        self.pending.lock().remove(&request_id);
+
+        if !accepted {
+            return Err(match status {
+                HostKeyStatus::Changed => anyhow!(
+                    "Host key for {}:{} has changed and was not trusted. This may indicate a man-in-the-middle attack",
+                    host,
+                    port
+                ),
+                _ => anyhow!("Host key for {}:{} was not trusted", host, port),
+            });
+        }
+
+        // The user accepted the key, so a known_hosts file that cannot be
+        // written only means the key is trusted for this run of the app
+        if let Err(e) = self.store.write().add(host, port, &key_type, key_blob) {
+            log::warn!(
+                "Could not save host key for {}:{} to known_hosts: {}",
+                host,
+                port,
+                e
+            );
+        }
+        Ok(true)
+    }
+
+    /// Answer a pending host key prompt
+    pub fn respond(&self, request_id: &str, accept: bool) -> Result<()> {
+        let tx = self
+            .pending
+            .lock()
+            .remove(request_id)
+            .ok_or_else(|| anyhow!("Host key request not found or expired"))?;
+        let _ = tx.send(accept);
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<KnownHostEntry> {
+        self.store.read().list()
+    }
+
+    pub fn remove(&self, host: &str, port: u16) -> Result<usize> {
+        self.store.write().remove(host, port)
+    }
+}
+
+/// Host name as written in known_hosts: `host` for port 22, `[host]:port` otherwise
+fn host_port(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+/// SHA256 fingerprint in OpenSSH display format
+pub fn fingerprint(key_blob: &[u8]) -> String {
+    let hash = digest::digest(&digest::SHA256, key_blob);
+    format!(
+        "SHA256:{}",
+        base64::engine::general_purpose::STANDARD_NO_PAD.encode(hash.as_ref())
+    )
+}
+
+/// Read the key type name that prefixes an SSH public key blob
+pub fn key_type_from_blob(blob: &[u8]) -> Option<String> {
+    let len = u32::from_be_bytes(blob.get(..4)?.try_into().ok()?) as usize;
+    let name = blob.get(4..4 + len)?;
+    String::from_utf8(name.to_vec()).ok()
+}
+
+fn hash_host(host_port: &str) -> Result<String> {
+    let mut salt = [0u8; HASH_SALT_LENGTH];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| anyhow!("Random generation failed"))?;
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &salt);
+    let tag = hmac::sign(&key, host_port.as_bytes());
+    Ok(format!(
+        "|1|{}|{}",
+        BASE64.encode(salt),
+        BASE64.encode(tag.as_ref())
+    ))
+}
+
+fn match_hashed(host_port: &str, pattern: &str) -> bool {
+    let mut parts = pattern.trim_start_matches("|1|").split('|');
+    let (Some(salt), Some(hash)) = (parts.next(), parts.next()) else {
+        return false;
+    };
+    let (Ok(salt), Ok(hash)) = (BASE64.decode(salt), BASE64.decode(hash)) else {
+        return false;
+    };
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &salt);
+    hmac::verify(&key, host_port.as_bytes(), &hash).is_ok()
+}
+
+/// Whether a single pattern names the host, hashed or in plain text, without wildcards
+fn names_pattern(host_port: &str, pattern: &str) -> bool {
+    if pattern.starts_with("|1|") {
+        match_hashed(host_port, pattern)
+    } else {
+        !pattern.contains(['*', '?', '!']) && pattern.eq_ignore_ascii_case(host_port)
+    }
+}
+
+/// Match a comma separated host pattern list. A matching negated pattern
+/// (`!pattern`) rejects the host even if another pattern matches.
+fn match_host_patterns(host_port: &str, patterns: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns.split(',') {
+        if pattern.starts_with("|1|") {
+            if match_hashed(host_port, pattern) {
+                matched = true;
+            }
+        } else if let Some(negated) = pattern.strip_prefix('!') {
+            if wildcard_match(negated, host_port) {
+                return false;
+            }
+        } else if wildcard_match(pattern, host_port) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+/// Glob match supporting `*` and `?`, case-insensitive like OpenSSH
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((bp, bt)) = backtrack {
+            p = bp + 1;
+            t = bt + 1;
+            backtrack = Some((bp, bt + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519_KEY: &str =
+        "AAAAC3NzaC1lZDI1NTE5AAAAIJdD7y3aLq454yWBdwLWbieU1ebz9/cu7/QEXn9OIeZJ";
+    const OTHER_ED25519_KEY: &str =
+        "AAAAC3NzaC1lZDI1NTE5AAAAIC1oOU/ewfF/Ty1wx6pMrNm9cNU9ElGSEuN2tAXQlq8J";
+
+    fn blob(key: &str) -> Vec<u8> {
+        BASE64.decode(key).unwrap()
+    }
+
+    #[test]
+    fn test_plain_and_bracketed_hosts() {
+        let store = KnownHosts::parse(&format!(
+            "# comment\nexample.com,10.0.0.1 ssh-ed25519 {k}\n[example.com]:2222 ssh-ed25519 {k}\n",
+            k = ED25519_KEY
+        ));
+        let key = blob(ED25519_KEY);
+        assert_eq!(
+            store.check("example.com", 22, "ssh-ed25519", &key),
+            HostKeyCheck::Trusted
+        );
+        assert_eq!(
+            store.check("10.0.0.1", 22, "ssh-ed25519", &key),
+            HostKeyCheck::Trusted
+        );
+        assert_eq!(
+            store.check("example.com", 2222, "ssh-ed25519", &key),
+            HostKeyCheck::Trusted
+        );
+        assert_eq!(
+            store.check("example.com", 2200, "ssh-ed25519", &key),
+            HostKeyCheck::Unknown
+        );
+    }
+
+    #[test]
+    fn test_changed_key_detected() {
+        let store = KnownHosts::parse(&format!("example.com ssh-ed25519 {}\n", ED25519_KEY));
+        let result = store.check("example.com", 22, "ssh-ed25519", &blob(OTHER_ED25519_KEY));
+        assert_eq!(
+            result,
+            HostKeyCheck::Changed {
+                previous_fingerprint: fingerprint(&blob(ED25519_KEY))
+            }
+        );
+    }
+
+    #[test]
+    fn test_hashed_hosts_roundtrip() {
+        let mut store = KnownHosts::new();
+        let key = blob(ED25519_KEY);
+        store.add("db.internal", 2222, "ssh-ed25519", &key).unwrap();
+
+        let reparsed = KnownHosts::parse(&store.render());
+        let entries = reparsed.list();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].hashed);
+        assert_eq!(
+            reparsed.check("db.internal", 2222, "ssh-ed25519", &key),
+            HostKeyCheck::Trusted
+        );
+        assert_eq!(
+            reparsed.check("db.internal", 22, "ssh-ed25519", &key),
+            HostKeyCheck::Unknown
+        );
+    }
+
+    #[test]
+    fn test_openssh_hashed_entry() {
+        // Generated with `ssh-keygen -H` for host "example.com"
+        let store = KnownHosts::parse(&format!(
+            "|1|OJcJ4pH0qKFOpJ1a3oC+u3KGM8o=|F9Ws5w7gyDhEqNIFNTqJUV46XqA= ssh-ed25519 {}\n",
+            ED25519_KEY
+        ));
+        let key = blob(ED25519_KEY);
+        assert_eq!(
+            store.check("example.com", 22, "ssh-ed25519", &key),
+            HostKeyCheck::Trusted
+        );
+        assert_eq!(
+            store.check("example.org", 22, "ssh-ed25519", &key),
+            HostKeyCheck::Unknown
+        );
+    }
+
+    #[test]
+    fn test_add_replaces_changed_key() {
+        let mut store = KnownHosts::parse(&format!("example.com ssh-ed25519 {}\n", ED25519_KEY));
+        let new_key = blob(OTHER_ED25519_KEY);
+        store
+            .add("example.com", 22, "ssh-ed25519", &new_key)
+            .unwrap();
+        assert_eq!(store.list().len(), 1);
+        assert_eq!(
+            store.check("example.com", 22, "ssh-ed25519", &new_key),
+            HostKeyCheck::Trusted
+        );
+    }
+
+    #[test]
+    fn test_add_kept_when_save_fails() {
+        let mut store = KnownHosts::new();
+        store.path = Some(PathBuf::from("/dev/null/known_hosts"));
+        let key = blob(ED25519_KEY);
+        assert!(store.add("example.com", 22, "ssh-ed25519", &key).is_err());
+        assert_eq!(
+            store.check("example.com", 22, "ssh-ed25519", &key),
+            HostKeyCheck::Trusted
+        );
+    }
+
+    #[test]
+    fn test_revoked_and_wildcards() {
+        let store = KnownHosts::parse(&format!(
+            "@revoked * ssh-ed25519 {}\n*.corp,!bad.corp ssh-ed25519 {}\n",
+            OTHER_ED25519_KEY, ED25519_KEY
+        ));
+        assert_eq!(
+            store.check("web.corp", 22, "ssh-ed25519", &blob(OTHER_ED25519_KEY)),
+            HostKeyCheck::Revoked
+        );
+        assert_eq!(
+            store.check("web.corp", 22, "ssh-ed25519", &blob(ED25519_KEY)),
+            HostKeyCheck::Trusted
+        );
+        assert_eq!(
+            store.check("bad.corp", 22, "ssh-ed25519", &blob(ED25519_KEY)),
+            HostKeyCheck::Unknown
+        );
+    }
+
+    #[test]
+    fn test_remove_host() {
+        let mut store = KnownHosts::parse(&format!(
+            "a.example ssh-ed25519 {k}\nb.example ssh-ed25519 {k}\n",
+            k = ED25519_KEY
+        ));
+        assert_eq!(store.remove("a.example", 22).unwrap(), 1);
+        assert_eq!(store.list().len(), 1);
+    }
+
+    #[test]
+    fn test_remove_host_from_shared_line() {
+        let mut store = KnownHosts::parse(&format!(
+            "a.example,10.0.0.1,*.corp ssh-ed25519 {k}\n[a.example]:2222,b.example ssh-ed25519 {k}\n",
+            k = ED25519_KEY
+        ));
+        let key = blob(ED25519_KEY);
+
+        // Only the host's name goes; the other hosts keep the key
+        assert_eq!(store.remove("a.example", 22).unwrap(), 1);
+        let entries = store.list();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].hosts, "10.0.0.1,*.corp");
+        assert_eq!(
+            store.check("a.example", 22, "ssh-ed25519", &key),
+            HostKeyCheck::Unknown
+        );
+        assert_eq!(
+            store.check("10.0.0.1", 22, "ssh-ed25519", &key),
+            HostKeyCheck::Trusted
+        );
+
+        // A line without hosts left is removed
+        assert_eq!(store.remove("a.example", 2222).unwrap(), 1);
+        assert_eq!(store.remove("b.example", 22).unwrap(), 1);
+        assert_eq!(store.list().len(), 1);
+        assert_eq!(store.remove("b.example", 22).unwrap(), 0);
+    }
+}
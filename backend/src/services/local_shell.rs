@@ -0,0 +1,375 @@
+//! Local Shell Sessions
+//!
+//! Runs shells on the local machine in a PTY. Output is streamed through the
+//! same data sender as SSH terminals and the sessions are addressed by the
+//! same session IDs, so the SSH commands (send data, resize, disconnect)
+//! work on them unchanged.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use portable_pty::{
+    native_pty_system, ChildKiller, CommandBuilder, ExitStatus, MasterPty, PtySize,
+};
+use uuid::Uuid;
+
+use crate::models::{
+    LocalShellInfo, LocalShellRequest, SessionStatus, SshStatusEvent, TerminalSize,
+};
+use crate::services::ssh_service::{SshEvent, SshEventSink};
+use crate::services::terminal_output::OutputSender;
+
+/// Shells looked up on the PATH where there is no `/etc/shells`
+#[cfg(windows)]
+const WINDOWS_SHELLS: &[(&str, &str)] = &[
+    ("PowerShell", "pwsh.exe"),
+    ("Windows PowerShell", "powershell.exe"),
+    ("Command Prompt", "cmd.exe"),
+];
+
+/// Shell running in a local PTY
+struct LocalShell {
+    master: parking_lot::Mutex<Box<dyn MasterPty + Send>>,
+    writer: Arc<parking_lot::Mutex<Box<dyn Write + Send>>>,
+    killer: parking_lot::Mutex<Box<dyn ChildKiller + Send + Sync>>,
+}
+
+/// Runs the local shell sessions
+pub struct LocalShellManager {
+    events: SshEventSink,
+    /// session_id -> shell
+    shells: Arc<parking_lot::Mutex<HashMap<String, Arc<LocalShell>>>>,
+}
+
+impl LocalShellManager {
+    pub fn new(events: SshEventSink) -> Self {
+        Self {
+            events,
+            shells: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Start a shell and stream its output to `data_tx`, which is dropped
+    /// once the shell has exited. Returns the session ID.
+    pub fn open(&self, request: &LocalShellRequest, data_tx: OutputSender) -> Result<String> {
+        let pair = native_pty_system().openpty(pty_size(request.terminal_size))?;
+
+        let mut command = match &request.shell {
+            Some(shell) => CommandBuilder::new(shell),
+            // The default program is started as a login shell
+            None if request.args.is_empty() => CommandBuilder::new_default_prog(),
+            None => CommandBuilder::new(CommandBuilder::new_default_prog().get_shell()),
+        };
+        command.args(&request.args);
+        if let Some(cwd) = &request.cwd {
+            command.cwd(cwd);
+        }
+        command.env("TERM", &request.term);
+        for (name, value) in &request.env {
+            command.env(name, value);
+        }
+
+        let mut child = pair
+            .slave
+            .spawn_command(command)
+            .map_err(|e| anyhow!("Failed to start shell: {}", e))?;
+        // The shell holds the only handle of the slave side now, so reading
+        // ends once it has exited
+        drop(pair.slave);
+
+        let reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+        let shell = Arc::new(LocalShell {
+            master: parking_lot::Mutex::new(pair.master),
+            writer: Arc::new(parking_lot::Mutex::new(writer)),
+            killer: parking_lot::Mutex::new(child.clone_killer()),
+        });
+
+        let session_id = Uuid::new_v4().to_string();
+        self.shells.lock().insert(session_id.clone(), shell);
+
+        std::thread::spawn(move || forward_output(reader, data_tx));
+
+        let shells = self.shells.clone();
+        let events = self.events.clone();
+        let id = session_id.clone();
+        std::thread::spawn(move || {
+            let message = match child.wait() {
+                Ok(status) => exit_message(&status),
+                Err(e) => format!("Lost track of the shell: {}", e),
+            };
+            // Dropping the shell closes the PTY, which also ends the output
+            // where the PTY outlives the shell (Windows). This is the only
+            // status event of the session; a shell closed by the user is
+            // already gone and needs no explanation.
+            let exited = shells.lock().remove(&id).is_some();
+            events.emit(SshEvent::Status(SshStatusEvent {
+                session_id: id,
+                status: SessionStatus::Disconnected,
+                message: exited.then_some(message),
+            }));
+        });
+
+        Ok(session_id)
+    }
+
+    pub fn contains(&self, session_id: &str) -> bool {
+        self.shells.lock().contains_key(session_id)
+    }
+
+    /// Type into the shell
+    pub async fn write(&self, session_id: &str, data: &[u8]) -> Result<()> {
+        let writer = self.shell(session_id)?.writer.clone();
+        let data = data.to_vec();
+        // Writes block while the shell is not reading
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let mut writer = writer.lock();
+            writer.write_all(&data)?;
+            writer.flush()?;
+            Ok(())
+        })
+        .await?
+    }
+
+    pub fn resize(&self, session_id: &str, size: TerminalSize) -> Result<()> {
+        self.shell(session_id)?.master.lock().resize(pty_size(size))
+    }
+
+    /// Kill the shell of a session. Returns whether there was one.
+    pub fn close(&self, session_id: &str) -> bool {
+        let Some(shell) = self.shells.lock().remove(session_id) else {
+            return false;
+        };
+        // Fails if the shell has just exited on its own
+        let _ = shell.killer.lock().kill();
+        true
+    }
+
+    fn shell(&self, session_id: &str) -> Result<Arc<LocalShell>> {
+        self.shells
+            .lock()
+            .get(session_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Session not found"))
+    }
+}
+
+/// Shells installed on this machine, the user's default shell first
+pub fn available_shells() -> Vec<LocalShellInfo> {
+    let default = CommandBuilder::new_default_prog().get_shell();
+
+    #[cfg(unix)]
+    let mut shells = {
+        let listed = std::fs::read_to_string("/etc/shells").unwrap_or_default();
+        parse_shells(&listed, &default)
+            .into_iter()
+            .filter(|shell| Path::new(&shell.path).is_file())
+            .collect::<Vec<_>>()
+    };
+    #[cfg(windows)]
+    let mut shells = WINDOWS_SHELLS
+        .iter()
+        .filter_map(|(name, program)| {
+            let path = find_in_path(program)?;
+            Some(LocalShellInfo {
+                name: name.to_string(),
+                default: same_program(&path, &default),
+                path,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if !shells.iter().any(|shell| shell.default) {
+        shells.insert(
+            0,
+            LocalShellInfo {
+                name: shell_name(&default),
+                path: default,
+                default: true,
+            },
+        );
+    }
+    shells.sort_by_key(|shell| !shell.default);
+    shells
+}
+
+/// Shells listed in an `/etc/shells` file, one per name (the first path
+/// listed wins unless another one is the default)
+fn parse_shells(listed: &str, default: &str) -> Vec<LocalShellInfo> {
+    let mut shells: Vec<LocalShellInfo> = Vec::new();
+    for path in listed
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    {
+        let name = shell_name(path);
+        // Not login shells
+        if matches!(name.as_str(), "nologin" | "false" | "git-shell") {
+            continue;
+        }
+        let is_default = same_program(path, default);
+        match shells.iter_mut().find(|shell| shell.name == name) {
+            Some(shell) if is_default => {
+                shell.path = path.to_string();
+                shell.default = true;
+            }
+            Some(_) => {}
+            None => shells.push(LocalShellInfo {
+                name,
+                path: path.to_string(),
+                default: is_default,
+            }),
+        }
+    }
+    shells
+}
+
+fn shell_name(path: &str) -> String {
+    let file = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+    file.strip_suffix(".exe")
+        .map(str::to_string)
+        .unwrap_or(file)
+}
+
+fn same_program(path: &str, other: &str) -> bool {
+    if cfg!(windows) {
+        path.eq_ignore_ascii_case(other)
+            || shell_name(path).eq_ignore_ascii_case(&shell_name(other))
+    } else {
+        path == other
+    }
+}
+
+#[cfg(windows)]
+fn find_in_path(program: &str) -> Option<String> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+        .map(|path| path.display().to_string())
+}
+
+fn pty_size(size: TerminalSize) -> PtySize {
+    PtySize {
+        rows: size.rows as u16,
+        cols: size.cols as u16,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+fn exit_message(status: &ExitStatus) -> String {
+    match status.signal() {
+        Some(signal) => format!("Shell was terminated by {}", signal),
+        None => format!("Shell exited with code {}", status.exit_code()),
+    }
+}
+
+/// Stream the output of a shell until its PTY is closed
+fn forward_output(mut reader: Box<dyn Read + Send>, data_tx: OutputSender) {
+    let mut buffer = [0u8; 8192];
+    loop {
+        // Reading fails rather than ending on some systems once the shell exits
+        match reader.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            // Waiting for room in the queue leaves the shell blocked on writing
+            Ok(n) => {
+                if !data_tx.blocking_send(buffer[..n].to_vec()) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::terminal_output;
+    use futures::StreamExt;
+
+    #[test]
+    fn test_parse_shells() {
+        let listed = "# /etc/shells: valid login shells\n\
+                      /bin/sh\n\
+                      /bin/bash\n\
+                      /usr/bin/bash\n\
+                      /usr/bin/zsh\n\
+                      \n\
+                      /usr/sbin/nologin\n\
+                      /usr/bin/fish\n";
+        let shells = parse_shells(listed, "/usr/bin/zsh");
+        let names: Vec<&str> = shells.iter().map(|shell| shell.name.as_str()).collect();
+        assert_eq!(names, ["sh", "bash", "zsh", "fish"]);
+        assert_eq!(shells[1].path, "/bin/bash");
+        assert!(shells[2].default);
+
+        // The default path of a shell listed twice is kept
+        let shells = parse_shells(listed, "/usr/bin/bash");
+        assert_eq!(shells[1].path, "/usr/bin/bash");
+        assert!(shells[1].default);
+        assert_eq!(shells.iter().filter(|shell| shell.default).count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shell_session() {
+        let events = SshEventSink::default();
+        let (events_tx, mut events_rx) = futures::channel::mpsc::unbounded();
+        events.set(events_tx);
+        let manager = LocalShellManager::new(events);
+        let (tx, mut rx) = terminal_output::channel();
+        let request = LocalShellRequest {
+            shell: Some("/bin/sh".to_string()),
+            args: Vec::new(),
+            cwd: None,
+            term: "dumb".to_string(),
+            env: [("GREETING".to_string(), "hello".to_string())].into(),
+            terminal_size: TerminalSize {
+                cols: 100,
+                rows: 30,
+            },
+        };
+        let session_id = manager.open(&request, tx).unwrap();
+        assert!(manager.contains(&session_id));
+
+        manager
+            .write(
+                &session_id,
+                b"echo \"$GREETING $TERM\"; stty size; exit 3\n",
+            )
+            .await
+            .unwrap();
+        let mut output = Vec::new();
+        while let Some(data) = rx.next_frame().await {
+            output.extend(data);
+        }
+        let output = String::from_utf8_lossy(&output);
+        assert!(output.contains("hello dumb"), "{}", output);
+        assert!(output.contains("30 100"), "{}", output);
+
+        // The session ends with the shell
+        for _ in 0..100 {
+            if !manager.contains(&session_id) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(!manager.contains(&session_id));
+        assert!(!manager.close(&session_id));
+
+        // ...and is reported once, with the exit status
+        let Some(SshEvent::Status(status)) = events_rx.next().await else {
+            panic!("expected a status event");
+        };
+        assert_eq!(status.status, SessionStatus::Disconnected);
+        assert_eq!(status.message.as_deref(), Some("Shell exited with code 3"));
+        assert!(!matches!(events_rx.try_next(), Ok(Some(_))));
+    }
+}
@@ -0,0 +1,768 @@
+//! Host Metrics Collector
+//!
+//! Samples the resource usage of remote Linux hosts. Each monitored session
+//! gets one exec channel running a shell loop that prints `/proc` files and
+//! `df` output between section markers. Every round is parsed into typed
+//! metrics, rates are derived from the round before it, and recent samples
+//! are kept for sparklines.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use russh::{client, Channel, ChannelMsg};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::models::{
+    CpuMetrics, DiskMetrics, LoadMetrics, MemoryMetrics, MetricsMonitorInfo, MetricsSample,
+    MetricsStoppedEvent, NetworkMetrics,
+};
+use crate::services::ssh_service::{SshEvent, SshEventSink};
+
+/// Samples kept per session (five minutes at the default interval)
+const HISTORY_LEN: usize = 150;
+
+/// Prefix of the lines separating the sections of a round
+const SECTION_MARKER: &str = "==> ";
+
+/// Section that closes a round
+const END_SECTION: &str = "end";
+
+/// Shell loop printing one round of raw metrics every `interval_secs`. It
+/// exits on its own once the channel is closed and `echo` hits a broken pipe.
+fn collector_script(interval_secs: u64) -> String {
+    format!(
+        "export LC_ALL=C; while :; do \
+         echo '{m}uptime'; cat /proc/uptime; \
+         echo '{m}stat'; cat /proc/stat; \
+         echo '{m}meminfo'; cat /proc/meminfo; \
+         echo '{m}loadavg'; cat /proc/loadavg; \
+         echo '{m}netdev'; cat /proc/net/dev; \
+         echo '{m}df'; df -P -k; \
+         echo '{m}{end}' || exit; \
+         sleep {interval}; done 2>/dev/null",
+        m = SECTION_MARKER,
+        end = END_SECTION,
+        interval = interval_secs.max(1),
+    )
+}
+
+/// Cumulative CPU time from a `cpu` line of /proc/stat, in clock ticks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuTimes {
+    pub total: u64,
+    pub idle: u64,
+    pub iowait: u64,
+}
+
+impl CpuTimes {
+    /// Usage and I/O wait percentages between `previous` and `self`
+    fn percent_since(&self, previous: &CpuTimes) -> Option<(f64, f64)> {
+        let total = self.total.checked_sub(previous.total)?;
+        let idle = self.idle.checked_sub(previous.idle)?;
+        let iowait = self.iowait.checked_sub(previous.iowait)?;
+        if total == 0 {
+            return None;
+        }
+        let busy = total.saturating_sub(idle + iowait);
+        Some((percent(busy, total), percent(iowait, total)))
+    }
+}
+
+/// Byte counters of a network interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetCounters {
+    pub interface: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// One round of raw metrics as reported by the host
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub uptime: Option<f64>,
+    pub cpu: Option<CpuTimes>,
+    pub cores: Vec<CpuTimes>,
+    pub memory: Option<MemoryMetrics>,
+    pub load: Option<LoadMetrics>,
+    pub disks: Vec<DiskMetrics>,
+    pub network: Vec<NetCounters>,
+}
+
+impl Snapshot {
+    /// Build a snapshot from the text of its sections
+    fn from_sections(sections: &HashMap<String, String>) -> Self {
+        let section = |name: &str| sections.get(name).map(String::as_str).unwrap_or("");
+        let (cpu, cores) = parse_stat(section("stat"));
+        Self {
+            uptime: parse_uptime(section("uptime")),
+            cpu,
+            cores,
+            memory: parse_meminfo(section("meminfo")),
+            load: parse_loadavg(section("loadavg")),
+            disks: parse_df(section("df")),
+            network: parse_net_dev(section("netdev")),
+        }
+    }
+
+    /// Whether the host reported any of the /proc metrics
+    fn has_proc(&self) -> bool {
+        self.cpu.is_some() || self.memory.is_some() || self.load.is_some()
+    }
+}
+
+/// Splits collector output into snapshots
+#[derive(Default)]
+pub struct SnapshotReader {
+    /// Incomplete last line
+    pending: Vec<u8>,
+    section: Option<String>,
+    sections: HashMap<String, String>,
+}
+
+impl SnapshotReader {
+    /// Consume output, returning the rounds it completed
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Snapshot> {
+        self.pending.extend_from_slice(data);
+        let mut snapshots = Vec::new();
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line[..end]);
+            let line = line.trim_end_matches('\r');
+
+            match line.strip_prefix(SECTION_MARKER) {
+                Some(END_SECTION) => {
+                    snapshots.push(Snapshot::from_sections(&self.sections));
+                    self.sections.clear();
+                    self.section = None;
+                }
+                Some(name) => self.section = Some(name.to_string()),
+                None => {
+                    if let Some(name) = &self.section {
+                        let text = self.sections.entry(name.clone()).or_default();
+                        text.push_str(line);
+                        text.push('\n');
+                    }
+                }
+            }
+        }
+        snapshots
+    }
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+/// Parse /proc/uptime: seconds since boot
+pub fn parse_uptime(text: &str) -> Option<f64> {
+    text.split_whitespace().next()?.parse().ok()
+}
+
+/// Parse /proc/stat into the times of all CPUs and of each core
+pub fn parse_stat(text: &str) -> (Option<CpuTimes>, Vec<CpuTimes>) {
+    let mut all = None;
+    let mut cores = Vec::new();
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        let Some(name) = fields.next().filter(|name| name.starts_with("cpu")) else {
+            continue;
+        };
+        // user nice system idle iowait irq softirq steal; guest time is
+        // already counted in user and nice
+        let values: Vec<u64> = fields.take(8).filter_map(|v| v.parse().ok()).collect();
+        if values.len() < 4 {
+            continue;
+        }
+        let times = CpuTimes {
+            total: values.iter().sum(),
+            idle: values[3],
+            iowait: values.get(4).copied().unwrap_or(0),
+        };
+        if name == "cpu" {
+            all = Some(times);
+        } else {
+            cores.push(times);
+        }
+    }
+    (all, cores)
+}
+
+/// Parse /proc/meminfo
+pub fn parse_meminfo(text: &str) -> Option<MemoryMetrics> {
+    let fields: HashMap<&str, u64> = text
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            // Values are in kB
+            let kb: u64 = value.split_whitespace().next()?.parse().ok()?;
+            Some((key.trim(), kb * 1024))
+        })
+        .collect();
+
+    let total = *fields.get("MemTotal")?;
+    // Kernels before 3.14 have no MemAvailable
+    let available = fields.get("MemAvailable").copied().unwrap_or_else(|| {
+        ["MemFree", "Buffers", "Cached"]
+            .iter()
+            .filter_map(|key| fields.get(key))
+            .sum()
+    });
+    let used = total.saturating_sub(available);
+    let swap_total = fields.get("SwapTotal").copied().unwrap_or(0);
+    let swap_free = fields.get("SwapFree").copied().unwrap_or(0);
+    Some(MemoryMetrics {
+        total_bytes: total,
+        available_bytes: available,
+        used_bytes: used,
+        usage_percent: percent(used, total),
+        swap_total_bytes: swap_total,
+        swap_used_bytes: swap_total.saturating_sub(swap_free),
+    })
+}
+
+/// Parse /proc/loadavg, e.g. `0.52 0.58 0.59 2/1234 5678`
+pub fn parse_loadavg(text: &str) -> Option<LoadMetrics> {
+    let mut fields = text.split_whitespace();
+    let one = fields.next()?.parse().ok()?;
+    let five = fields.next()?.parse().ok()?;
+    let fifteen = fields.next()?.parse().ok()?;
+    let (running, tasks) = fields.next()?.split_once('/')?;
+    Some(LoadMetrics {
+        one,
+        five,
+        fifteen,
+        running: running.parse().ok()?,
+        tasks: tasks.parse().ok()?,
+    })
+}
+
+/// Parse /proc/net/dev, leaving out the loopback interface
+pub fn parse_net_dev(text: &str) -> Vec<NetCounters> {
+    text.lines()
+        .filter_map(|line| {
+            // Old kernels print large counters right after the colon
+            let (interface, counters) = line.split_once(':')?;
+            let interface = interface.trim();
+            if interface == "lo" {
+                return None;
+            }
+            let counters: Vec<u64> = counters
+                .split_whitespace()
+                .map(|v| v.parse().ok())
+                .collect::<Option<_>>()?;
+            // Eight receive columns come before the transmit ones
+            Some(NetCounters {
+                interface: interface.to_string(),
+                rx_bytes: *counters.first()?,
+                tx_bytes: *counters.get(8)?,
+            })
+        })
+        .collect()
+}
+
+/// Filesystems that only hold memory or kernel state
+const PSEUDO_FILESYSTEMS: &[&str] = &["tmpfs", "devtmpfs", "udev", "shm", "none", "proc", "sysfs"];
+
+/// Parse `df -P -k`, leaving out pseudo filesystems
+pub fn parse_df(text: &str) -> Vec<DiskMetrics> {
+    text.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 || PSEUDO_FILESYSTEMS.contains(&fields[0]) {
+                return None;
+            }
+            let total: u64 = fields[1].parse().ok()?;
+            let used: u64 = fields[2].parse().ok()?;
+            let available: u64 = fields[3].parse().ok()?;
+            if total == 0 {
+                return None;
+            }
+            Some(DiskMetrics {
+                filesystem: fields[0].to_string(),
+                // Mount points may contain spaces
+                mount_point: fields[5..].join(" "),
+                total_bytes: total * 1024,
+                used_bytes: used * 1024,
+                available_bytes: available * 1024,
+                // Same as df's capacity column: reserved blocks do not count
+                usage_percent: percent(used, used + available),
+            })
+        })
+        .collect()
+}
+
+/// Counters of the previous snapshot
+struct Previous {
+    uptime: Option<f64>,
+    clock: f64,
+    cpu: Option<CpuTimes>,
+    cores: Vec<CpuTimes>,
+    network: HashMap<String, (u64, u64)>,
+}
+
+/// Turns snapshots into samples, deriving rates from the snapshot before
+#[derive(Default)]
+pub struct RateTracker {
+    previous: Option<Previous>,
+}
+
+impl RateTracker {
+    /// Build the sample of `snapshot`, taken `clock` seconds into collection.
+    /// The host's uptime is preferred as the clock so network latency does
+    /// not skew rates.
+    pub fn sample(&mut self, session_id: &str, snapshot: Snapshot, clock: f64) -> MetricsSample {
+        let previous = self.previous.take();
+        let elapsed = previous
+            .as_ref()
+            .map(|prev| match (snapshot.uptime, prev.uptime) {
+                (Some(now), Some(before)) if now > before => now - before,
+                _ => clock - prev.clock,
+            });
+
+        let cpu = previous.as_ref().and_then(|prev| {
+            let (usage, iowait) = snapshot.cpu?.percent_since(&prev.cpu?)?;
+            let cores = snapshot
+                .cores
+                .iter()
+                .zip(&prev.cores)
+                .map(|(now, before)| now.percent_since(before).map_or(0.0, |(usage, _)| usage))
+                .collect();
+            Some(CpuMetrics {
+                usage_percent: usage,
+                iowait_percent: iowait,
+                cores,
+            })
+        });
+
+        let rate = |now: u64, before: Option<u64>| -> Option<f64> {
+            let elapsed = elapsed.filter(|secs| *secs > 0.0)?;
+            // A lower counter means it wrapped or the interface was reset
+            Some(now.checked_sub(before?)? as f64 / elapsed)
+        };
+        let network = snapshot
+            .network
+            .iter()
+            .map(|counters| {
+                let before = previous
+                    .as_ref()
+                    .and_then(|prev| prev.network.get(&counters.interface));
+                NetworkMetrics {
+                    interface: counters.interface.clone(),
+                    rx_bytes: counters.rx_bytes,
+                    tx_bytes: counters.tx_bytes,
+                    rx_bytes_per_sec: rate(counters.rx_bytes, before.map(|b| b.0)),
+                    tx_bytes_per_sec: rate(counters.tx_bytes, before.map(|b| b.1)),
+                }
+            })
+            .collect();
+
+        self.previous = Some(Previous {
+            uptime: snapshot.uptime,
+            clock,
+            cpu: snapshot.cpu,
+            cores: snapshot.cores,
+            network: snapshot
+                .network
+                .iter()
+                .map(|c| (c.interface.clone(), (c.rx_bytes, c.tx_bytes)))
+                .collect(),
+        });
+
+        MetricsSample {
+            session_id: session_id.to_string(),
+            timestamp: Utc::now().timestamp_millis(),
+            uptime_secs: snapshot.uptime,
+            cpu,
+            memory: snapshot.memory,
+            load: snapshot.load,
+            disks: snapshot.disks,
+            network,
+        }
+    }
+}
+
+struct Monitor {
+    /// Tells a collector apart from one started later for the same session
+    monitor_id: String,
+    interval_secs: u64,
+    cancel: CancellationToken,
+    history: VecDeque<MetricsSample>,
+}
+
+impl Monitor {
+    fn info(&self, session_id: &str) -> MetricsMonitorInfo {
+        MetricsMonitorInfo {
+            session_id: session_id.to_string(),
+            interval_secs: self.interval_secs,
+            samples: self.history.len(),
+        }
+    }
+}
+
+/// Runs the metrics collectors of sessions and keeps their recent samples
+pub struct MetricsManager {
+    events: SshEventSink,
+    /// session_id -> running collector
+    monitors: Arc<parking_lot::Mutex<HashMap<String, Monitor>>>,
+}
+
+impl MetricsManager {
+    pub fn new(events: SshEventSink) -> Self {
+        Self {
+            events,
+            monitors: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Get the collector of a session, if one is running
+    pub fn info(&self, session_id: &str) -> Option<MetricsMonitorInfo> {
+        self.monitors
+            .lock()
+            .get(session_id)
+            .map(|monitor| monitor.info(session_id))
+    }
+
+    /// Start collecting on `channel`. Samples are emitted as
+    /// `SshEvent::Metrics` and kept in the session's history. If a collector
+    /// is already running for the session, it is kept and `channel` closed.
+    pub fn spawn(
+        &self,
+        session_id: &str,
+        interval_secs: u64,
+        channel: Channel<client::Msg>,
+    ) -> MetricsMonitorInfo {
+        let interval_secs = interval_secs.max(1);
+        let monitor_id = Uuid::new_v4().to_string();
+        let cancel = CancellationToken::new();
+        {
+            let mut monitors = self.monitors.lock();
+            if let Some(monitor) = monitors.get(session_id) {
+                let info = monitor.info(session_id);
+                tokio::spawn(async move {
+                    let _ = channel.close().await;
+                });
+                return info;
+            }
+            monitors.insert(
+                session_id.to_string(),
+                Monitor {
+                    monitor_id: monitor_id.clone(),
+                    interval_secs,
+                    cancel: cancel.clone(),
+                    history: VecDeque::with_capacity(HISTORY_LEN),
+                },
+            );
+        }
+
+        let monitors = self.monitors.clone();
+        let events = self.events.clone();
+        let id = session_id.to_string();
+        tokio::spawn(async move {
+            let result = collect(channel, &id, interval_secs, &cancel, |sample| {
+                let mut monitors = monitors.lock();
+                match monitors.get_mut(&id) {
+                    Some(monitor) if monitor.monitor_id == monitor_id => {
+                        if monitor.history.len() == HISTORY_LEN {
+                            monitor.history.pop_front();
+                        }
+                        monitor.history.push_back(sample.clone());
+                        events.emit(SshEvent::Metrics(sample));
+                        true
+                    }
+                    _ => false,
+                }
+            })
+            .await;
+
+            {
+                let mut monitors = monitors.lock();
+                if monitors
+                    .get(&id)
+                    .is_some_and(|monitor| monitor.monitor_id == monitor_id)
+                {
+                    monitors.remove(&id);
+                }
+            }
+            if let Err(e) = &result {
+                log::warn!("Metrics collection for session {} stopped: {}", id, e);
+            }
+            events.emit(SshEvent::MetricsStopped(MetricsStoppedEvent {
+                session_id: id,
+                error: result.err().map(|e| e.to_string()),
+            }));
+        });
+
+        MetricsMonitorInfo {
+            session_id: session_id.to_string(),
+            interval_secs,
+            samples: 0,
+        }
+    }
+
+    /// Stop the collector of a session and drop its history
+    pub fn stop(&self, session_id: &str) -> bool {
+        match self.monitors.lock().remove(session_id) {
+            Some(monitor) => {
+                monitor.cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Recent samples of a session, oldest first
+    pub fn history(&self, session_id: &str) -> Result<Vec<MetricsSample>> {
+        self.monitors
+            .lock()
+            .get(session_id)
+            .map(|monitor| monitor.history.iter().cloned().collect())
+            .ok_or_else(|| anyhow!("Metrics are not being collected for this session"))
+    }
+}
+
+/// Run the collector script on `channel` and pass each sample to `on_sample`
+/// until it returns false, the channel closes or `cancel` fires
+async fn collect(
+    mut channel: Channel<client::Msg>,
+    session_id: &str,
+    interval_secs: u64,
+    cancel: &CancellationToken,
+    mut on_sample: impl FnMut(MetricsSample) -> bool,
+) -> Result<()> {
+    let started = Instant::now();
+    let mut reader = SnapshotReader::default();
+    let mut rates = RateTracker::default();
+    let result = async {
+        channel
+            .exec(true, collector_script(interval_secs).as_str())
+            .await?;
+        channel.eof().await?;
+
+        loop {
+            let msg = tokio::select! {
+                msg = channel.wait() => msg,
+                _ = cancel.cancelled() => return Ok(()),
+            };
+            match msg {
+                Some(ChannelMsg::Data { data }) => {
+                    for snapshot in reader.feed(&data) {
+                        if !snapshot.has_proc() {
+                            return Err(anyhow!("Host does not provide /proc metrics"));
+                        }
+                        let clock = started.elapsed().as_secs_f64();
+                        if !on_sample(rates.sample(session_id, snapshot, clock)) {
+                            return Ok(());
+                        }
+                    }
+                }
+                Some(ChannelMsg::Failure) => {
+                    return Err(anyhow!("Server refused to run the metrics collector"));
+                }
+                Some(ChannelMsg::Close) | None => return Err(anyhow!("Metrics channel closed")),
+                _ => {}
+            }
+        }
+    }
+    .await;
+
+    let _ = channel.close().await;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAT: &str = "\
+cpu  4705 356 584 3699 23 0 23 0 0 0
+cpu0 2348 178 292 1849 12 0 12 0 0 0
+cpu1 2357 178 292 1850 11 0 11 0 0 0
+intr 1462898 41 0 0 0 0 0 0 0 1 0 0 0 0 0 0
+ctxt 2710213
+btime 1700000000
+processes 7123
+procs_running 2
+";
+
+    const STAT_LATER: &str = "\
+cpu  4805 356 604 3759 43 0 23 0 0 0
+cpu0 2448 178 302 1849 12 0 12 0 0 0
+cpu1 2357 178 302 1910 21 0 11 0 0 0
+";
+
+    const MEMINFO: &str = "\
+MemTotal:        8048260 kB
+MemFree:          532444 kB
+MemAvailable:    4024130 kB
+Buffers:          231028 kB
+Cached:          3012644 kB
+SwapCached:            0 kB
+SwapTotal:       2097148 kB
+SwapFree:        1048574 kB
+";
+
+    const NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  104860    1024    0    0    0     0          0         0   104860    1024    0    0    0     0       0          0
+  eth0: 98765432   87654    0    0    0     0          0        12 12345678   65432    0    0    0     0       0          0
+docker0:       0       0    0    0    0     0          0         0      656       8    0    0    0     0       0          0
+";
+
+    const DF: &str = "\
+Filesystem     1024-blocks     Used Available Capacity Mounted on
+/dev/sda1         51474912 20491672  28345416      42% /
+tmpfs              4024128        0   4024128       0% /dev/shm
+/dev/sdb1        103081248 51540624  46297424      53% /mnt/backup disk
+";
+
+    #[test]
+    fn test_parse_stat() {
+        let (all, cores) = parse_stat(STAT);
+        assert_eq!(
+            all,
+            Some(CpuTimes {
+                total: 9390,
+                idle: 3699,
+                iowait: 23,
+            })
+        );
+        assert_eq!(cores.len(), 2);
+        assert_eq!(cores[1].idle, 1850);
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let memory = parse_meminfo(MEMINFO).unwrap();
+        assert_eq!(memory.total_bytes, 8048260 * 1024);
+        assert_eq!(memory.available_bytes, 4024130 * 1024);
+        assert_eq!(memory.used_bytes, 4024130 * 1024);
+        assert!((memory.usage_percent - 50.0).abs() < 0.01);
+        assert_eq!(memory.swap_used_bytes, 1048574 * 1024);
+
+        // Without MemAvailable, free memory and caches count as available
+        let old = MEMINFO.replace("MemAvailable:    4024130 kB\n", "");
+        let memory = parse_meminfo(&old).unwrap();
+        assert_eq!(memory.available_bytes, (532444 + 231028 + 3012644) * 1024);
+
+        assert!(parse_meminfo("").is_none());
+    }
+
+    #[test]
+    fn test_parse_loadavg_and_uptime() {
+        let load = parse_loadavg("0.52 0.58 0.59 2/1234 5678\n").unwrap();
+        assert_eq!((load.one, load.five, load.fifteen), (0.52, 0.58, 0.59));
+        assert_eq!((load.running, load.tasks), (2, 1234));
+        assert!(parse_loadavg("0.52 0.58").is_none());
+
+        assert_eq!(parse_uptime("350735.47 234388.90\n"), Some(350735.47));
+    }
+
+    #[test]
+    fn test_parse_net_dev() {
+        let network = parse_net_dev(NET_DEV);
+        assert_eq!(
+            network,
+            vec![
+                NetCounters {
+                    interface: "eth0".to_string(),
+                    rx_bytes: 98765432,
+                    tx_bytes: 12345678,
+                },
+                NetCounters {
+                    interface: "docker0".to_string(),
+                    rx_bytes: 0,
+                    tx_bytes: 656,
+                },
+            ]
+        );
+        // Counters glued to the colon on old kernels
+        let glued = parse_net_dev("  eth1:4294967296 1 0 0 0 0 0 0 42 1 0 0 0 0 0 0");
+        assert_eq!((glued[0].rx_bytes, glued[0].tx_bytes), (4294967296, 42));
+    }
+
+    #[test]
+    fn test_parse_df() {
+        let disks = parse_df(DF);
+        assert_eq!(disks.len(), 2);
+        assert_eq!(disks[0].mount_point, "/");
+        assert_eq!(disks[0].total_bytes, 51474912 * 1024);
+        assert_eq!(disks[0].usage_percent.round(), 42.0);
+        assert_eq!(disks[1].mount_point, "/mnt/backup disk");
+    }
+
+    fn round(stat: &str, uptime: &str, net_dev: &str) -> String {
+        format!(
+            "==> uptime\n{}\n==> stat\n{}==> meminfo\n{}==> loadavg\n0.52 0.58 0.59 2/1234 5678\n\
+             ==> netdev\n{}==> df\n{}==> end\n",
+            uptime, stat, MEMINFO, net_dev, DF
+        )
+    }
+
+    #[test]
+    fn test_reader_splits_rounds() {
+        let output = round(STAT, "100.00 50.00", NET_DEV);
+        let mut reader = SnapshotReader::default();
+        // Output arrives in arbitrary chunks
+        let (first, second) = output.as_bytes().split_at(97);
+        assert!(reader.feed(first).is_empty());
+        let snapshots = reader.feed(second);
+        assert_eq!(snapshots.len(), 1);
+
+        let snapshot = &snapshots[0];
+        assert_eq!(snapshot.uptime, Some(100.0));
+        assert_eq!(snapshot.cores.len(), 2);
+        assert!(snapshot.memory.is_some() && snapshot.load.is_some());
+        assert_eq!(snapshot.disks.len(), 2);
+        assert_eq!(snapshot.network.len(), 2);
+        assert!(snapshot.has_proc());
+
+        // A host without /proc still completes rounds, just empty ones
+        let snapshots = reader.feed(b"==> stat\n==> meminfo\n==> end\n");
+        assert!(!snapshots[0].has_proc());
+    }
+
+    #[test]
+    fn test_rates() {
+        let later_net = NET_DEV
+            .replace("98765432", "98775432")
+            .replace("12345678", "12347678")
+            .replace("     656", "       0");
+        let output =
+            round(STAT, "100.00 50.00", NET_DEV) + &round(STAT_LATER, "102.00 51.00", &later_net);
+        let mut snapshots = SnapshotReader::default()
+            .feed(output.as_bytes())
+            .into_iter();
+        let mut rates = RateTracker::default();
+
+        let first = rates.sample("s1", snapshots.next().unwrap(), 0.0);
+        assert!(first.cpu.is_none());
+        assert!(first.network[0].rx_bytes_per_sec.is_none());
+
+        // The host clock says 2 seconds passed even though the sample came late
+        let second = rates.sample("s1", snapshots.next().unwrap(), 5.0);
+        let cpu = second.cpu.unwrap();
+        // 200 ticks passed: 60 idle, 20 iowait
+        assert!((cpu.usage_percent - 60.0).abs() < 0.01);
+        assert!((cpu.iowait_percent - 10.0).abs() < 0.01);
+        assert_eq!(cpu.cores.len(), 2);
+        assert!((cpu.cores[0] - 100.0).abs() < 0.01);
+        assert!((cpu.cores[1] - 12.5).abs() < 0.01);
+
+        let eth0 = &second.network[0];
+        assert_eq!(eth0.rx_bytes_per_sec, Some(5000.0));
+        assert_eq!(eth0.tx_bytes_per_sec, Some(1000.0));
+        // docker0's transmit counter went down, so it was reset
+        assert_eq!(second.network[1].tx_bytes_per_sec, None);
+        assert_eq!(second.network[1].rx_bytes_per_sec, Some(0.0));
+    }
+}
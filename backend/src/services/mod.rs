@@ -2,10 +2,30 @@
 //!
 //! This module contains all service implementations.
 
+pub mod algorithms;
+pub mod batch;
+pub mod broadcast;
+pub mod certificate;
 pub mod crypto_service;
 pub mod database;
+pub mod diagnostics;
+pub mod exec;
+pub mod interactive_auth;
+pub mod known_hosts;
+pub mod local_shell;
+pub mod metrics;
+pub mod proxy;
+pub mod recording;
+pub mod scrollback;
 pub mod sftp_service;
+pub mod ssh_agent;
+pub mod ssh_config;
 pub mod ssh_service;
+pub mod telnet;
+pub mod terminal_output;
+#[cfg(test)]
+mod test_server;
+pub mod tunnel;
 
 pub use crypto_service::CryptoService;
 pub use database::DatabaseService;
@@ -0,0 +1,348 @@
+//! Proxy Dialer
+//!
+//! Opens TCP connections through SOCKS5 (RFC 1928, with RFC 1929
+//! username/password auth) or HTTP CONNECT proxies. The returned stream is
+//! positioned right after the proxy handshake and can carry the SSH transport.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::models::{ProxyConfig, ProxyType};
+
+/// Upper bound for the HTTP CONNECT response header
+const MAX_HTTP_RESPONSE_LENGTH: usize = 8192;
+
+/// Time allowed for the TCP connection to the proxy
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time allowed for the proxy to answer the handshake, including its own
+/// connection to the target
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Connect to `host:port` through the given proxy
+pub async fn connect_via_proxy(proxy: &ProxyConfig, host: &str, port: u16) -> Result<TcpStream> {
+    let connect = TcpStream::connect((proxy.host.as_str(), proxy.port));
+    let mut stream = match tokio::time::timeout(CONNECT_TIMEOUT, connect).await {
+        Ok(result) => result.map_err(|e| {
+            anyhow!(
+                "Failed to connect to proxy {}:{}: {}",
+                proxy.host,
+                proxy.port,
+                e
+            )
+        })?,
+        Err(_) => {
+            return Err(anyhow!(
+                "Connecting to proxy {}:{} timed out after {} s",
+                proxy.host,
+                proxy.port,
+                CONNECT_TIMEOUT.as_secs()
+            ))
+        }
+    };
+
+    let handshake = async {
+        match proxy.proxy_type {
+            ProxyType::Socks5 => socks5_handshake(&mut stream, proxy, host, port).await,
+            ProxyType::Http => http_connect_handshake(&mut stream, proxy, host, port).await,
+        }
+    };
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake)
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "Proxy {}:{} did not complete the handshake within {} s",
+                proxy.host,
+                proxy.port,
+                HANDSHAKE_TIMEOUT.as_secs()
+            )
+        })??;
+    Ok(stream)
+}
+
+async fn socks5_handshake(
+    stream: &mut TcpStream,
+    proxy: &ProxyConfig,
+    host: &str,
+    port: u16,
+) -> Result<()> {
+    let credentials = proxy
+        .username
+        .as_deref()
+        .map(|username| (username, proxy.password.as_deref().unwrap_or_default()));
+
+    // Greeting: offer "no auth", plus username/password when configured
+    if credentials.is_some() {
+        stream.write_all(&[0x05, 0x02, 0x00, 0x02]).await?;
+    } else {
+        stream.write_all(&[0x05, 0x01, 0x00]).await?;
+    }
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != 0x05 {
+        return Err(anyhow!("Proxy is not a SOCKS5 server"));
+    }
+    match (reply[1], credentials) {
+        (0x00, _) => {}
+        (0x02, Some((username, password))) => {
+            if username.len() > 255 || password.len() > 255 {
+                return Err(anyhow!("SOCKS5 username or password is too long"));
+            }
+            let mut request = vec![0x01, username.len() as u8];
+            request.extend_from_slice(username.as_bytes());
+            request.push(password.len() as u8);
+            request.extend_from_slice(password.as_bytes());
+            stream.write_all(&request).await?;
+
+            let mut status = [0u8; 2];
+            stream.read_exact(&mut status).await?;
+            if status[1] != 0x00 {
+                return Err(anyhow!("SOCKS5 proxy authentication failed"));
+            }
+        }
+        (0x02, None) => return Err(anyhow!("SOCKS5 proxy requires authentication")),
+        _ => return Err(anyhow!("SOCKS5 proxy rejected all authentication methods")),
+    }
+
+    // CONNECT request; hostnames are resolved by the proxy
+    let mut request = vec![0x05, 0x01, 0x00];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(0x01);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(0x04);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            if host.len() > 255 {
+                return Err(anyhow!("Host name is too long for SOCKS5"));
+            }
+            request.push(0x03);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    if header[1] != 0x00 {
+        return Err(anyhow!(
+            "SOCKS5 proxy could not connect to {}:{}: {}",
+            host,
+            port,
+            socks5_error(header[1])
+        ));
+    }
+
+    // Skip the bound address
+    let address_length = match header[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => {
+            let mut length = [0u8; 1];
+            stream.read_exact(&mut length).await?;
+            length[0] as usize
+        }
+        _ => return Err(anyhow!("Invalid SOCKS5 reply")),
+    };
+    let mut bound = vec![0u8; address_length + 2];
+    stream.read_exact(&mut bound).await?;
+
+    Ok(())
+}
+
+fn socks5_error(code: u8) -> &'static str {
+    match code {
+        0x01 => "general failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+async fn http_connect_handshake(
+    stream: &mut TcpStream,
+    proxy: &ProxyConfig,
+    host: &str,
+    port: u16,
+) -> Result<()> {
+    let authority = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+    if let Some(username) = &proxy.username {
+        let token = BASE64.encode(format!(
+            "{}:{}",
+            username,
+            proxy.password.as_deref().unwrap_or_default()
+        ));
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // Read the response header byte by byte so no tunnelled data is consumed
+    let mut response = Vec::new();
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_HTTP_RESPONSE_LENGTH {
+            return Err(anyhow!("HTTP proxy response is too long"));
+        }
+        if stream.read(&mut byte).await? == 0 {
+            return Err(anyhow!("HTTP proxy closed the connection"));
+        }
+        response.push(byte[0]);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+    match status {
+        "200" => Ok(()),
+        "407" => Err(anyhow!("HTTP proxy authentication failed")),
+        _ => Err(anyhow!("HTTP proxy refused CONNECT: {}", status_line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn proxy_config(proxy_type: ProxyType, port: u16, auth: bool) -> ProxyConfig {
+        ProxyConfig {
+            proxy_type,
+            host: "127.0.0.1".to_string(),
+            port,
+            username: auth.then(|| "user".to_string()),
+            password: auth.then(|| "secret".to_string()),
+        }
+    }
+
+    /// Minimal SOCKS5 stand-in that checks the handshake and then echoes
+    async fn mock_socks5(listener: TcpListener) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut greeting = [0u8; 4];
+        stream.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(greeting, [0x05, 0x02, 0x00, 0x02]);
+        stream.write_all(&[0x05, 0x02]).await.unwrap();
+
+        let mut auth = [0u8; 2 + 4 + 1 + 6];
+        stream.read_exact(&mut auth).await.unwrap();
+        assert_eq!(&auth[2..6], b"user");
+        assert_eq!(&auth[7..], b"secret");
+        stream.write_all(&[0x01, 0x00]).await.unwrap();
+
+        let mut connect = [0u8; 5];
+        stream.read_exact(&mut connect).await.unwrap();
+        assert_eq!(connect[3], 0x03);
+        let mut target = vec![0u8; connect[4] as usize + 2];
+        stream.read_exact(&mut target).await.unwrap();
+        assert_eq!(&target[..connect[4] as usize], b"ssh.internal");
+        assert_eq!(&target[connect[4] as usize..], &22u16.to_be_bytes());
+        stream
+            .write_all(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0, 22])
+            .await
+            .unwrap();
+
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        stream.write_all(&buf).await.unwrap();
+    }
+
+    /// Minimal HTTP CONNECT stand-in that checks basic auth and then echoes
+    async fn mock_http(listener: TcpListener, expected_auth: &'static str) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut byte = [0u8; 1];
+        while !request.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).await.unwrap();
+            request.push(byte[0]);
+        }
+        let request = String::from_utf8(request).unwrap();
+        assert!(request.starts_with("CONNECT ssh.internal:2222 HTTP/1.1\r\n"));
+        if request.contains(expected_auth) {
+            stream
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\nSSH-")
+                .await
+                .unwrap();
+        } else {
+            stream
+                .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socks5_with_auth() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(mock_socks5(listener));
+
+        let proxy = proxy_config(ProxyType::Socks5, port, true);
+        let mut stream = connect_via_proxy(&proxy, "ssh.internal", 22).await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_connect_with_basic_auth() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // base64("user:secret")
+        let server = tokio::spawn(mock_http(
+            listener,
+            "Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=",
+        ));
+
+        let proxy = proxy_config(ProxyType::Http, port, true);
+        let mut stream = connect_via_proxy(&proxy, "ssh.internal", 2222)
+            .await
+            .unwrap();
+        // Data after the header belongs to the tunnel
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"SSH-");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_connect_auth_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(mock_http(
+            listener,
+            "Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=",
+        ));
+
+        let proxy = proxy_config(ProxyType::Http, port, false);
+        let result = connect_via_proxy(&proxy, "ssh.internal", 2222).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("authentication failed"));
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,504 @@
+//! Session Recording
+//!
+//! Records terminal sessions as asciicast v2 files
+//! (https://docs.asciinema.org/manual/asciicast/v2/): a JSON header line
+//! followed by one `[time, code, data]` line per output ("o"), input ("i")
+//! or resize ("r") event. Files are written by a background task so that the
+//! SSH data path never blocks on disk I/O.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::models::{RecordingInfo, ReplayFrame, TerminalSize};
+
+/// Longest pause reproduced during replay
+const REPLAY_IDLE_LIMIT: f64 = 3.0;
+
+/// Tail of the file scanned for the last event when listing recordings
+const TAIL_SCAN_LENGTH: u64 = 64 * 1024;
+
+/// asciicast v2 header line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CastHeader {
+    version: u8,
+    width: u32,
+    height: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    env: Option<HashMap<String, String>>,
+}
+
+/// asciicast v2 event line
+type CastEvent = (f64, String, String);
+
+enum EventKind {
+    Output,
+    Input,
+    Resize,
+}
+
+struct RecorderEvent {
+    time: f64,
+    kind: EventKind,
+    data: Vec<u8>,
+}
+
+/// Recording in progress for one session
+struct Recorder {
+    recording_id: String,
+    started: Instant,
+    record_input: bool,
+    tx: mpsc::UnboundedSender<RecorderEvent>,
+}
+
+impl Recorder {
+    fn send(&self, kind: EventKind, data: Vec<u8>) {
+        let _ = self.tx.send(RecorderEvent {
+            time: self.started.elapsed().as_secs_f64(),
+            kind,
+            data,
+        });
+    }
+}
+
+/// Manages recordings in progress and stored recording files
+#[derive(Default)]
+pub struct RecordingManager {
+    dir: parking_lot::RwLock<Option<PathBuf>>,
+    /// session_id -> recorder
+    active: parking_lot::Mutex<HashMap<String, Recorder>>,
+    /// replay_id -> cancellation token
+    replays: Arc<parking_lot::Mutex<HashMap<String, CancellationToken>>>,
+}
+
+impl RecordingManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the directory recordings are stored in
+    pub fn set_dir(&self, dir: PathBuf) -> Result<()> {
+        std::fs::create_dir_all(&dir)?;
+        *self.dir.write() = Some(dir);
+        Ok(())
+    }
+
+    fn dir(&self) -> Result<PathBuf> {
+        self.dir
+            .read()
+            .clone()
+            .ok_or_else(|| anyhow!("Recording storage is not available"))
+    }
+
+    fn path_of(&self, recording_id: &str) -> Result<PathBuf> {
+        // Recording IDs are UUIDs, which also keeps them from escaping the directory
+        Uuid::parse_str(recording_id).map_err(|_| anyhow!("Invalid recording ID"))?;
+        Ok(self.dir()?.join(format!("{}.cast", recording_id)))
+    }
+
+    /// Start recording a session
+    pub fn start(
+        &self,
+        session_id: &str,
+        title: String,
+        size: TerminalSize,
+        term: &str,
+        record_input: bool,
+    ) -> Result<RecordingInfo> {
+        if self.active.lock().contains_key(session_id) {
+            return Err(anyhow!("Session is already being recorded"));
+        }
+
+        let recording_id = Uuid::new_v4().to_string();
+        let path = self.path_of(&recording_id)?;
+        let now = Utc::now();
+        let header = CastHeader {
+            version: 2,
+            width: size.cols,
+            height: size.rows,
+            timestamp: Some(now.timestamp()),
+            title: Some(title.clone()),
+            env: Some(HashMap::from([("TERM".to_string(), term.to_string())])),
+        };
+
+        let mut file = std::fs::File::create(&path)?;
+        writeln!(file, "{}", serde_json::to_string(&header)?)?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(write_events(tokio::fs::File::from_std(file), rx));
+
+        self.active.lock().insert(
+            session_id.to_string(),
+            Recorder {
+                recording_id: recording_id.clone(),
+                started: Instant::now(),
+                record_input,
+                tx,
+            },
+        );
+
+        Ok(RecordingInfo {
+            recording_id,
+            title: Some(title),
+            width: size.cols,
+            height: size.rows,
+            started_at: Some(now.to_rfc3339()),
+            duration: 0.0,
+            size: 0,
+            active: true,
+        })
+    }
+
+    /// Stop recording a session; returns false if it was not being recorded
+    pub fn stop(&self, session_id: &str) -> bool {
+        // Dropping the sender lets the writer flush and finish
+        self.active.lock().remove(session_id).is_some()
+    }
+
+    /// Record output received from the session
+    pub fn output(&self, session_id: &str, data: &[u8]) {
+        if let Some(recorder) = self.active.lock().get(session_id) {
+            recorder.send(EventKind::Output, data.to_vec());
+        }
+    }
+
+    /// Record input sent to the session, if the recording opted in
+    pub fn input(&self, session_id: &str, data: &[u8]) {
+        if let Some(recorder) = self.active.lock().get(session_id) {
+            if recorder.record_input {
+                recorder.send(EventKind::Input, data.to_vec());
+            }
+        }
+    }
+
+    /// Record a terminal resize
+    pub fn resize(&self, session_id: &str, size: TerminalSize) {
+        if let Some(recorder) = self.active.lock().get(session_id) {
+            recorder.send(
+                EventKind::Resize,
+                format!("{}x{}", size.cols, size.rows).into_bytes(),
+            );
+        }
+    }
+
+    /// List stored recordings, newest first
+    pub fn list(&self) -> Result<Vec<RecordingInfo>> {
+        let active: Vec<String> = self
+            .active
+            .lock()
+            .values()
+            .map(|r| r.recording_id.clone())
+            .collect();
+
+        let mut recordings = Vec::new();
+        for entry in std::fs::read_dir(self.dir()?)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("cast") {
+                continue;
+            }
+            let Some(recording_id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            // Skip files that are not readable recordings
+            if let Ok(mut info) = read_info(&path, recording_id) {
+                info.active = active.contains(&info.recording_id);
+                recordings.push(info);
+            }
+        }
+        recordings.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        Ok(recordings)
+    }
+
+    /// Delete a recording that is no longer being written
+    pub fn delete(&self, recording_id: &str) -> Result<()> {
+        if self
+            .active
+            .lock()
+            .values()
+            .any(|r| r.recording_id == recording_id)
+        {
+            return Err(anyhow!("Recording is still in progress"));
+        }
+        std::fs::remove_file(self.path_of(recording_id)?)?;
+        Ok(())
+    }
+
+    /// Replay a recording in real time (scaled by `speed`), sending output and
+    /// resize events to `tx`. Returns the replay ID used to stop it.
+    pub fn replay(
+        &self,
+        recording_id: &str,
+        speed: f64,
+        tx: mpsc::UnboundedSender<ReplayFrame>,
+    ) -> Result<String> {
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(anyhow!("Replay speed must be positive"));
+        }
+        let (_, events) = read_cast(&self.path_of(recording_id)?)?;
+
+        let replay_id = Uuid::new_v4().to_string();
+        let cancel = CancellationToken::new();
+        self.replays
+            .lock()
+            .insert(replay_id.clone(), cancel.clone());
+
+        let replays = self.replays.clone();
+        let id = replay_id.clone();
+        tokio::spawn(async move {
+            let mut previous = 0.0;
+            for (time, kind, data) in events {
+                if kind != "o" && kind != "r" {
+                    continue;
+                }
+                let delay = (time - previous).clamp(0.0, REPLAY_IDLE_LIMIT) / speed;
+                previous = time;
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tokio::time::sleep(Duration::from_secs_f64(delay)) => {}
+                }
+                let frame = ReplayFrame {
+                    replay_id: id.clone(),
+                    time,
+                    kind,
+                    data,
+                };
+                if tx.send(frame).is_err() {
+                    break;
+                }
+            }
+            replays.lock().remove(&id);
+        });
+
+        Ok(replay_id)
+    }
+
+    /// Stop a running replay
+    pub fn stop_replay(&self, replay_id: &str) -> Result<()> {
+        let cancel = self
+            .replays
+            .lock()
+            .remove(replay_id)
+            .ok_or_else(|| anyhow!("Replay not found"))?;
+        cancel.cancel();
+        Ok(())
+    }
+}
+
+/// Append events to the cast file until the recorder is dropped
+async fn write_events(file: tokio::fs::File, mut rx: mpsc::UnboundedReceiver<RecorderEvent>) {
+    let mut writer = tokio::io::BufWriter::new(file);
+    let mut output = Utf8Stream::default();
+    let mut input = Utf8Stream::default();
+
+    while let Some(event) = rx.recv().await {
+        let mut next = Some(event);
+        // Write everything that is queued, then flush once
+        while let Some(event) = next {
+            let (code, data) = match event.kind {
+                EventKind::Output => ("o", output.decode(&event.data)),
+                EventKind::Input => ("i", input.decode(&event.data)),
+                EventKind::Resize => ("r", String::from_utf8_lossy(&event.data).into_owned()),
+            };
+            if !data.is_empty() {
+                let time = (event.time * 1_000_000.0).round() / 1_000_000.0;
+                let line = serde_json::json!([time, code, data]).to_string() + "\n";
+                if let Err(e) = writer.write_all(line.as_bytes()).await {
+                    log::error!("Failed to write recording: {}", e);
+                    return;
+                }
+            }
+            next = rx.try_recv().ok();
+        }
+        if let Err(e) = writer.flush().await {
+            log::error!("Failed to write recording: {}", e);
+            return;
+        }
+    }
+}
+
+/// Incremental UTF-8 decoder: multi-byte characters split across chunks are
+/// held back until complete, invalid bytes become U+FFFD
+#[derive(Default)]
+pub(crate) struct Utf8Stream {
+    pending: Vec<u8>,
+}
+
+impl Utf8Stream {
+    pub(crate) fn decode(&mut self, data: &[u8]) -> String {
+        self.pending.extend_from_slice(data);
+        let mut text = String::new();
+        let mut rest: &[u8] = &self.pending;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    // `valid_up_to` marks a valid prefix, so this cannot fail
+                    text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        // Incomplete sequence at the end, wait for more data
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.pending = rest.to_vec();
+        text
+    }
+}
+
+fn read_cast(path: &Path) -> Result<(CastHeader, Vec<CastEvent>)> {
+    let reader = BufReader::new(std::fs::File::open(path)?);
+    let mut lines = reader.lines();
+    let header: CastHeader = serde_json::from_str(
+        &lines
+            .next()
+            .ok_or_else(|| anyhow!("Recording is empty"))??,
+    )?;
+    if header.version != 2 {
+        return Err(anyhow!("Unsupported asciicast version {}", header.version));
+    }
+
+    let mut events = Vec::new();
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // A recording cut off mid-line (e.g. on crash) ends at the last complete event
+        match serde_json::from_str::<CastEvent>(&line) {
+            Ok(event) => events.push(event),
+            Err(_) => break,
+        }
+    }
+    Ok((header, events))
+}
+
+fn read_info(path: &Path, recording_id: &str) -> Result<RecordingInfo> {
+    let mut file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+
+    let mut header_line = String::new();
+    BufReader::new(&mut file).read_line(&mut header_line)?;
+    let header: CastHeader = serde_json::from_str(&header_line)?;
+
+    // The duration is the time of the last complete event
+    let start = size.saturating_sub(TAIL_SCAN_LENGTH);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    let duration = String::from_utf8_lossy(&tail)
+        .lines()
+        .rev()
+        .find_map(|line| serde_json::from_str::<CastEvent>(line).ok())
+        .map(|(time, _, _)| time)
+        .unwrap_or(0.0);
+
+    Ok(RecordingInfo {
+        recording_id: recording_id.to_string(),
+        title: header.title,
+        width: header.width,
+        height: header.height,
+        started_at: header
+            .timestamp
+            .and_then(|t| Utc.timestamp_opt(t, 0).single())
+            .map(|t| t.to_rfc3339()),
+        duration,
+        size,
+        active: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_stream_joins_split_characters() {
+        let mut stream = Utf8Stream::default();
+        let bytes = "héllo 世界".as_bytes();
+        // Split inside "é" and inside "世"
+        assert_eq!(stream.decode(&bytes[..2]), "h");
+        assert_eq!(stream.decode(&bytes[2..8]), "éllo ");
+        assert_eq!(stream.decode(&bytes[8..]), "世界");
+        assert_eq!(stream.decode(b"\xffok"), "\u{FFFD}ok");
+    }
+
+    #[tokio::test]
+    async fn test_record_and_list() {
+        let dir = std::env::temp_dir().join(format!("opsbot-rec-{}", Uuid::new_v4()));
+        let manager = RecordingManager::new();
+        manager.set_dir(dir.clone()).unwrap();
+
+        let size = TerminalSize {
+            cols: 120,
+            rows: 40,
+        };
+        let info = manager
+            .start("session", "root@db1".to_string(), size, "vt100", false)
+            .unwrap();
+        manager.output("session", "$ ls\r\n".as_bytes());
+        manager.input("session", b"secret");
+        manager.resize(
+            "session",
+            TerminalSize {
+                cols: 100,
+                rows: 30,
+            },
+        );
+        assert!(manager.stop("session"));
+
+        // Wait for the writer to drain
+        let path = manager.path_of(&info.recording_id).unwrap();
+        let header = read_cast(&path).unwrap().0;
+        assert_eq!(header.env.unwrap()["TERM"], "vt100");
+        let mut events = Vec::new();
+        for _ in 0..50 {
+            events = read_cast(&path).unwrap().1;
+            if events.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].1, "o");
+        assert_eq!(events[0].2, "$ ls\r\n");
+        assert_eq!(events[1].1, "r");
+        assert_eq!(events[1].2, "100x30");
+
+        let listed = manager.list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].title.as_deref(), Some("root@db1"));
+        assert_eq!(listed[0].width, 120);
+        assert!(!listed[0].active);
+
+        manager.delete(&info.recording_id).unwrap();
+        assert!(manager.list().unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,803 @@
+//! Terminal Scrollback
+//!
+//! Keeps the latest output of each terminal session on the backend. The raw
+//! bytes let a tab that reattaches to a session (e.g. after a reload) restore
+//! its screen. The output is also kept as lines of text, with the escape
+//! sequences interpreted as far as they affect a line (colors, carriage
+//! returns, erasing) and full-screen programs left out, which can be
+//! searched, read in ranges and exported as plain text or HTML.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use regex::RegexBuilder;
+
+use crate::models::{
+    ScrollbackExportFormat, ScrollbackLines, ScrollbackMatch, ScrollbackSearchRequest,
+    ScrollbackSnapshot, DEFAULT_SCROLLBACK_LINES,
+};
+
+/// Raw output kept per session
+pub const DEFAULT_SCROLLBACK: usize = 1024 * 1024;
+
+/// Matches returned by a search unless asked otherwise
+const DEFAULT_MAX_RESULTS: usize = 1000;
+
+/// Lines longer than this are wrapped
+const MAX_LINE_LENGTH: usize = 16 * 1024;
+
+/// Longest parameter list of an escape sequence kept
+const MAX_PARAMS_LENGTH: usize = 64;
+
+/// Colors of the exported page where the output sets none
+const DEFAULT_FOREGROUND: &str = "#d4d4d4";
+const DEFAULT_BACKGROUND: &str = "#1e1e1e";
+
+/// The 16 basic colors (xterm's defaults)
+const PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Scrollback shared between a session's output stream and the service
+pub type SharedScrollback = Arc<parking_lot::Mutex<Scrollback>>;
+
+/// Latest output of a terminal
+pub struct Scrollback {
+    buffer: VecDeque<u8>,
+    capacity: usize,
+    /// Length of the output so far; the buffer holds the bytes just before
+    end: u64,
+    transcript: Transcript,
+}
+
+impl Scrollback {
+    /// Scrollback of `capacity` bytes of raw output and the default number
+    /// of lines of text
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: VecDeque::with_capacity(capacity.min(64 * 1024)),
+            capacity,
+            end: 0,
+            transcript: Transcript::new(DEFAULT_SCROLLBACK_LINES),
+        }
+    }
+
+    pub fn shared(capacity: usize) -> SharedScrollback {
+        Arc::new(parking_lot::Mutex::new(Self::new(capacity)))
+    }
+
+    /// Change the number of lines of text kept, dropping the oldest ones
+    /// past it
+    pub fn set_max_lines(&mut self, max_lines: usize) {
+        self.transcript.max_lines = max_lines;
+        self.transcript.trim();
+    }
+
+    /// Append output, dropping the oldest bytes past the capacity
+    pub fn push(&mut self, data: &[u8]) {
+        self.end += data.len() as u64;
+        self.transcript.feed(data);
+
+        let data = &data[data.len().saturating_sub(self.capacity)..];
+        let overflow = (self.buffer.len() + data.len()).saturating_sub(self.capacity);
+        self.buffer.drain(..overflow);
+        self.buffer.extend(data);
+    }
+
+    /// Position of the first buffered byte in the output
+    pub fn offset(&self) -> u64 {
+        self.end - self.buffer.len() as u64
+    }
+
+    /// Buffered output. Where the oldest bytes have been dropped it starts at
+    /// the first whole UTF-8 character.
+    pub fn snapshot(&self) -> ScrollbackSnapshot {
+        let skip = self
+            .buffer
+            .iter()
+            .take(3)
+            .take_while(|&&byte| byte & 0xc0 == 0x80)
+            .count();
+        let data: Vec<u8> = self.buffer.iter().skip(skip).copied().collect();
+        ScrollbackSnapshot {
+            data: BASE64.encode(data),
+            offset: self.offset() + skip as u64,
+            end: self.end,
+        }
+    }
+
+    /// Find a text or regular expression in the lines kept, oldest first
+    pub fn search(&self, request: &ScrollbackSearchRequest) -> Result<Vec<ScrollbackMatch>> {
+        if request.query.is_empty() {
+            return Err(anyhow!("Search text is empty"));
+        }
+        let pattern = if request.regex {
+            request.query.clone()
+        } else {
+            regex::escape(&request.query)
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!request.case_sensitive)
+            .build()
+            .map_err(|e| anyhow!("Invalid regular expression: {}", e))?;
+        let max_results = request.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+
+        let mut matches = Vec::new();
+        for (number, line) in self.transcript.lines() {
+            for found in regex.find_iter(&line.text) {
+                // Patterns such as `a*` also match nothing between characters
+                if found.is_empty() {
+                    continue;
+                }
+                if matches.len() == max_results {
+                    return Ok(matches);
+                }
+                let start = line.text[..found.start()].chars().count();
+                matches.push(ScrollbackMatch {
+                    line: number,
+                    start,
+                    end: start + found.as_str().chars().count(),
+                    text: line.text.clone(),
+                });
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Up to `count` lines of text from line number `start` on. Lines no
+    /// longer kept are skipped.
+    pub fn lines(&self, start: u64, count: usize) -> ScrollbackLines {
+        let first_line = self.transcript.first_line;
+        let start = start.max(first_line);
+        let lines = self
+            .transcript
+            .lines()
+            .skip((start - first_line).try_into().unwrap_or(usize::MAX))
+            .take(count)
+            .map(|(_, line)| line.into_owned().text)
+            .collect();
+        ScrollbackLines {
+            start,
+            first_line,
+            total_lines: self.transcript.line_count(),
+            lines,
+        }
+    }
+
+    /// The lines kept as a file of the given format
+    pub fn export(&self, format: ScrollbackExportFormat, title: &str) -> String {
+        match format {
+            ScrollbackExportFormat::Text => {
+                let mut text = String::new();
+                for (_, line) in self.transcript.lines() {
+                    text.push_str(&line.text);
+                    text.push('\n');
+                }
+                text
+            }
+            ScrollbackExportFormat::Html => self.export_html(title),
+        }
+    }
+
+    fn export_html(&self, title: &str) -> String {
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>\nbody {{ margin: 0; background: {}; color: {}; }}\n\
+             pre {{ margin: 0; padding: 8px; white-space: pre-wrap; \
+             font: 13px Menlo, Consolas, \"DejaVu Sans Mono\", monospace; }}\n\
+             </style>\n</head>\n<body>\n<pre>",
+            escape_html(title),
+            DEFAULT_BACKGROUND,
+            DEFAULT_FOREGROUND
+        );
+        for (_, line) in self.transcript.lines() {
+            for (i, (start, style)) in line.runs.iter().enumerate() {
+                let end = line
+                    .runs
+                    .get(i + 1)
+                    .map(|(next, _)| *next)
+                    .unwrap_or(line.text.len());
+                let text = escape_html(&line.text[*start..end]);
+                if *style == Style::default() {
+                    html.push_str(&text);
+                } else {
+                    let _ = write!(html, "<span style=\"{}\">{}</span>", style.css(), text);
+                }
+            }
+            html.push('\n');
+        }
+        html.push_str("</pre>\n</body>\n</html>\n");
+        html
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Color {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    fn css(self) -> String {
+        let (r, g, b) = match self {
+            Color::Indexed(index @ 0..=15) => PALETTE[index as usize],
+            // 6x6x6 color cube
+            Color::Indexed(index @ 16..=231) => {
+                let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+                let index = index - 16;
+                (level(index / 36), level(index / 6 % 6), level(index % 6))
+            }
+            // Grayscale ramp
+            Color::Indexed(index) => {
+                let gray = 8 + (index - 232) * 10;
+                (gray, gray, gray)
+            }
+            Color::Rgb(r, g, b) => (r, g, b),
+        };
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+/// Text attributes set by SGR sequences
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Style {
+    foreground: Option<Color>,
+    background: Option<Color>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    inverse: bool,
+}
+
+impl Style {
+    /// Apply the parameters of an SGR (`CSI ... m`) sequence
+    fn apply(&mut self, params: &[Vec<u16>]) {
+        if params.is_empty() {
+            *self = Style::default();
+            return;
+        }
+        let mut i = 0;
+        while i < params.len() {
+            let code = params[i].first().copied().unwrap_or(0);
+            match code {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                30..=37 => self.foreground = Some(Color::Indexed((code - 30) as u8)),
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(Color::Indexed((code - 40) as u8)),
+                49 => self.background = None,
+                90..=97 => self.foreground = Some(Color::Indexed((code - 90 + 8) as u8)),
+                100..=107 => self.background = Some(Color::Indexed((code - 100 + 8) as u8)),
+                38 | 48 => {
+                    let color = if params[i].len() > 1 {
+                        // Colon form: 38:5:n, 38:2:r:g:b or 38:2:colorspace:r:g:b
+                        extended_color(&params[i][1..])
+                    } else {
+                        let rest: Vec<u16> = params[i + 1..]
+                            .iter()
+                            .map(|param| param.first().copied().unwrap_or(0))
+                            .take(4)
+                            .collect();
+                        i += match rest.first() {
+                            Some(5) => 2,
+                            Some(2) => 4,
+                            _ => 0,
+                        };
+                        extended_color(&rest)
+                    };
+                    if code == 38 {
+                        self.foreground = color;
+                    } else {
+                        self.background = color;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    fn css(&self) -> String {
+        let (mut foreground, mut background) = (
+            self.foreground.map(Color::css),
+            self.background.map(Color::css),
+        );
+        if self.inverse {
+            (foreground, background) = (
+                Some(background.unwrap_or_else(|| DEFAULT_BACKGROUND.to_string())),
+                Some(foreground.unwrap_or_else(|| DEFAULT_FOREGROUND.to_string())),
+            );
+        }
+        let mut css = Vec::new();
+        if let Some(color) = foreground {
+            css.push(format!("color: {}", color));
+        }
+        if let Some(color) = background {
+            css.push(format!("background-color: {}", color));
+        }
+        if self.bold {
+            css.push("font-weight: bold".to_string());
+        }
+        if self.dim {
+            css.push("opacity: 0.7".to_string());
+        }
+        if self.italic {
+            css.push("font-style: italic".to_string());
+        }
+        if self.underline {
+            css.push("text-decoration: underline".to_string());
+        }
+        css.join("; ")
+    }
+}
+
+/// Color of a `38`/`48` SGR parameter from the parameters after it
+fn extended_color(params: &[u16]) -> Option<Color> {
+    let byte = |value: u16| value.min(255) as u8;
+    match params {
+        [5, index, ..] => Some(Color::Indexed(byte(*index))),
+        [2, _, r, g, b] | [2, r, g, b] => Some(Color::Rgb(byte(*r), byte(*g), byte(*b))),
+        _ => None,
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Line of text with the style changes in it
+#[derive(Debug, Clone)]
+struct Line {
+    text: String,
+    /// Byte offset in `text` each style starts at, the first one at 0
+    runs: Vec<(usize, Style)>,
+}
+
+impl Line {
+    fn from_cells(cells: &[(char, Style)]) -> Self {
+        let mut text = String::new();
+        let mut runs: Vec<(usize, Style)> = Vec::new();
+        for &(c, style) in cells {
+            if runs.last().map(|(_, last)| *last) != Some(style) {
+                runs.push((text.len(), style));
+            }
+            text.push(c);
+        }
+        Self { text, runs }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseState {
+    Ground,
+    Escape,
+    /// Control sequence (`ESC [`), collecting parameters
+    Csi,
+    /// Operating system command or other string (`ESC ]`, `ESC P`...), up to
+    /// BEL or `ESC \`
+    Text,
+    TextEscape,
+    /// `ESC (` and the like, which take one more byte
+    Designate,
+}
+
+/// Output as lines of text
+struct Transcript {
+    lines: VecDeque<Line>,
+    max_lines: usize,
+    /// Number of the oldest line kept
+    first_line: u64,
+    /// Line being written
+    cells: Vec<(char, Style)>,
+    column: usize,
+    style: Style,
+    /// Full-screen programs draw on the alternate screen, which has no
+    /// scrollback
+    alternate_screen: bool,
+    state: ParseState,
+    params: String,
+    /// Start of a UTF-8 character split between reads
+    utf8: Vec<u8>,
+}
+
+impl Transcript {
+    fn new(max_lines: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            max_lines,
+            first_line: 0,
+            cells: Vec::new(),
+            column: 0,
+            style: Style::default(),
+            alternate_screen: false,
+            state: ParseState::Ground,
+            params: String::new(),
+            utf8: Vec::new(),
+        }
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        for &byte in data {
+            if byte >= 0x80 && self.state == ParseState::Ground {
+                self.feed_utf8(byte);
+                continue;
+            }
+            if !self.utf8.is_empty() {
+                self.utf8.clear();
+                self.print(char::REPLACEMENT_CHARACTER);
+            }
+            self.state = match self.state {
+                ParseState::Ground => {
+                    self.control(byte);
+                    if byte == 0x1b {
+                        ParseState::Escape
+                    } else {
+                        ParseState::Ground
+                    }
+                }
+                ParseState::Escape => match byte {
+                    b'[' => {
+                        self.params.clear();
+                        ParseState::Csi
+                    }
+                    b']' | b'P' | b'X' | b'^' | b'_' => ParseState::Text,
+                    b'(' | b')' | b'*' | b'+' | b'#' | b'%' => ParseState::Designate,
+                    _ => ParseState::Ground,
+                },
+                ParseState::Csi => match byte {
+                    0x40..=0x7e => {
+                        self.control_sequence(byte);
+                        ParseState::Ground
+                    }
+                    0x1b => ParseState::Escape,
+                    _ => {
+                        if self.params.len() < MAX_PARAMS_LENGTH {
+                            self.params.push(byte as char);
+                        }
+                        ParseState::Csi
+                    }
+                },
+                ParseState::Text => match byte {
+                    0x07 => ParseState::Ground,
+                    0x1b => ParseState::TextEscape,
+                    _ => ParseState::Text,
+                },
+                ParseState::TextEscape | ParseState::Designate => ParseState::Ground,
+            };
+        }
+    }
+
+    fn feed_utf8(&mut self, byte: u8) {
+        let continuation = byte & 0xc0 == 0x80;
+        if !continuation && !self.utf8.is_empty() {
+            self.utf8.clear();
+            self.print(char::REPLACEMENT_CHARACTER);
+        }
+        if continuation && self.utf8.is_empty() {
+            self.print(char::REPLACEMENT_CHARACTER);
+            return;
+        }
+        self.utf8.push(byte);
+        let length = match self.utf8[0] {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        if self.utf8.len() < length {
+            return;
+        }
+        let c = std::str::from_utf8(&self.utf8)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        self.utf8.clear();
+        self.print(c);
+    }
+
+    fn control(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.newline(),
+            b'\r' => self.column = 0,
+            0x08 => self.column = self.column.saturating_sub(1),
+            b'\t' => self.column = (self.column / 8 + 1) * 8,
+            0x20..=0x7e => self.print(byte as char),
+            _ => {}
+        }
+    }
+
+    fn control_sequence(&mut self, action: u8) {
+        // Private sequences (`CSI ? ...`) only matter for the alternate screen
+        if let Some(private) = self.params.strip_prefix('?') {
+            if matches!(action, b'h' | b'l')
+                && private
+                    .split(';')
+                    .any(|mode| matches!(mode, "47" | "1047" | "1049"))
+            {
+                self.alternate_screen = action == b'h';
+            }
+            return;
+        }
+        if self.alternate_screen {
+            return;
+        }
+        let params: Vec<Vec<u16>> = self
+            .params
+            .split(';')
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                param
+                    .split(':')
+                    .map(|value| value.parse().unwrap_or(0))
+                    .collect()
+            })
+            .collect();
+        let count = params
+            .first()
+            .and_then(|param| param.first())
+            .copied()
+            .unwrap_or(0) as usize;
+        match action {
+            b'm' => self.style.apply(&params),
+            // Erase in line
+            b'K' => match count {
+                0 => self.cells.truncate(self.column),
+                1 => {
+                    let end = (self.column + 1).min(self.cells.len());
+                    for cell in &mut self.cells[..end] {
+                        *cell = (' ', Style::default());
+                    }
+                }
+                _ => self.cells.clear(),
+            },
+            // Cursor forward, back and to a column
+            b'C' => self.column += count.max(1),
+            b'D' => self.column = self.column.saturating_sub(count.max(1)),
+            b'G' => self.column = count.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        if self.alternate_screen {
+            return;
+        }
+        if self.column >= MAX_LINE_LENGTH {
+            self.newline();
+        }
+        if self.column >= self.cells.len() {
+            self.cells.resize(self.column, (' ', Style::default()));
+            self.cells.push((c, self.style));
+        } else {
+            self.cells[self.column] = (c, self.style);
+        }
+        self.column += 1;
+    }
+
+    fn newline(&mut self) {
+        if self.alternate_screen {
+            return;
+        }
+        self.lines.push_back(Line::from_cells(&self.cells));
+        self.cells.clear();
+        self.column = 0;
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.lines.len() > self.max_lines {
+            self.lines.pop_front();
+            self.first_line += 1;
+        }
+    }
+
+    /// Number of lines so far, counting the one being written
+    fn line_count(&self) -> u64 {
+        self.first_line + self.lines.len() as u64 + u64::from(!self.cells.is_empty())
+    }
+
+    /// Lines kept with their numbers, the one being written last
+    fn lines(&self) -> impl Iterator<Item = (u64, Cow<'_, Line>)> + '_ {
+        let current = (!self.cells.is_empty()).then(|| Cow::Owned(Line::from_cells(&self.cells)));
+        self.lines
+            .iter()
+            .map(Cow::Borrowed)
+            .chain(current)
+            .enumerate()
+            .map(|(i, line)| (self.first_line + i as u64, line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(snapshot: &ScrollbackSnapshot) -> Vec<u8> {
+        BASE64.decode(&snapshot.data).unwrap()
+    }
+
+    fn search(scrollback: &Scrollback, query: &str, regex: bool) -> Result<Vec<ScrollbackMatch>> {
+        scrollback.search(&ScrollbackSearchRequest {
+            session_id: String::new(),
+            query: query.to_string(),
+            regex,
+            case_sensitive: false,
+            max_results: None,
+        })
+    }
+
+    #[test]
+    fn test_scrollback() {
+        let mut scrollback = Scrollback::new(8);
+        scrollback.push(b"hello ");
+        let snapshot = scrollback.snapshot();
+        assert_eq!(contents(&snapshot), b"hello ");
+        assert_eq!((snapshot.offset, snapshot.end), (0, 6));
+
+        // The oldest bytes make room for new ones
+        scrollback.push(b"world");
+        let snapshot = scrollback.snapshot();
+        assert_eq!(contents(&snapshot), b"lo world");
+        assert_eq!((snapshot.offset, snapshot.end), (3, 11));
+
+        // Only the tail of output longer than the buffer is kept
+        scrollback.push(b"0123456789");
+        let snapshot = scrollback.snapshot();
+        assert_eq!(contents(&snapshot), b"23456789");
+        assert_eq!((snapshot.offset, snapshot.end), (13, 21));
+
+        // A character cut in half at the start is left out
+        scrollback.push("→→→".as_bytes());
+        let snapshot = scrollback.snapshot();
+        assert_eq!(contents(&snapshot), "→→".as_bytes());
+        assert_eq!((snapshot.offset, snapshot.end), (24, 30));
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut scrollback = Scrollback::new(DEFAULT_SCROLLBACK);
+        // Colors, a window title, a progress bar redrawn in place and a
+        // character and sequence split between reads
+        scrollback.push(b"\x1b]0;user@host: ~\x07$ ls\r\n\x1b[01;34mbin\x1b[0m  notes.txt\r\n");
+        scrollback.push(b"Downloading 10%\rDownloading 100%\r\n\xe2\x9c");
+        scrollback.push(b"\x93 done\x1b[");
+        scrollback.push(b"32m ok\x1b[0m\r\nwarn\x08\x08\x08\x08error\r\nab\tc\r\n");
+        // A full-screen program leaves nothing behind
+        scrollback.push(b"\x1b[?1049h\x1b[H\x1b[2Jtop - 10:00\r\n\x1b[?1049l");
+        scrollback.push(b"old text\r\x1b[Knew\r\n$ ");
+
+        let lines = scrollback.lines(0, 100);
+        assert_eq!(
+            lines.lines,
+            [
+                "$ ls",
+                "bin  notes.txt",
+                "Downloading 100%",
+                "✓ done ok",
+                "error",
+                "ab      c",
+                "new",
+                "$ ",
+            ]
+        );
+        assert_eq!(
+            (lines.start, lines.first_line, lines.total_lines),
+            (0, 0, 8)
+        );
+
+        // Ranges, and the oldest lines dropped past the limit
+        assert_eq!(
+            scrollback.lines(2, 2).lines,
+            ["Downloading 100%", "✓ done ok"]
+        );
+        scrollback.set_max_lines(3);
+        let lines = scrollback.lines(0, 2);
+        assert_eq!(lines.lines, ["error", "ab      c"]);
+        assert_eq!(
+            (lines.start, lines.first_line, lines.total_lines),
+            (4, 4, 8)
+        );
+    }
+
+    #[test]
+    fn test_search() {
+        let mut scrollback = Scrollback::new(DEFAULT_SCROLLBACK);
+        scrollback.push(
+            "Überprüfung: OK\r\nERROR disk full\r\nerror: retry \x1b[31mError\x1b[0m\r\n"
+                .as_bytes(),
+        );
+
+        let matches = search(&scrollback, "error", false).unwrap();
+        let found: Vec<(u64, usize, usize)> =
+            matches.iter().map(|m| (m.line, m.start, m.end)).collect();
+        assert_eq!(found, [(1, 0, 5), (2, 0, 5), (2, 13, 18)]);
+        assert_eq!(matches[2].text, "error: retry Error");
+
+        // Offsets count characters, not bytes
+        let matches = search(&scrollback, r"\bOK$", true).unwrap();
+        assert_eq!(
+            (matches[0].line, matches[0].start, matches[0].end),
+            (0, 13, 15)
+        );
+
+        // Plain text is not a pattern
+        assert!(search(&scrollback, "disk.full", false).unwrap().is_empty());
+        assert_eq!(search(&scrollback, "disk.full", true).unwrap().len(), 1);
+        assert!(search(&scrollback, "x*", true).unwrap().is_empty());
+        assert!(search(&scrollback, "(", true).is_err());
+        assert!(search(&scrollback, "", false).is_err());
+
+        let matches = scrollback
+            .search(&ScrollbackSearchRequest {
+                session_id: String::new(),
+                query: "Error".to_string(),
+                regex: false,
+                case_sensitive: true,
+                max_results: Some(1),
+            })
+            .unwrap();
+        assert_eq!((matches.len(), matches[0].start), (1, 13));
+    }
+
+    #[test]
+    fn test_export() {
+        let mut scrollback = Scrollback::new(DEFAULT_SCROLLBACK);
+        scrollback
+            .push(b"a < b\r\n\x1b[1;38;5;196mred\x1b[0m \x1b[38;2;0;128;255;7mblue\x1b[m\r\n");
+
+        let text = scrollback.export(ScrollbackExportFormat::Text, "session");
+        assert_eq!(text, "a < b\nred blue\n");
+
+        let html = scrollback.export(ScrollbackExportFormat::Html, "root@<host>");
+        assert!(html.contains("<title>root@&lt;host&gt;</title>"));
+        assert!(html.contains("a &lt; b\n"));
+        assert!(html.contains("<span style=\"color: #ff0000; font-weight: bold\">red</span> "));
+        assert!(html.contains(&format!(
+            "<span style=\"color: {}; background-color: #0080ff\">blue</span>",
+            DEFAULT_BACKGROUND
+        )));
+    }
+}
@@ -0,0 +1,169 @@
+//! SSH Agent Support
+//!
+//! Authenticates with identities held by a running ssh-agent (`SSH_AUTH_SOCK`
+//! on Unix, the OpenSSH named pipe on Windows) and bridges forwarded agent
+//! channels from the server back to the local agent.
+
+use anyhow::{anyhow, Result};
+use russh::client;
+use russh::Channel;
+use russh_keys::agent::client::AgentClient;
+use russh_keys::key::PublicKey;
+
+use crate::services::ssh_service::SshClientHandler;
+
+/// Connection to the local agent. A concrete stream type rather than a boxed
+/// `AgentStream`, which would keep `authenticate` from being `Send`.
+#[cfg(unix)]
+pub type AgentConnection = tokio::net::UnixStream;
+
+/// Connection to the local agent
+#[cfg(windows)]
+pub type AgentConnection = tokio::net::windows::named_pipe::NamedPipeClient;
+
+/// Named pipe used by the Windows OpenSSH agent service
+#[cfg(windows)]
+const WINDOWS_AGENT_PIPE: &str = r"\\.\pipe\openssh-ssh-agent";
+
+/// Open a raw connection to the local ssh-agent
+#[cfg(unix)]
+pub async fn connect_agent() -> Result<AgentConnection> {
+    let path = std::env::var_os("SSH_AUTH_SOCK")
+        .ok_or_else(|| anyhow!("SSH agent is not available (SSH_AUTH_SOCK is not set)"))?;
+    connect_agent_at(std::path::Path::new(&path)).await
+}
+
+/// Open a raw connection to the agent listening on `path`
+#[cfg(unix)]
+pub async fn connect_agent_at(path: &std::path::Path) -> Result<AgentConnection> {
+    let stream = tokio::net::UnixStream::connect(path).await.map_err(|e| {
+        anyhow!(
+            "Failed to connect to SSH agent at {}: {}",
+            path.display(),
+            e
+        )
+    })?;
+    Ok(stream)
+}
+
+/// Open a raw connection to the local ssh-agent
+#[cfg(windows)]
+pub async fn connect_agent() -> Result<AgentConnection> {
+    let stream = tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(WINDOWS_AGENT_PIPE)
+        .map_err(|e| anyhow!("Failed to connect to SSH agent: {}", e))?;
+    Ok(stream)
+}
+
+/// List the public keys held by the agent
+pub async fn list_identities(agent: &mut AgentClient<AgentConnection>) -> Result<Vec<PublicKey>> {
+    agent
+        .request_identities()
+        .await
+        .map_err(|e| anyhow!("Failed to list SSH agent identities: {}", e))
+}
+
+/// Try each agent identity in turn until the server accepts one
+pub async fn authenticate(
+    handle: &mut client::Handle<SshClientHandler>,
+    username: &str,
+) -> Result<bool> {
+    let mut agent = AgentClient::connect(connect_agent().await?);
+    let identities = list_identities(&mut agent).await?;
+    if identities.is_empty() {
+        return Err(anyhow!("SSH agent has no identities"));
+    }
+
+    for key in identities {
+        let (returned, result) = handle.authenticate_future(username, key, agent).await;
+        agent = returned;
+        if result? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Relay a forwarded agent channel to the local agent until either side closes
+pub async fn forward_channel(channel: Channel<client::Msg>) -> Result<()> {
+    let mut agent = connect_agent().await?;
+    let mut stream = channel.into_stream();
+    tokio::io::copy_bidirectional(&mut stream, &mut agent).await?;
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use russh_keys::PublicKeyBase64;
+    use std::process::{Child, Command};
+    use std::time::Duration;
+
+    /// Throwaway ssh-agent bound to a socket in a temp directory
+    struct TestAgent {
+        child: Child,
+        dir: std::path::PathBuf,
+    }
+
+    impl TestAgent {
+        fn socket(&self) -> std::path::PathBuf {
+            self.dir.join("agent.sock")
+        }
+    }
+
+    impl Drop for TestAgent {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    async fn spawn_agent() -> TestAgent {
+        let dir = std::env::temp_dir().join(format!("opsbot-agent-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let child = Command::new("ssh-agent")
+            .arg("-D")
+            .arg("-a")
+            .arg(dir.join("agent.sock"))
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .expect("ssh-agent must be installed");
+        let agent = TestAgent { child, dir };
+        for _ in 0..50 {
+            if agent.socket().exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        agent
+    }
+
+    #[tokio::test]
+    async fn test_list_identities_from_agent() {
+        let agent = spawn_agent().await;
+
+        let key_path = agent.dir.join("id_ed25519");
+        let status = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+            .arg(&key_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let status = Command::new("ssh-add")
+            .env("SSH_AUTH_SOCK", agent.socket())
+            .arg(&key_path)
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut client = AgentClient::connect(connect_agent_at(&agent.socket()).await.unwrap());
+        let identities = list_identities(&mut client).await.unwrap();
+        assert_eq!(identities.len(), 1);
+
+        let public = std::fs::read_to_string(key_path.with_extension("pub")).unwrap();
+        let encoded = public.split_whitespace().nth(1).unwrap();
+        assert_eq!(identities[0].public_key_base64(), encoded);
+    }
+}
@@ -2,18 +2,259 @@
 //!
 //! Provides SSH connection management using russh library.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use chrono::Utc;
 use futures::channel::mpsc;
+use futures::StreamExt;
+use tokio::net::TcpStream;
 use tokio::sync::RwLock;
+use ring::digest;
 use russh::*;
 use russh_keys::*;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
-use crate::models::{JumpHostConfig, SessionStatus, SshAuthType, SshConnectRequest, SshSessionInfo, TerminalSize};
+use crate::models::{
+    AlgorithmPreferences, BatchHostResult, BatchJob, BatchJobRequest, BatchJobSummary,
+    BatchProgressEvent, BatchTarget, BroadcastGroupInfo, BroadcastReport, CertificateInfo,
+    ConnectErrorKind, ConnectionDiagnostics, DiagnosticStage, DynamicForwardRequest, ExecRequest,
+    ExecResult, HostKeyPromptEvent, JumpHostConfig, KeepaliveSettings,
+    KeyboardInteractivePromptEvent, KnownHostEntry, LocalForwardRequest, LocalShellInfo,
+    LocalShellRequest, MetricsMonitorInfo, MetricsRequest, MetricsSample, MetricsStoppedEvent,
+    NegotiatedAlgorithms, ProxyConfig, ReconnectPolicy, RecordingInfo, RecordingOptions,
+    RemoteForwardRequest, ReplayFrame, ScrollbackExportRequest, ScrollbackLines, ScrollbackMatch,
+    ScrollbackSearchRequest, ScrollbackSnapshot, SessionStatus, SshAuthType, SshConnectRequest,
+    SshSessionInfo, SshStatusEvent, TelnetConnectRequest, TerminalOptions, TerminalSize,
+    TunnelInfo, MAX_SCROLLBACK_LINES, MIN_SCROLLBACK_LINES,
+};
+use crate::services::algorithms::{self, KexCapture, KexInit};
+use crate::services::batch::{BatchEvent, BatchManager};
+use crate::services::broadcast::{self, BroadcastGroups};
+use crate::services::certificate;
+use crate::services::diagnostics::{self, fail, Report};
+use crate::services::exec::{self, ExecEvent, ExecManager};
+use crate::services::interactive_auth::InteractiveAuth;
+use crate::services::known_hosts::HostKeyVerifier;
+use crate::services::local_shell::{self, LocalShellManager};
+use crate::services::metrics::MetricsManager;
+use crate::services::proxy::connect_via_proxy;
+use crate::services::recording::RecordingManager;
+use crate::services::scrollback::SharedScrollback;
+use crate::services::ssh_agent;
+use crate::services::telnet::TelnetManager;
+use crate::services::terminal_output::OutputSender;
+use crate::services::tunnel::TunnelManager;
+
+/// Events raised by the SSH service that are not tied to a session stream
+#[derive(Debug, Clone)]
+pub enum SshEvent {
+    HostKeyPrompt(HostKeyPromptEvent),
+    KeyboardInteractivePrompt(KeyboardInteractivePromptEvent),
+    /// Connection state changes detected by the service (lost, reconnecting...)
+    Status(SshStatusEvent),
+    /// Sample of a session's host metrics collector
+    Metrics(MetricsSample),
+    MetricsStopped(MetricsStoppedEvent),
+}
+
+/// Shared sender for `SshEvent`s, installed once the frontend is ready
+#[derive(Clone, Default)]
+pub struct SshEventSink(Arc<parking_lot::RwLock<Option<mpsc::UnboundedSender<SshEvent>>>>);
+
+impl SshEventSink {
+    pub fn set(&self, tx: mpsc::UnboundedSender<SshEvent>) {
+        *self.0.write() = Some(tx);
+    }
+
+    /// Send an event, returning false if nobody is listening
+    pub fn emit(&self, event: SshEvent) -> bool {
+        match self.0.read().as_ref() {
+            Some(tx) => tx.unbounded_send(event).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// SSH connection handle shared between the sessions and tunnels of a
+/// connection. Most operations only need a read lock; a few russh calls such
+/// as `tcpip_forward` take `&mut self`.
+pub type SharedHandle = Arc<RwLock<client::Handle<SshClientHandler>>>;
+
+/// Session a terminal channel belongs to
+#[derive(Clone)]
+pub struct ChannelRoute {
+    pub session_id: String,
+    /// Whether the session asked for the local ssh-agent to be forwarded
+    pub agent_forwarding: bool,
+}
+
+/// Terminal channels of a connection by channel ID
+pub type ChannelRoutes = Arc<parking_lot::RwLock<HashMap<ChannelId, ChannelRoute>>>;
+
+/// Input for a terminal channel, which belongs to the task reading its output
+enum TerminalInput {
+    Data(Vec<u8>),
+    Resize(TerminalSize),
+    Close,
+}
+
+/// Lifecycle events of a connection raised by its client handler
+pub enum ConnectionEvent {
+    /// The server closed the terminal channel of a session (e.g. the shell exited)
+    ChannelClosed(String),
+    /// The connection is gone, with the reason if known
+    Closed(Option<String>),
+}
+
+/// Sessions to the same user@host:port share one connection if they reach it
+/// the same way, log in with the same credentials and ask for the same
+/// connection settings
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ConnectionKey {
+    username: String,
+    host: String,
+    port: u16,
+    /// The proxy, then the jump hosts in hop order
+    route: Vec<String>,
+    /// SHA-256 of the credentials of every hop, so the pool keeps no secrets
+    identity: Vec<u8>,
+    algorithms: AlgorithmPreferences,
+    keepalive: KeepaliveSettings,
+    auto_reconnect: Option<ReconnectPolicy>,
+    agent_forwarding: bool,
+}
+
+impl ConnectionKey {
+    fn new(request: &SshConnectRequest) -> Self {
+        let mut route = Vec::new();
+        let mut identity = digest::Context::new(&digest::SHA256);
+        if let Some(proxy) = &request.proxy {
+            route.push(format!(
+                "{:?} {}@{}:{}",
+                proxy.proxy_type,
+                proxy.username.as_deref().unwrap_or_default(),
+                proxy.host.to_lowercase(),
+                proxy.port
+            ));
+            digest_field(&mut identity, proxy.password.as_deref());
+        }
+        for hop in &request.jump_hosts {
+            route.push(format!(
+                "{}@{}:{}",
+                hop.username,
+                hop.host.to_lowercase(),
+                hop.port
+            ));
+            digest_field(&mut identity, Some(&format!("{:?}", hop.auth_type)));
+            digest_field(&mut identity, hop.password.as_deref());
+            digest_field(&mut identity, hop.private_key.as_deref());
+            digest_field(&mut identity, hop.passphrase.as_deref());
+            digest_field(&mut identity, hop.certificate.as_deref());
+        }
+        digest_field(&mut identity, Some(&request.auth_type));
+        digest_field(&mut identity, request.password.as_deref());
+        digest_field(&mut identity, request.private_key.as_deref());
+        digest_field(&mut identity, request.passphrase.as_deref());
+        digest_field(&mut identity, request.certificate.as_deref());
+
+        Self {
+            username: request.username.clone(),
+            host: request.host.to_lowercase(),
+            port: request.port,
+            route,
+            identity: identity.finish().as_ref().to_vec(),
+            algorithms: request.algorithms.clone(),
+            keepalive: request.keepalive,
+            auto_reconnect: request.auto_reconnect,
+            agent_forwarding: request.agent_forwarding,
+        }
+    }
+}
+
+/// Add a field to a digest, length first so neighbouring fields cannot run
+/// into each other
+fn digest_field(context: &mut digest::Context, field: Option<&str>) {
+    let field = field.unwrap_or_default().as_bytes();
+    context.update(&(field.len() as u64).to_be_bytes());
+    context.update(field);
+}
+
+/// Lifecycle of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectionState {
+    Open,
+    /// Lost and being re-established for its sessions
+    Reconnecting,
+    /// Lost for good
+    Closed,
+}
+
+/// Authenticated SSH connection with the terminals of several sessions
+/// multiplexed over it
+pub struct SshConnection {
+    key: ConnectionKey,
+    /// Request the connection was established with, reused to reconnect.
+    /// Its connection settings are part of the key, so every session on the
+    /// connection asked for the same.
+    request: SshConnectRequest,
+    state: parking_lot::Mutex<ConnectionState>,
+    handle: SharedHandle,
+    /// Jump host connections carrying the target transport, in hop order
+    jump_handles: parking_lot::Mutex<Vec<client::Handle<SshClientHandler>>>,
+    /// Algorithms negotiated with the target
+    algorithms: parking_lot::Mutex<Option<NegotiatedAlgorithms>>,
+    routes: ChannelRoutes,
+    /// Sessions with a terminal on this connection. The connection is closed
+    /// when the last one is released.
+    sessions: parking_lot::Mutex<HashSet<String>>,
+}
+
+impl SshConnection {
+    fn session_ids(&self) -> Vec<String> {
+        self.sessions.lock().iter().cloned().collect()
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        *self.state.lock() = state;
+    }
+
+    /// Whether the connection is lost but kept for its sessions until it is
+    /// re-established. The supervisor may not have noticed the loss yet.
+    async fn is_reconnecting(&self) -> bool {
+        match *self.state.lock() {
+            ConnectionState::Reconnecting => return true,
+            ConnectionState::Closed => return false,
+            ConnectionState::Open => {}
+        }
+        self.request.auto_reconnect.is_some() && self.handle.read().await.is_closed()
+    }
+
+    /// Close the connection, then tear down the jump host chain innermost hop first
+    async fn close(&self, reason: &str) {
+        let _ = self
+            .handle
+            .read()
+            .await
+            .disconnect(Disconnect::ByApplication, reason, "")
+            .await;
+        let jump_handles = std::mem::take(&mut *self.jump_handles.lock());
+        for jump_handle in jump_handles.into_iter().rev() {
+            let _ = jump_handle
+                .disconnect(Disconnect::ByApplication, reason, "")
+                .await;
+        }
+    }
+}
+
+/// Pooled connection of a user@host:port. Holding the lock while connecting
+/// makes sessions opened at the same time share one connection too.
+type ConnectionSlot = Arc<tokio::sync::Mutex<Option<Arc<SshConnection>>>>;
 
 /// SSH session handle for managing a single SSH connection
 pub struct SshSession {
@@ -23,12 +264,15 @@ pub struct SshSession {
     pub host: String,
     pub username: String,
     pub port: u16,
-    handle: Option<client::Handle<SshClientHandler>>,
-    channel: Option<Channel<client::Msg>>,
-    tx: Option<mpsc::UnboundedSender<Vec<u8>>>,
+    /// Connection the terminal is multiplexed over
+    connection: Option<Arc<SshConnection>>,
+    /// Input of the terminal channel while it is open
+    terminal: Option<mpsc::UnboundedSender<TerminalInput>>,
+    tx: Option<OutputSender>,
     // Store connection parameters for reconnection
     connect_request: Option<SshConnectRequest>,
     terminal_size: TerminalSize,
+    certificate: Option<CertificateInfo>,
 }
 
 impl SshSession {
@@ -40,11 +284,16 @@ impl SshSession {
             host: request.host.clone(),
             username: request.username.clone(),
             port: request.port,
-            handle: None,
-            channel: None,
+            connection: None,
+            terminal: None,
             tx: None,
             connect_request: Some(request.clone()),
             terminal_size: request.terminal_size,
+            certificate: request
+                .certificate
+                .as_deref()
+                .and_then(|text| certificate::parse(text).ok())
+                .map(|cert| certificate::info(&cert)),
         }
     }
 
@@ -56,16 +305,40 @@ impl SshSession {
             connected_at: None,
             host: self.host.clone(),
             username: self.username.clone(),
+            certificate: self.certificate.clone(),
+            algorithms: self
+                .connection
+                .as_ref()
+                .and_then(|connection| connection.algorithms.lock().clone()),
         }
     }
 }
 
 /// SSH client handler for russh callbacks
 pub struct SshClientHandler {
-    pub session_id: String,
-    pub data_tx: mpsc::UnboundedSender<Vec<u8>>,
-    /// Terminal channel ID - only data from this channel will be forwarded to terminal
-    pub terminal_channel_id: Arc<RwLock<Option<ChannelId>>>,
+    /// Terminal channels of the connection's sessions
+    pub routes: ChannelRoutes,
+    /// Host and port the server key is verified against
+    pub host: String,
+    pub port: u16,
+    pub host_keys: Arc<HostKeyVerifier>,
+    /// Routes forwarded-tcpip channels of remote port forwards
+    pub tunnels: Arc<TunnelManager>,
+    /// Receives the lifecycle events of the connection
+    pub events_tx: Option<mpsc::UnboundedSender<ConnectionEvent>>,
+    disconnect_reason: Option<String>,
+    /// Why the server key was refused, kept past the end of the connection
+    host_key_error: Arc<parking_lot::Mutex<Option<String>>>,
+}
+
+// The handler lives exactly as long as the connection task, so dropping it is
+// the one signal that covers every way a connection can end.
+impl Drop for SshClientHandler {
+    fn drop(&mut self) {
+        if let Some(tx) = self.events_tx.take() {
+            let _ = tx.unbounded_send(ConnectionEvent::Closed(self.disconnect_reason.take()));
+        }
+    }
 }
 
 #[async_trait]
@@ -74,41 +347,102 @@ impl client::Handler for SshClientHandler {
 
     async fn check_server_key(
         &mut self,
-        _server_public_key: &key::PublicKey,
+        server_public_key: &key::PublicKey,
     ) -> Result<bool, Self::Error> {
-        // TODO: Implement proper host key verification
-        // For now, accept all keys (not secure for production)
-        Ok(true)
+        let result = self
+            .host_keys
+            .verify(&self.host, self.port, &server_public_key.public_key_bytes())
+            .await;
+        let error = match &result {
+            Ok(true) => None,
+            Ok(false) => Some(format!(
+                "Host key for {}:{} was not trusted",
+                self.host, self.port
+            )),
+            Err(e) => Some(e.to_string()),
+        };
+        *self.host_key_error.lock() = error;
+        result
+    }
+
+    async fn disconnected(
+        &mut self,
+        reason: client::DisconnectReason<Self::Error>,
+    ) -> Result<(), Self::Error> {
+        match reason {
+            client::DisconnectReason::ReceivedDisconnect(info) => {
+                self.disconnect_reason = Some(if info.message.is_empty() {
+                    "Server closed the connection".to_string()
+                } else {
+                    format!("Server closed the connection: {}", info.message)
+                });
+                Ok(())
+            }
+            client::DisconnectReason::Error(e) => {
+                self.disconnect_reason = Some(e.to_string());
+                Err(e)
+            }
+        }
     }
 
-    async fn data(
+    async fn channel_close(
         &mut self,
         channel: ChannelId,
-        data: &[u8],
         _session: &mut client::Session,
     ) -> Result<(), Self::Error> {
-        // Only forward data from terminal channel to avoid SFTP binary data in terminal
-        if let Some(term_ch) = *self.terminal_channel_id.read().await {
-            if channel == term_ch {
-                let _ = self.data_tx.unbounded_send(data.to_vec());
-            }
+        // Routes of channels closed on our side are removed beforehand
+        let route = self.routes.write().remove(&channel);
+        if let (Some(route), Some(tx)) = (route, &self.events_tx) {
+            let _ = tx.unbounded_send(ConnectionEvent::ChannelClosed(route.session_id));
         }
         Ok(())
     }
 
-    async fn extended_data(
+    async fn server_channel_open_forwarded_tcpip(
         &mut self,
-        channel: ChannelId,
-        _ext: u32,
-        data: &[u8],
+        channel: Channel<client::Msg>,
+        connected_address: &str,
+        connected_port: u32,
+        _originator_address: &str,
+        _originator_port: u32,
         _session: &mut client::Session,
     ) -> Result<(), Self::Error> {
-        // Only forward data from terminal channel
-        if let Some(term_ch) = *self.terminal_channel_id.read().await {
-            if channel == term_ch {
-                let _ = self.data_tx.unbounded_send(data.to_vec());
-            }
+        let session_ids: Vec<String> = self
+            .routes
+            .read()
+            .values()
+            .map(|route| route.session_id.clone())
+            .collect();
+        self.tunnels
+            .accept_forwarded(&session_ids, channel, connected_address, connected_port);
+        Ok(())
+    }
+
+    async fn server_channel_open_agent_forward(
+        &mut self,
+        channel: Channel<client::Msg>,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        // Agent requests are made per terminal channel
+        let allowed = self
+            .routes
+            .read()
+            .values()
+            .any(|route| route.agent_forwarding);
+        if !allowed {
+            log::warn!("Rejected agent channel from {}: agent forwarding is disabled", self.host);
+            tokio::spawn(async move {
+                let _ = channel.close().await;
+            });
+            return Ok(());
         }
+
+        let host = self.host.clone();
+        tokio::spawn(async move {
+            if let Err(e) = ssh_agent::forward_channel(channel).await {
+                log::warn!("Agent forwarding for {} failed: {}", host, e);
+            }
+        });
         Ok(())
     }
 }
@@ -116,6 +450,24 @@ impl client::Handler for SshClientHandler {
 /// SSH Service for managing multiple SSH sessions
 pub struct SshService {
     sessions: Arc<RwLock<HashMap<String, SshSession>>>,
+    /// Connections available for new sessions, by user@host:port
+    pool: parking_lot::Mutex<HashMap<ConnectionKey, ConnectionSlot>>,
+    events: SshEventSink,
+    host_keys: Arc<HostKeyVerifier>,
+    interactive_auth: InteractiveAuth,
+    tunnels: Arc<TunnelManager>,
+    recordings: Arc<RecordingManager>,
+    execs: ExecManager,
+    broadcasts: BroadcastGroups,
+    batches: BatchManager,
+    metrics: MetricsManager,
+    /// Terminals running a shell on this machine, handled like SSH sessions
+    local_shells: LocalShellManager,
+    /// Telnet terminals, handled like SSH sessions
+    telnet: TelnetManager,
+    /// Latest output of every terminal session, by session ID
+    scrollbacks: parking_lot::Mutex<HashMap<String, SharedScrollback>>,
+    data_dir: parking_lot::RwLock<Option<PathBuf>>,
 }
 
 impl Default for SshService {
@@ -126,88 +478,172 @@ impl Default for SshService {
 
 impl SshService {
     pub fn new() -> Self {
+        let events = SshEventSink::default();
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            pool: parking_lot::Mutex::new(HashMap::new()),
+            host_keys: Arc::new(HostKeyVerifier::new(events.clone())),
+            interactive_auth: InteractiveAuth::new(events.clone()),
+            tunnels: Arc::new(TunnelManager::new()),
+            recordings: Arc::new(RecordingManager::new()),
+            execs: ExecManager::new(),
+            broadcasts: BroadcastGroups::new(),
+            batches: BatchManager::new(),
+            metrics: MetricsManager::new(events.clone()),
+            local_shells: LocalShellManager::new(events.clone()),
+            telnet: TelnetManager::new(events.clone()),
+            scrollbacks: parking_lot::Mutex::new(HashMap::new()),
+            events,
+            data_dir: parking_lot::RwLock::new(None),
         }
     }
 
-    /// Connect to SSH server with password authentication
-    pub async fn connect_with_password(
-        &self,
-        request: SshConnectRequest,
-        data_tx: mpsc::UnboundedSender<Vec<u8>>,
-    ) -> Result<String> {
-        let password = request
-            .password
-            .as_ref()
-            .ok_or_else(|| anyhow!("Password is required"))?;
+    /// Install the sender used to deliver service events to the frontend
+    pub fn set_event_sender(&self, tx: mpsc::UnboundedSender<SshEvent>) {
+        self.events.set(tx);
+    }
 
-        let mut session = SshSession::new(&request);
-        let session_id = session.session_id.clone();
+    /// Set the application data directory and load persistent state from it
+    pub fn set_data_dir(&self, dir: PathBuf) -> Result<()> {
+        self.host_keys.load(&dir.join("known_hosts"))?;
+        self.recordings.set_dir(dir.join("recordings"))?;
+        self.batches.set_dir(dir.join("batch-jobs"))?;
+        *self.data_dir.write() = Some(dir);
+        Ok(())
+    }
 
-        // Check if jump host is configured
-        if let Some(ref jump) = request.jump_host {
-            return self.connect_via_jump_host(&request, jump, data_tx).await;
+    /// Create a client handler that verifies the server key of `host:port`
+    fn new_handler(&self, routes: ChannelRoutes, host: &str, port: u16) -> SshClientHandler {
+        SshClientHandler {
+            routes,
+            host: host.to_string(),
+            port,
+            host_keys: self.host_keys.clone(),
+            tunnels: self.tunnels.clone(),
+            events_tx: None,
+            disconnect_reason: None,
+            host_key_error: Arc::default(),
         }
+    }
 
-        // Configure SSH client
-        let config = client::Config {
-            inactivity_timeout: Some(std::time::Duration::from_secs(3600)),
-            ..Default::default()
-        };
-        let config = Arc::new(config);
+    /// Report a connection state change detected by the service
+    fn emit_status(&self, session_id: &str, status: SessionStatus, message: Option<String>) {
+        self.events.emit(SshEvent::Status(SshStatusEvent {
+            session_id: session_id.to_string(),
+            status,
+            message,
+        }));
+    }
 
-        // Create shared terminal channel ID for filtering data
-        let terminal_channel_id = Arc::new(RwLock::new(None));
-        let terminal_channel_id_clone = terminal_channel_id.clone();
+    /// Answer a pending host key prompt
+    pub fn respond_host_key(&self, request_id: &str, accept: bool) -> Result<()> {
+        self.host_keys.respond(request_id, accept)
+    }
 
-        let handler = SshClientHandler {
-            session_id: session_id.clone(),
-            data_tx: data_tx.clone(),
-            terminal_channel_id: terminal_channel_id_clone,
-        };
+    /// Answer a pending keyboard-interactive prompt; `None` cancels authentication
+    pub fn respond_keyboard_interactive(
+        &self,
+        request_id: &str,
+        answers: Option<Vec<String>>,
+    ) -> Result<()> {
+        self.interactive_auth.respond(request_id, answers)
+    }
 
-        // Connect to server
-        let addr = format!("{}:{}", request.host, request.port);
-        let mut handle = client::connect(config, addr, handler).await?;
+    /// List entries of the known_hosts store
+    pub fn list_known_hosts(&self) -> Vec<KnownHostEntry> {
+        self.host_keys.list()
+    }
 
-        // Authenticate with password
-        let auth_result = handle
-            .authenticate_password(&request.username, password)
-            .await?;
+    /// Forget recorded keys for a host so it can be trusted again
+    pub fn remove_known_host(&self, host: &str, port: u16) -> Result<usize> {
+        self.host_keys.remove(host, port)
+    }
+
+    /// Connect to SSH server with password authentication
+    pub async fn connect_with_password(
+        self: &Arc<Self>,
+        request: SshConnectRequest,
+        data_tx: OutputSender,
+    ) -> Result<String> {
+        if request.password.is_none() {
+            return Err(anyhow!("Password is required"));
+        }
+        self.connect_session(request, data_tx).await
+    }
 
-        if !auth_result {
-            return Err(anyhow!("Password authentication failed"));
+    /// Connect to SSH server with private key authentication
+    pub async fn connect_with_key(
+        self: &Arc<Self>,
+        request: SshConnectRequest,
+        data_tx: OutputSender,
+    ) -> Result<String> {
+        if request.private_key.is_none() {
+            return Err(anyhow!("Private key is required"));
         }
+        self.connect_session(request, data_tx).await
+    }
 
-        // Open a shell channel
-        let channel = handle.channel_open_session().await?;
+    /// Connect to SSH server using identities held by the local ssh-agent
+    pub async fn connect_with_agent(
+        self: &Arc<Self>,
+        request: SshConnectRequest,
+        data_tx: OutputSender,
+    ) -> Result<String> {
+        self.connect_session(request, data_tx).await
+    }
 
-        // Store terminal channel ID for filtering data
-        *terminal_channel_id.write().await = Some(channel.id());
+    /// Connect to SSH server with keyboard-interactive authentication (OTP / 2FA).
+    /// Server prompts are relayed to the frontend as events.
+    pub async fn connect_interactive(
+        self: &Arc<Self>,
+        request: SshConnectRequest,
+        data_tx: OutputSender,
+    ) -> Result<String> {
+        self.connect_session(request, data_tx).await
+    }
 
-        // Request PTY
-        channel
-            .request_pty(
-                false,
-                "xterm-256color",
-                request.terminal_size.cols,
-                request.terminal_size.rows,
-                0,
-                0,
-                &[],
-            )
-            .await?;
+    /// Open the terminal channel of a new session, on the pooled connection
+    /// to the same user@host:port if there is one
+    async fn connect_session(
+        self: &Arc<Self>,
+        request: SshConnectRequest,
+        data_tx: OutputSender,
+    ) -> Result<String> {
+        let lines = request.terminal.scrollback_lines;
+        if !(MIN_SCROLLBACK_LINES..=MAX_SCROLLBACK_LINES).contains(&lines) {
+            return Err(fail(
+                ConnectErrorKind::Config,
+                format!(
+                    "Scrollback must be between {} and {} lines",
+                    MIN_SCROLLBACK_LINES, MAX_SCROLLBACK_LINES
+                ),
+            ));
+        }
 
-        // Set UTF-8 locale environment variables
-        let _ = channel.set_env(false, "LANG", "en_US.UTF-8").await;
-        let _ = channel.set_env(false, "LC_ALL", "en_US.UTF-8").await;
+        let mut session = SshSession::new(&request);
+        let session_id = session.session_id.clone();
 
-        // Request shell
-        channel.request_shell(false).await?;
+        let connection = self.acquire_connection(&session_id, &request).await?;
+        let channel = match self
+            .start_terminal(&connection, &session_id, &request)
+            .await
+        {
+            Ok(channel) => channel,
+            Err(e) => {
+                self.release_connection(&session_id, &connection).await;
+                return Err(e);
+            }
+        };
 
-        session.handle = Some(handle);
-        session.channel = Some(channel);
+        let scrollback = data_tx.scrollback();
+        scrollback
+            .lock()
+            .set_max_lines(request.terminal.scrollback_lines);
+        self.scrollbacks
+            .lock()
+            .insert(session_id.clone(), scrollback);
+        session.connection = Some(connection);
+        session.terminal = Some(self.spawn_terminal(&session_id, channel, data_tx.clone()));
         session.tx = Some(data_tx);
         session.status = SessionStatus::Connected;
 
@@ -217,236 +653,508 @@ impl SshService {
         Ok(session_id)
     }
 
-    /// Connect to SSH server with private key authentication
-    pub async fn connect_with_key(
+    /// Open the terminal of a new session on its connection and start the shell
+    async fn start_terminal(
         &self,
-        request: SshConnectRequest,
-        data_tx: mpsc::UnboundedSender<Vec<u8>>,
-    ) -> Result<String> {
-        let private_key_str = request
-            .private_key
-            .as_ref()
-            .ok_or_else(|| anyhow!("Private key is required"))?;
+        connection: &SshConnection,
+        session_id: &str,
+        request: &SshConnectRequest,
+    ) -> Result<Channel<client::Msg>> {
+        let route = ChannelRoute {
+            session_id: session_id.to_string(),
+            agent_forwarding: request.agent_forwarding,
+        };
+        let channel =
+            open_terminal(connection, route, request.terminal_size, &request.terminal).await?;
+
+        // Start recording before the shell prints anything
+        if let Some(options) = request.recording {
+            self.recordings.start(
+                session_id,
+                format!("{}@{}", request.username, request.host),
+                request.terminal_size,
+                &request.terminal.term,
+                options.record_input,
+            )?;
+        }
 
-        let mut session = SshSession::new(&request);
-        let session_id = session.session_id.clone();
+        start_shell(&channel, &request.terminal).await?;
+        Ok(channel)
+    }
+
+    /// Hand a started terminal channel to a task that reads its output into
+    /// `output` and writes the session's input to it
+    fn spawn_terminal(
+        &self,
+        session_id: &str,
+        channel: Channel<client::Msg>,
+        output: OutputSender,
+    ) -> mpsc::UnboundedSender<TerminalInput> {
+        let (input_tx, input_rx) = mpsc::unbounded();
+        tokio::spawn(run_terminal(
+            channel,
+            input_rx,
+            output,
+            self.recordings.clone(),
+            session_id.to_string(),
+        ));
+        input_tx
+    }
 
-        // Check if jump host is configured
-        if let Some(ref jump) = request.jump_host {
-            return self.connect_via_jump_host(&request, jump, data_tx).await;
+    /// Attach a session to the pooled connection of its user@host:port,
+    /// connecting and authenticating only if there is none yet
+    async fn acquire_connection(
+        self: &Arc<Self>,
+        session_id: &str,
+        request: &SshConnectRequest,
+    ) -> Result<Arc<SshConnection>> {
+        let key = ConnectionKey::new(request);
+        let slot = self.pool.lock().entry(key.clone()).or_default().clone();
+        let mut pooled = slot.lock().await;
+
+        // A connection being re-established stays pooled for its sessions;
+        // this session gets a connection of its own meanwhile
+        let mut shared = true;
+        if let Some(connection) = pooled.as_ref() {
+            if connection.is_reconnecting().await {
+                shared = false;
+            } else if *connection.state.lock() == ConnectionState::Open
+                && !connection.handle.read().await.is_closed()
+            {
+                connection.sessions.lock().insert(session_id.to_string());
+                return Ok(connection.clone());
+            }
         }
 
-        // Parse private key
-        let key_pair = if let Some(passphrase) = &request.passphrase {
-            decode_secret_key(private_key_str, Some(passphrase))?
-        } else {
-            decode_secret_key(private_key_str, None)?
-        };
+        let routes = ChannelRoutes::default();
+        let (events_tx, events_rx) = mpsc::unbounded();
+        let mut handler = self.new_handler(routes.clone(), &request.host, request.port);
+        handler.events_tx = Some(events_tx);
+
+        let config = client_config(request)?;
+        let (handle, jump_handles, algorithms) = self.establish(request, config, handler).await?;
+
+        let connection = Arc::new(SshConnection {
+            key,
+            request: request.clone(),
+            state: parking_lot::Mutex::new(ConnectionState::Open),
+            handle: Arc::new(RwLock::new(handle)),
+            jump_handles: parking_lot::Mutex::new(jump_handles),
+            algorithms: parking_lot::Mutex::new(algorithms),
+            routes,
+            sessions: parking_lot::Mutex::new(HashSet::from([session_id.to_string()])),
+        });
+        if shared {
+            *pooled = Some(connection.clone());
+        }
+        tokio::spawn(supervise(self.clone(), connection.clone(), events_rx));
+        Ok(connection)
+    }
 
-        // Configure SSH client
-        let config = client::Config {
-            inactivity_timeout: Some(std::time::Duration::from_secs(3600)),
-            ..Default::default()
+    /// Detach a session from its connection, closing the connection along
+    /// with its last session
+    async fn release_connection(&self, session_id: &str, connection: &Arc<SshConnection>) {
+        connection
+            .routes
+            .write()
+            .retain(|_, route| route.session_id != session_id);
+
+        // Hold the pool slot so no new session picks up a connection being closed
+        let slot = self.pool.lock().get(&connection.key).cloned();
+        let mut pooled = match &slot {
+            Some(slot) => Some(slot.lock().await),
+            None => None,
         };
-        let config = Arc::new(config);
+        {
+            let mut sessions = connection.sessions.lock();
+            sessions.remove(session_id);
+            if !sessions.is_empty() {
+                return;
+            }
+        }
+        if let Some(pooled) = pooled.as_mut() {
+            if pooled.as_ref().is_some_and(|c| Arc::ptr_eq(c, connection)) {
+                **pooled = None;
+            }
+        }
+        drop(pooled);
+
+        connection.close("User disconnected").await;
+    }
 
-        // Create shared terminal channel ID for filtering data
-        let terminal_channel_id = Arc::new(RwLock::new(None));
-        let terminal_channel_id_clone = terminal_channel_id.clone();
+    /// Mark the sessions of a dropped connection as disconnected and, if the
+    /// connection's request opted in, reconnect it in place with exponential
+    /// backoff. Returns the event stream of the new connection after a reconnect.
+    async fn connection_lost(
+        &self,
+        connection: &SshConnection,
+        reason: Option<String>,
+    ) -> Option<mpsc::UnboundedReceiver<ConnectionEvent>> {
+        // Channel IDs of the old connection mean nothing on a new one
+        connection.routes.write().clear();
+
+        // Sessions closed on purpose are released before their connection ends
+        let session_ids = self
+            .mark_sessions(connection, SessionStatus::Disconnected)
+            .await;
+        if session_ids.is_empty() {
+            connection.set_state(ConnectionState::Closed);
+            return None;
+        }
+        let reason = reason.unwrap_or_else(|| "Connection closed".to_string());
+        log::warn!(
+            "SSH connection to {}@{}:{} lost: {}",
+            connection.key.username,
+            connection.key.host,
+            connection.key.port,
+            reason
+        );
 
-        let handler = SshClientHandler {
-            session_id: session_id.clone(),
-            data_tx: data_tx.clone(),
-            terminal_channel_id: terminal_channel_id_clone,
+        let Some(policy) = connection.request.auto_reconnect else {
+            connection.set_state(ConnectionState::Closed);
+            let message = format!("Connection lost: {}", reason);
+            self.end_sessions(&session_ids, SessionStatus::Disconnected, &message)
+                .await;
+            return None;
         };
 
-        // Connect to server
-        let addr = format!("{}:{}", request.host, request.port);
-        let mut handle = client::connect(config, addr, handler).await?;
+        connection.set_state(ConnectionState::Reconnecting);
+        let mut last_error = reason;
+        for attempt in 1..=policy.max_attempts {
+            let delay = policy.delay(attempt);
+            let session_ids = self
+                .mark_sessions(connection, SessionStatus::Connecting)
+                .await;
+            if session_ids.is_empty() {
+                connection.set_state(ConnectionState::Closed);
+                return None;
+            }
+            let message = format!(
+                "Reconnecting in {:.1}s (attempt {}/{}): {}",
+                delay.as_secs_f64(),
+                attempt,
+                policy.max_attempts,
+                last_error
+            );
+            for session_id in &session_ids {
+                self.emit_status(session_id, SessionStatus::Connecting, Some(message.clone()));
+            }
+            tokio::time::sleep(delay).await;
+
+            match self.reopen(connection).await {
+                Ok(events) => {
+                    connection.set_state(ConnectionState::Open);
+                    self.resume_sessions(connection).await;
+                    return Some(events);
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Reconnect attempt {} to {} failed: {}",
+                        attempt,
+                        connection.key.host,
+                        e
+                    );
+                    last_error = e.to_string();
+                }
+            }
+        }
 
-        // Authenticate with public key
-        let auth_result = handle
-            .authenticate_publickey(&request.username, Arc::new(key_pair))
-            .await?;
+        connection.set_state(ConnectionState::Closed);
+        let session_ids = self.mark_sessions(connection, SessionStatus::Error).await;
+        let message = format!(
+            "Reconnect failed after {} attempts: {}",
+            policy.max_attempts, last_error
+        );
+        self.end_sessions(&session_ids, SessionStatus::Error, &message)
+            .await;
+        None
+    }
 
-        if !auth_result {
-            return Err(anyhow!("Public key authentication failed"));
+    /// Set the status of the sessions of a dropped connection and return the
+    /// IDs of those still open
+    async fn mark_sessions(
+        &self,
+        connection: &SshConnection,
+        status: SessionStatus,
+    ) -> Vec<String> {
+        let mut sessions = self.sessions.write().await;
+        let mut session_ids = Vec::new();
+        for session_id in connection.session_ids() {
+            if let Some(session) = sessions.get_mut(&session_id) {
+                session.status = status;
+                session.terminal = None;
+                session_ids.push(session_id);
+            }
         }
+        session_ids
+    }
 
-        // Open a shell channel
-        let channel = handle.channel_open_session().await?;
+    /// Give up on sessions whose connection could not be kept
+    async fn end_sessions(&self, session_ids: &[String], status: SessionStatus, message: &str) {
+        for session_id in session_ids {
+            self.tunnels.close_session(session_id).await;
+            self.recordings.stop(session_id);
+            self.emit_status(session_id, status, Some(message.to_string()));
+        }
+    }
 
-        // Store terminal channel ID for filtering data
-        *terminal_channel_id.write().await = Some(channel.id());
+    /// Re-establish a dropped connection in place. Sessions and tunnels keep
+    /// their shared handle, which then points at the new transport.
+    async fn reopen(
+        &self,
+        connection: &SshConnection,
+    ) -> Result<mpsc::UnboundedReceiver<ConnectionEvent>> {
+        let request = &connection.request;
+        let (events_tx, events_rx) = mpsc::unbounded();
+        let mut handler = self.new_handler(connection.routes.clone(), &request.host, request.port);
+        handler.events_tx = Some(events_tx);
+
+        let config = client_config(request)?;
+        let (handle, jump_handles, algorithms) = self.establish(request, config, handler).await?;
+        *connection.handle.write().await = handle;
+        *connection.jump_handles.lock() = jump_handles;
+        *connection.algorithms.lock() = algorithms;
+        Ok(events_rx)
+    }
 
-        // Request PTY
-        channel
-            .request_pty(
-                false,
-                "xterm-256color",
-                request.terminal_size.cols,
-                request.terminal_size.rows,
-                0,
-                0,
-                &[],
-            )
-            .await?;
+    /// Reopen the terminals of a connection's sessions after a reconnect
+    async fn resume_sessions(&self, connection: &SshConnection) {
+        for session_id in connection.session_ids() {
+            match self.resume_session(connection, &session_id).await {
+                Ok(true) => {
+                    self.tunnels.restore_remote(&session_id).await;
+                    self.emit_status(
+                        &session_id,
+                        SessionStatus::Connected,
+                        Some("Reconnected".to_string()),
+                    );
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    if let Some(session) = self.sessions.write().await.get_mut(&session_id) {
+                        session.status = SessionStatus::Error;
+                    }
+                    let message = format!("Could not reopen the terminal: {}", e);
+                    self.end_sessions(&[session_id], SessionStatus::Error, &message)
+                        .await;
+                }
+            }
+        }
 
-        // Set UTF-8 locale environment variables
-        let _ = channel.set_env(false, "LANG", "en_US.UTF-8").await;
-        let _ = channel.set_env(false, "LC_ALL", "en_US.UTF-8").await;
+        // Every session went away while reconnecting
+        if connection.sessions.lock().is_empty() {
+            connection.close("Session closed").await;
+        }
+    }
 
-        // Request shell
-        channel.request_shell(false).await?;
+    /// Reopen the terminal of a session on its re-established connection,
+    /// keeping the session ID and data stream. Returns false if the session
+    /// was closed in the meantime.
+    async fn resume_session(&self, connection: &SshConnection, session_id: &str) -> Result<bool> {
+        let (route, size, terminal, data_tx) = {
+            let sessions = self.sessions.read().await;
+            let Some(session) = sessions.get(session_id) else {
+                return Ok(false);
+            };
+            let data_tx = session
+                .tx
+                .clone()
+                .ok_or_else(|| anyhow!("Session has no data stream"))?;
+            let route = ChannelRoute {
+                session_id: session_id.to_string(),
+                agent_forwarding: session
+                    .connect_request
+                    .as_ref()
+                    .is_some_and(|r| r.agent_forwarding),
+            };
+            let terminal = session
+                .connect_request
+                .as_ref()
+                .map(|r| r.terminal.clone())
+                .unwrap_or_default();
+            (route, session.terminal_size, terminal, data_tx)
+        };
 
-        session.handle = Some(handle);
-        session.channel = Some(channel);
-        session.tx = Some(data_tx);
+        // The shell is new, so the startup commands run again
+        let channel = open_terminal(connection, route, size, &terminal).await?;
+        start_shell(&channel, &terminal).await?;
+
+        let mut sessions = self.sessions.write().await;
+        let Some(session) = sessions.get_mut(session_id) else {
+            drop(sessions);
+            connection.routes.write().remove(&channel.id());
+            let _ = channel.close().await;
+            return Ok(false);
+        };
+        session.terminal = Some(self.spawn_terminal(session_id, channel, data_tx));
         session.status = SessionStatus::Connected;
+        Ok(true)
+    }
 
-        // Store session
-        self.sessions.write().await.insert(session_id.clone(), session);
+    /// Open the SSH transport to the target (directly or through the jump host
+    /// chain) and authenticate. Returns the target handle, the jump host
+    /// handles, which must be kept alive for as long as the target handle is
+    /// used, and the algorithms negotiated with the target.
+    async fn establish(
+        &self,
+        request: &SshConnectRequest,
+        config: Arc<client::Config>,
+        handler: SshClientHandler,
+    ) -> Result<(
+        client::Handle<SshClientHandler>,
+        Vec<client::Handle<SshClientHandler>>,
+        Option<NegotiatedAlgorithms>,
+    )> {
+        let credentials = Credentials::from_request(request)?;
+        let jump_handles = self.open_jump_hosts(request, &config).await?;
+
+        let (mut handle, handshake) = open_transport(
+            jump_handles.last(),
+            request.proxy.as_ref(),
+            &request.host,
+            request.port,
+            config.clone(),
+            handler,
+        )
+        .await?;
+
+        self.authenticate(&mut handle, &request.host, &credentials)
+            .await
+            .map_err(|e| handshake.explain(e, &config))?;
 
-        Ok(session_id)
+        // The key exchange is complete once authenticated
+        let algorithms = handshake.capture.server().and_then(|server| {
+            algorithms::negotiate(&KexInit::client(&config.preferred), &server).ok()
+        });
+        Ok((handle, jump_handles, algorithms))
     }
 
-    /// Connect via jump host (bastion/proxy)
-    async fn connect_via_jump_host(
+    /// Connect and authenticate to the jump hosts of `request` in order. Each
+    /// one is reached through the previous one.
+    async fn open_jump_hosts(
         &self,
         request: &SshConnectRequest,
-        jump: &JumpHostConfig,
-        data_tx: mpsc::UnboundedSender<Vec<u8>>,
-    ) -> Result<String> {
-        let mut session = SshSession::new(request);
-        let session_id = session.session_id.clone();
+        config: &Arc<client::Config>,
+    ) -> Result<Vec<client::Handle<SshClientHandler>>> {
+        let mut jump_handles: Vec<client::Handle<SshClientHandler>> = Vec::new();
+        for (index, jump) in request.jump_hosts.iter().enumerate() {
+            let hop = format!("Jump host {} ({})", index + 1, jump.host);
+
+            // The jump host never carries a terminal, so it has no channel routes
+            let jump_handler = self.new_handler(ChannelRoutes::default(), &jump.host, jump.port);
+
+            let (mut jump_handle, handshake) = open_transport(
+                jump_handles.last(),
+                request.proxy.as_ref(),
+                &jump.host,
+                jump.port,
+                config.clone(),
+                jump_handler,
+            )
+            .await
+            .map_err(|e| diagnostics::on_hop(e, &hop))?;
 
-        // First, connect to jump host
-        let jump_config = client::Config {
-            inactivity_timeout: Some(std::time::Duration::from_secs(3600)),
-            ..Default::default()
-        };
-        let jump_config = Arc::new(jump_config);
-
-        // Create a dummy handler for jump host (we won't use its data channel)
-        let (dummy_tx, _dummy_rx) = mpsc::unbounded::<Vec<u8>>();
-        let jump_handler = SshClientHandler {
-            session_id: format!("{}-jump", session_id),
-            data_tx: dummy_tx,
-            terminal_channel_id: Arc::new(RwLock::new(None)),
-        };
+            let jump_credentials = Credentials::from_jump_host(jump);
+            self.authenticate(&mut jump_handle, &jump.host, &jump_credentials)
+                .await
+                .map_err(|e| diagnostics::on_hop(handshake.explain(e, config), &hop))?;
 
-        let jump_addr = format!("{}:{}", jump.host, jump.port);
-        let mut jump_handle = client::connect(jump_config, jump_addr, jump_handler).await?;
+            jump_handles.push(jump_handle);
+        }
+        Ok(jump_handles)
+    }
 
-        // Authenticate to jump host
-        match jump.auth_type {
+    /// Authenticate an SSH handle with the given credentials
+    async fn authenticate(
+        &self,
+        handle: &mut client::Handle<SshClientHandler>,
+        host: &str,
+        credentials: &Credentials<'_>,
+    ) -> Result<()> {
+        match credentials.auth_type {
             SshAuthType::Password => {
-                let password = jump
+                let password = credentials
                     .password
-                    .as_ref()
-                    .ok_or_else(|| anyhow!("Jump host password is required"))?;
-                let auth_result = jump_handle
-                    .authenticate_password(&jump.username, password)
+                    .ok_or_else(|| fail(ConnectErrorKind::Config, "Password is required"))?;
+                let auth_result = handle
+                    .authenticate_password(credentials.username, password)
                     .await?;
                 if !auth_result {
-                    return Err(anyhow!("Jump host password authentication failed"));
+                    return Err(fail(
+                        ConnectErrorKind::Auth,
+                        "Password authentication failed",
+                    ));
                 }
             }
             SshAuthType::Key => {
-                let private_key_str = jump
+                let private_key_str = credentials
                     .private_key
-                    .as_ref()
-                    .ok_or_else(|| anyhow!("Jump host private key is required"))?;
-                let key_pair = if let Some(passphrase) = &jump.passphrase {
-                    decode_secret_key(private_key_str, Some(passphrase))?
-                } else {
-                    decode_secret_key(private_key_str, None)?
+                    .ok_or_else(|| fail(ConnectErrorKind::Config, "Private key is required"))?;
+                let key_pair =
+                    decode_secret_key(private_key_str, credentials.passphrase).map_err(|e| {
+                        fail(
+                            ConnectErrorKind::Config,
+                            format!("Could not read private key: {}", e),
+                        )
+                    })?;
+                let Some(cert_text) = credentials.certificate else {
+                    let auth_result = handle
+                        .authenticate_publickey(credentials.username, Arc::new(key_pair))
+                        .await?;
+                    if !auth_result {
+                        return Err(fail(
+                            ConnectErrorKind::Auth,
+                            "Public key authentication failed",
+                        ));
+                    }
+                    return Ok(());
                 };
-                let auth_result = jump_handle
-                    .authenticate_publickey(&jump.username, Arc::new(key_pair))
+
+                let cert = certificate::parse(cert_text)
+                    .map_err(|e| fail(ConnectErrorKind::Config, e.to_string()))?;
+                let public_key = key_pair.clone_public_key()?.public_key_bytes();
+                certificate::check(&cert, &public_key, Utc::now().timestamp() as u64)
+                    .map_err(|e| fail(ConnectErrorKind::Auth, e.to_string()))?;
+                let auth_result = handle
+                    .authenticate_openssh_cert(credentials.username, Arc::new(key_pair), cert)
                     .await?;
                 if !auth_result {
-                    return Err(anyhow!("Jump host key authentication failed"));
+                    return Err(fail(
+                        ConnectErrorKind::Auth,
+                        "Certificate authentication failed",
+                    ));
                 }
             }
             SshAuthType::Interactive => {
-                return Err(anyhow!("Interactive auth not supported for jump host"));
+                let auth_result = self
+                    .interactive_auth
+                    .authenticate(handle, host, credentials.username, credentials.password)
+                    .await?;
+                if !auth_result {
+                    return Err(fail(
+                        ConnectErrorKind::Auth,
+                        "Keyboard-interactive authentication failed",
+                    ));
+                }
+            }
+            SshAuthType::Agent => {
+                let auth_result = ssh_agent::authenticate(handle, credentials.username).await?;
+                if !auth_result {
+                    return Err(fail(
+                        ConnectErrorKind::Auth,
+                        "SSH agent authentication failed",
+                    ));
+                }
             }
         }
-
-        // Open a direct-tcpip channel to the target host through the jump host
-        let target_addr = format!("{}:{}", request.host, request.port);
-        let channel = jump_handle
-            .channel_open_direct_tcpip(
-                &request.host,
-                request.port as u32,
-                "127.0.0.1",
-                0,
-            )
-            .await?;
-
-        // Now connect to the target through the tunnel
-        let target_config = client::Config {
-            inactivity_timeout: Some(std::time::Duration::from_secs(3600)),
-            ..Default::default()
-        };
-        let target_config = Arc::new(target_config);
-
-        let _target_handler = SshClientHandler {
-            session_id: session_id.clone(),
-            data_tx: data_tx.clone(),
-            terminal_channel_id: Arc::new(RwLock::new(None)),
-        };
-
-        // Create a stream from the channel for the second SSH connection
-        // Note: This is a simplified implementation. In production, you'd need
-        // to properly bridge the channel I/O with the SSH client.
-
-        // For now, we'll use a simpler approach: execute ssh command on jump host
-        // This is more compatible and works in most cases
-
-        // Close the direct-tcpip channel as we'll use a different approach
-        let _ = channel.close().await;
-
-        // Open a session channel on jump host and execute ssh command
-        let jump_session = jump_handle.channel_open_session().await?;
-
-        // Request PTY on jump host
-        jump_session
-            .request_pty(
-                false,
-                "xterm-256color",
-                request.terminal_size.cols,
-                request.terminal_size.rows,
-                0,
-                0,
-                &[],
-            )
-            .await?;
-
-        // Execute ssh command to target
-        let ssh_cmd = format!(
-            "ssh -o StrictHostKeyChecking=no -p {} {}@{}",
-            request.port, request.username, request.host
-        );
-        jump_session.exec(false, ssh_cmd).await?;
-
-        session.handle = Some(jump_handle);
-        session.channel = Some(jump_session);
-        session.tx = Some(data_tx);
-        session.status = SessionStatus::Connected;
-
-        // Store session
-        self.sessions.write().await.insert(session_id.clone(), session);
-
-        Ok(session_id)
+        Ok(())
     }
 
     /// Reconnect a disconnected session
     pub async fn reconnect(
-        &self,
+        self: &Arc<Self>,
         session_id: &str,
-        data_tx: mpsc::UnboundedSender<Vec<u8>>,
+        data_tx: OutputSender,
     ) -> Result<String> {
         // Get the stored connection request
         let connect_request = {
@@ -460,61 +1168,101 @@ impl SshService {
                 .ok_or_else(|| anyhow!("No connection info stored for reconnection"))?
         };
 
+        let scrollback = self.session_scrollback(session_id).ok();
+
         // Remove old session
-        self.sessions.write().await.remove(session_id);
+        self.disconnect(session_id).await?;
+
+        // The new session continues the scrollback of the old one
+        if let Some(scrollback) = scrollback {
+            std::mem::swap(&mut *data_tx.scrollback().lock(), &mut *scrollback.lock());
+        }
 
         // Create new connection with same parameters
         match connect_request.auth_type.as_str() {
             "password" => self.connect_with_password(connect_request, data_tx).await,
             "key" => self.connect_with_key(connect_request, data_tx).await,
+            "interactive" => self.connect_interactive(connect_request, data_tx).await,
+            "agent" => self.connect_with_agent(connect_request, data_tx).await,
             _ => Err(anyhow!("Unsupported auth type for reconnection")),
         }
     }
 
     /// Send data to SSH session
     pub async fn send_data(&self, session_id: &str, data: &[u8]) -> Result<()> {
-        let sessions = self.sessions.read().await;
+        if self.local_shells.contains(session_id) {
+            return self.local_shells.write(session_id, data).await;
+        }
+        if self.telnet.contains(session_id) {
+            return self.telnet.send(session_id, data);
+        }
+        let sessions = self.sessions.read().await;
         let session = sessions
             .get(session_id)
             .ok_or_else(|| anyhow!("Session not found"))?;
 
-        if let Some(channel) = &session.channel {
-            channel.data(data).await?;
+        if let Some(terminal) = &session.terminal {
+            self.recordings.input(session_id, data);
+            terminal
+                .unbounded_send(TerminalInput::Data(data.to_vec()))
+                .map_err(|_| anyhow!("Terminal is closed"))?;
         }
         Ok(())
     }
 
     /// Resize terminal
     pub async fn resize_terminal(&self, session_id: &str, size: TerminalSize) -> Result<()> {
-        let sessions = self.sessions.read().await;
+        if self.local_shells.contains(session_id) {
+            return self.local_shells.resize(session_id, size);
+        }
+        if self.telnet.contains(session_id) {
+            return self.telnet.resize(session_id, size);
+        }
+        let mut sessions = self.sessions.write().await;
         let session = sessions
-            .get(session_id)
+            .get_mut(session_id)
             .ok_or_else(|| anyhow!("Session not found"))?;
 
-        if let Some(channel) = &session.channel {
-            channel
-                .window_change(size.cols, size.rows, 0, 0)
-                .await?;
+        if let Some(terminal) = &session.terminal {
+            terminal
+                .unbounded_send(TerminalInput::Resize(size))
+                .map_err(|_| anyhow!("Terminal is closed"))?;
+            session.terminal_size = size;
+            self.recordings.resize(session_id, size);
         }
         Ok(())
     }
 
     /// Disconnect SSH session
     pub async fn disconnect(&self, session_id: &str) -> Result<()> {
-        let mut sessions = self.sessions.write().await;
-        if let Some(mut session) = sessions.remove(session_id) {
+        self.scrollbacks.lock().remove(session_id);
+        if self.local_shells.close(session_id) || self.telnet.close(session_id) {
+            return Ok(());
+        }
+        let session = self.sessions.write().await.remove(session_id);
+        if let Some(mut session) = session {
             session.status = SessionStatus::Disconnected;
 
-            // Close channel
-            if let Some(channel) = session.channel.take() {
-                let _ = channel.close().await;
-            }
+            // Tunnels live exactly as long as the session
+            self.tunnels.close_session(session_id).await;
+            self.recordings.stop(session_id);
+            self.broadcasts.remove_session(session_id);
+            self.metrics.stop(session_id);
+
+            if let Some(connection) = session.connection.take() {
+                // Stop routing first so closing is not taken for the shell exiting
+                connection
+                    .routes
+                    .write()
+                    .retain(|_, route| route.session_id != session_id);
+
+                // Close channel
+                if let Some(terminal) = session.terminal.take() {
+                    let _ = terminal.unbounded_send(TerminalInput::Close);
+                }
 
-            // Close handle
-            if let Some(handle) = session.handle.take() {
-                let _ = handle
-                    .disconnect(Disconnect::ByApplication, "User disconnected", "")
-                    .await;
+                // Close the connection unless other sessions still use it
+                self.release_connection(session_id, &connection).await;
             }
         }
         Ok(())
@@ -532,6 +1280,9 @@ impl SshService {
 
     /// Check if session exists and is connected
     pub async fn is_connected(&self, session_id: &str) -> bool {
+        if self.local_shells.contains(session_id) || self.telnet.contains(session_id) {
+            return true;
+        }
         self.sessions
             .read()
             .await
@@ -540,6 +1291,99 @@ impl SshService {
             .unwrap_or(false)
     }
 
+    /// Start a shell on this machine in a new terminal session. Its output
+    /// goes to `data_tx` as that of SSH sessions does.
+    pub fn open_local_shell(
+        &self,
+        request: &LocalShellRequest,
+        data_tx: OutputSender,
+    ) -> Result<String> {
+        let scrollback = data_tx.scrollback();
+        let session_id = self.local_shells.open(request, data_tx)?;
+        self.scrollbacks
+            .lock()
+            .insert(session_id.clone(), scrollback);
+        Ok(session_id)
+    }
+
+    /// Shells installed on this machine
+    pub fn list_local_shells(&self) -> Vec<LocalShellInfo> {
+        local_shell::available_shells()
+    }
+
+    /// Open a telnet session. Its output goes to `data_tx` as that of SSH
+    /// sessions does.
+    pub async fn connect_telnet(
+        &self,
+        request: &TelnetConnectRequest,
+        data_tx: OutputSender,
+    ) -> Result<String> {
+        let scrollback = data_tx.scrollback();
+        let session_id = self.telnet.connect(request, data_tx).await?;
+        self.scrollbacks
+            .lock()
+            .insert(session_id.clone(), scrollback);
+        Ok(session_id)
+    }
+
+    /// Latest output of a terminal session, for a tab that reattaches to it
+    pub fn scrollback(&self, session_id: &str) -> Result<ScrollbackSnapshot> {
+        let snapshot = self.session_scrollback(session_id)?.lock().snapshot();
+        Ok(snapshot)
+    }
+
+    /// Search the scrollback text of a terminal session
+    pub fn search_scrollback(
+        &self,
+        request: &ScrollbackSearchRequest,
+    ) -> Result<Vec<ScrollbackMatch>> {
+        self.session_scrollback(&request.session_id)?
+            .lock()
+            .search(request)
+    }
+
+    /// Lines of the scrollback text of a terminal session
+    pub fn scrollback_lines(
+        &self,
+        session_id: &str,
+        start: u64,
+        count: usize,
+    ) -> Result<ScrollbackLines> {
+        let lines = self
+            .session_scrollback(session_id)?
+            .lock()
+            .lines(start, count);
+        Ok(lines)
+    }
+
+    /// Write the scrollback text of a terminal session to a file
+    pub async fn export_scrollback(&self, request: &ScrollbackExportRequest) -> Result<()> {
+        let title = match self.get_session_info(&request.session_id).await {
+            Some(info) => format!("{}@{}", info.username, info.host),
+            None => "Terminal".to_string(),
+        };
+        let contents = self
+            .session_scrollback(&request.session_id)?
+            .lock()
+            .export(request.format, &title);
+        tokio::fs::write(&request.path, contents)
+            .await
+            .map_err(|e| anyhow!("Failed to write {}: {}", request.path, e))
+    }
+
+    /// Forget the scrollback of a session once its output stream has ended
+    pub fn output_ended(&self, session_id: &str) {
+        self.scrollbacks.lock().remove(session_id);
+    }
+
+    fn session_scrollback(&self, session_id: &str) -> Result<SharedScrollback> {
+        self.scrollbacks
+            .lock()
+            .get(session_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Session not found"))
+    }
+
     /// Open a new channel for SFTP on an existing SSH connection
     /// Returns the channel ready for SFTP subsystem request
     pub async fn open_sftp_channel(&self, session_id: &str) -> Result<Channel<client::Msg>> {
@@ -552,17 +1396,22 @@ impl SshService {
             return Err(anyhow!("Session not connected"));
         }
 
-        let handle = session
-            .handle
+        let connection = session
+            .connection
             .as_ref()
             .ok_or_else(|| anyhow!("No handle available"))?;
 
-        let channel = handle.channel_open_session().await?;
+        let channel = connection
+            .handle
+            .read()
+            .await
+            .channel_open_session()
+            .await?;
         Ok(channel)
     }
 
-    /// Execute a command on the remote server and return output
-    pub async fn exec_command(&self, session_id: &str, command: &str) -> Result<String> {
+    /// Get the handle of a connected session for use outside the session map
+    async fn shared_handle(&self, session_id: &str) -> Result<SharedHandle> {
         let sessions = self.sessions.read().await;
         let session = sessions
             .get(session_id)
@@ -572,85 +1421,1370 @@ impl SshService {
             return Err(anyhow!("Session not connected"));
         }
 
-        let handle = session
-            .handle
+        session
+            .connection
             .as_ref()
-            .ok_or_else(|| anyhow!("No handle available"))?;
+            .map(|connection| connection.handle.clone())
+            .ok_or_else(|| anyhow!("No handle available"))
+    }
 
-        let mut channel = handle.channel_open_session().await?;
-        channel.exec(true, command).await?;
+    /// Open a local port forward (`ssh -L`) on an existing SSH connection
+    pub async fn create_local_tunnel(&self, request: LocalForwardRequest) -> Result<TunnelInfo> {
+        let handle = self.shared_handle(&request.session_id).await?;
+        self.tunnels.create_local(handle, request).await
+    }
 
-        let mut output = Vec::new();
-        loop {
-            match channel.wait().await {
-                Some(ChannelMsg::Data { data }) => {
-                    output.extend_from_slice(&data);
-                }
-                Some(ChannelMsg::ExtendedData { data, .. }) => {
-                    output.extend_from_slice(&data);
-                }
-                Some(ChannelMsg::Eof) | Some(ChannelMsg::Close) | None => break,
-                _ => {}
+    /// Open a remote port forward (`ssh -R`): the server listens and
+    /// connections are forwarded to a local address
+    pub async fn create_remote_tunnel(&self, request: RemoteForwardRequest) -> Result<TunnelInfo> {
+        let handle = self.shared_handle(&request.session_id).await?;
+        self.tunnels.create_remote(handle, request).await
+    }
+
+    /// Open a dynamic port forward (`ssh -D`): a local SOCKS5 server whose
+    /// connections are tunnelled through the SSH connection
+    pub async fn create_dynamic_tunnel(
+        &self,
+        request: DynamicForwardRequest,
+    ) -> Result<TunnelInfo> {
+        let handle = self.shared_handle(&request.session_id).await?;
+        self.tunnels.create_dynamic(handle, request).await
+    }
+
+    /// List tunnels, optionally only those of one session
+    pub fn list_tunnels(&self, session_id: Option<&str>) -> Vec<TunnelInfo> {
+        self.tunnels.list(session_id)
+    }
+
+    /// Close a tunnel
+    pub async fn close_tunnel(&self, tunnel_id: &str) -> Result<()> {
+        self.tunnels.close(tunnel_id).await
+    }
+
+    /// Start recording a connected session
+    pub async fn start_recording(
+        &self,
+        session_id: &str,
+        options: RecordingOptions,
+    ) -> Result<RecordingInfo> {
+        let sessions = self.sessions.read().await;
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+
+        if session.status != SessionStatus::Connected {
+            return Err(anyhow!("Session not connected"));
+        }
+
+        let term = session
+            .connect_request
+            .as_ref()
+            .map(|request| request.terminal.term.clone())
+            .unwrap_or_else(|| TerminalOptions::default().term);
+        self.recordings.start(
+            session_id,
+            format!("{}@{}", session.username, session.host),
+            session.terminal_size,
+            &term,
+            options.record_input,
+        )
+    }
+
+    /// Stop recording a session
+    pub fn stop_recording(&self, session_id: &str) -> Result<()> {
+        if !self.recordings.stop(session_id) {
+            return Err(anyhow!("Session is not being recorded"));
+        }
+        Ok(())
+    }
+
+    /// List stored recordings
+    pub fn list_recordings(&self) -> Result<Vec<RecordingInfo>> {
+        self.recordings.list()
+    }
+
+    /// Delete a stored recording
+    pub fn delete_recording(&self, recording_id: &str) -> Result<()> {
+        self.recordings.delete(recording_id)
+    }
+
+    /// Replay a recording, streaming its frames to `tx`
+    pub fn replay_recording(
+        &self,
+        recording_id: &str,
+        speed: f64,
+        tx: tokio::sync::mpsc::UnboundedSender<ReplayFrame>,
+    ) -> Result<String> {
+        self.recordings.replay(recording_id, speed, tx)
+    }
+
+    /// Stop a running replay
+    pub fn stop_replay(&self, replay_id: &str) -> Result<()> {
+        self.recordings.stop_replay(replay_id)
+    }
+
+    /// Create a broadcast group from existing sessions
+    pub async fn create_broadcast_group(
+        &self,
+        name: String,
+        session_ids: Vec<String>,
+    ) -> Result<BroadcastGroupInfo> {
+        {
+            let sessions = self.sessions.read().await;
+            if let Some(missing) = session_ids.iter().find(|id| !sessions.contains_key(*id)) {
+                return Err(anyhow!("Session not found: {}", missing));
             }
         }
+        Ok(self.broadcasts.create(name, session_ids))
+    }
 
-        Ok(String::from_utf8_lossy(&output).to_string())
+    /// Add a session to a broadcast group
+    pub async fn join_broadcast_group(
+        &self,
+        group_id: &str,
+        session_id: &str,
+    ) -> Result<BroadcastGroupInfo> {
+        if !self.sessions.read().await.contains_key(session_id) {
+            return Err(anyhow!("Session not found"));
+        }
+        self.broadcasts.join(group_id, session_id)
     }
 
-    /// Test SSH connection without creating a session
-    pub async fn test_connection(&self, request: &SshConnectRequest) -> Result<()> {
-        // Configure SSH client with shorter timeout for testing
-        let config = client::Config {
-            inactivity_timeout: Some(std::time::Duration::from_secs(10)),
-            ..Default::default()
+    /// Remove a session from a broadcast group
+    pub fn leave_broadcast_group(
+        &self,
+        group_id: &str,
+        session_id: &str,
+    ) -> Result<BroadcastGroupInfo> {
+        self.broadcasts.leave(group_id, session_id)
+    }
+
+    /// Delete a broadcast group; its sessions stay open
+    pub fn delete_broadcast_group(&self, group_id: &str) -> Result<()> {
+        self.broadcasts.delete(group_id)
+    }
+
+    /// List broadcast groups
+    pub fn list_broadcast_groups(&self) -> Vec<BroadcastGroupInfo> {
+        self.broadcasts.list()
+    }
+
+    /// Send data to every session of a broadcast group
+    pub async fn broadcast_data(&self, group_id: &str, data: &[u8]) -> Result<BroadcastReport> {
+        let members = self.broadcasts.members(group_id)?;
+        let results = futures::future::join_all(members.iter().map(|session_id| async move {
+            if !self.is_connected(session_id).await {
+                return Err(anyhow!("Session not connected"));
+            }
+            self.send_data(session_id, data).await
+        }))
+        .await;
+        Ok(broadcast::report(members, results))
+    }
+
+    /// Resize the terminal of every session of a broadcast group
+    pub async fn broadcast_resize(
+        &self,
+        group_id: &str,
+        size: TerminalSize,
+    ) -> Result<BroadcastReport> {
+        let members = self.broadcasts.members(group_id)?;
+        let mut results = Vec::with_capacity(members.len());
+        for session_id in &members {
+            results.push(self.resize_terminal(session_id, size).await);
+        }
+        Ok(broadcast::report(members, results))
+    }
+
+    /// Run a command on the connection of a session and wait for it to exit
+    pub async fn exec(&self, request: &ExecRequest) -> Result<ExecResult> {
+        let handle = self.shared_handle(&request.session_id).await?;
+        let channel = handle.read().await.channel_open_session().await?;
+        exec::run(channel, request, CancellationToken::new()).await
+    }
+
+    /// Run a command in the background, streaming its output to `tx`.
+    /// Returns the exec ID used to cancel it.
+    pub async fn exec_stream(
+        &self,
+        request: ExecRequest,
+        tx: tokio::sync::mpsc::UnboundedSender<ExecEvent>,
+    ) -> Result<String> {
+        let handle = self.shared_handle(&request.session_id).await?;
+        let channel = handle.read().await.channel_open_session().await?;
+        Ok(self.execs.spawn(channel, request, tx))
+    }
+
+    /// Cancel a streaming command
+    pub fn cancel_exec(&self, exec_id: &str) -> Result<()> {
+        self.execs.cancel(exec_id)
+    }
+
+    /// Run a command on many hosts in the background, sending each host's
+    /// result to `tx` as it finishes. Returns the job ID.
+    pub fn start_batch(
+        self: &Arc<Self>,
+        request: BatchJobRequest,
+        tx: tokio::sync::mpsc::UnboundedSender<BatchEvent>,
+    ) -> Result<String> {
+        if request.targets.is_empty() {
+            return Err(anyhow!("No hosts to run the command on"));
+        }
+        if let Some(target) = request
+            .targets
+            .iter()
+            .find(|t| t.session_id.is_some() == t.connection.is_some())
+        {
+            return Err(anyhow!(
+                "Host {} needs either a session or connection settings",
+                target.name
+            ));
+        }
+
+        let (job_id, cancel) = self
+            .batches
+            .start(&request.command, request.targets.len())?;
+        tokio::spawn(run_batch(self.clone(), job_id.clone(), request, cancel, tx));
+        Ok(job_id)
+    }
+
+    /// Cancel a running batch job
+    pub fn cancel_batch(&self, job_id: &str) -> Result<()> {
+        self.batches.cancel(job_id)
+    }
+
+    /// List running and stored batch jobs
+    pub fn list_batches(&self) -> Result<Vec<BatchJobSummary>> {
+        self.batches.list()
+    }
+
+    /// Get a batch job with its host results
+    pub fn get_batch(&self, job_id: &str) -> Result<BatchJob> {
+        self.batches.get(job_id)
+    }
+
+    /// Delete a stored batch job
+    pub fn delete_batch(&self, job_id: &str) -> Result<()> {
+        self.batches.delete(job_id)
+    }
+
+    /// Start sampling the host metrics of a session over its own exec
+    /// channel. A collector that is already running is kept as it is.
+    pub async fn start_metrics(&self, request: &MetricsRequest) -> Result<MetricsMonitorInfo> {
+        if let Some(info) = self.metrics.info(&request.session_id) {
+            return Ok(info);
+        }
+        let handle = self.shared_handle(&request.session_id).await?;
+        let channel = handle.read().await.channel_open_session().await?;
+        Ok(self
+            .metrics
+            .spawn(&request.session_id, request.interval_secs, channel))
+    }
+
+    /// Stop the metrics collector of a session
+    pub fn stop_metrics(&self, session_id: &str) -> Result<()> {
+        if !self.metrics.stop(session_id) {
+            return Err(anyhow!("Metrics are not being collected for this session"));
+        }
+        Ok(())
+    }
+
+    /// Recent metrics samples of a session, oldest first
+    pub fn metrics_history(&self, session_id: &str) -> Result<Vec<MetricsSample>> {
+        self.metrics.history(session_id)
+    }
+
+    /// Run the command of a batch job on one target
+    async fn run_batch_target(
+        self: &Arc<Self>,
+        index: usize,
+        target: &BatchTarget,
+        request: &BatchJobRequest,
+        cancel: &CancellationToken,
+    ) -> BatchHostResult {
+        let host = match (&target.connection, &target.session_id) {
+            (Some(connection), _) => connection.host.clone(),
+            (None, Some(session_id)) => self
+                .get_session_info(session_id)
+                .await
+                .map(|info| info.host)
+                .unwrap_or_default(),
+            (None, None) => String::new(),
+        };
+
+        let outcome = if cancel.is_cancelled() {
+            Err(anyhow!("Cancelled before it ran"))
+        } else {
+            self.batch_exec(target, request, cancel).await
         };
-        let config = Arc::new(config);
-
-        // Create a dummy handler for testing
-        let (dummy_tx, _dummy_rx) = mpsc::unbounded::<Vec<u8>>();
-        let handler = SshClientHandler {
-            session_id: "test".to_string(),
-            data_tx: dummy_tx,
-            terminal_channel_id: Arc::new(RwLock::new(None)),
+        let (result, error) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(e) => (None, Some(e.to_string())),
         };
+        BatchHostResult {
+            index,
+            name: target.name.clone(),
+            host,
+            result,
+            error,
+        }
+    }
 
-        // Connect to server
-        let addr = format!("{}:{}", request.host, request.port);
-        let mut handle = client::connect(config, addr, handler).await?;
+    /// Run a batch command on the session or an on-demand connection of a
+    /// target, within the job's per-host timeout
+    async fn batch_exec(
+        self: &Arc<Self>,
+        target: &BatchTarget,
+        request: &BatchJobRequest,
+        cancel: &CancellationToken,
+    ) -> Result<ExecResult> {
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(request.timeout_ms);
+        // Whatever connecting took is deducted from the command's time
+        let exec_request = |session_id: &str| ExecRequest {
+            session_id: session_id.to_string(),
+            command: request.command.clone(),
+            stdin: request.stdin.clone(),
+            timeout_ms: Some(
+                deadline
+                    .saturating_duration_since(tokio::time::Instant::now())
+                    .as_millis() as u64,
+            ),
+        };
 
-        // Authenticate based on auth type
-        let auth_result = match request.auth_type.as_str() {
-            "password" => {
-                let password = request
-                    .password
-                    .as_ref()
-                    .ok_or_else(|| anyhow!("Password is required"))?;
-                handle.authenticate_password(&request.username, password).await?
-            }
-            "key" => {
-                let private_key_str = request
-                    .private_key
-                    .as_ref()
-                    .ok_or_else(|| anyhow!("Private key is required"))?;
-                let key_pair = if let Some(passphrase) = &request.passphrase {
-                    decode_secret_key(private_key_str, Some(passphrase))?
-                } else {
-                    decode_secret_key(private_key_str, None)?
-                };
-                handle.authenticate_publickey(&request.username, Arc::new(key_pair)).await?
-            }
-            _ => return Err(anyhow!("Unsupported authentication type")),
+        if let Some(session_id) = &target.session_id {
+            let handle = self.shared_handle(session_id).await?;
+            let channel = handle.read().await.channel_open_session().await?;
+            return exec::run(channel, &exec_request(session_id), cancel.clone()).await;
+        }
+        let connect_request = target
+            .connection
+            .as_ref()
+            .ok_or_else(|| anyhow!("No connection settings"))?;
+
+        // The lease keeps the connection open while the command runs, like a session would
+        let lease = format!("batch-{}", Uuid::new_v4());
+        let connection = tokio::select! {
+            result = tokio::time::timeout_at(
+                deadline,
+                self.acquire_connection(&lease, connect_request),
+            ) => result.map_err(|_| anyhow!("Timed out connecting"))??,
+            _ = cancel.cancelled() => return Err(anyhow!("Cancelled while connecting")),
         };
 
-        if !auth_result {
-            return Err(anyhow!("Authentication failed"));
+        let result = async {
+            let channel = connection
+                .handle
+                .read()
+                .await
+                .channel_open_session()
+                .await?;
+            exec::run(channel, &exec_request(&lease), cancel.clone()).await
         }
+        .await;
+        self.release_connection(&lease, &connection).await;
+        result
+    }
+
+    /// Test SSH connection without creating a session
+    pub async fn test_connection(&self, request: &SshConnectRequest) -> Result<()> {
+        let config = test_config(request)?;
+
+        // Create a handler without terminals for testing
+        let handler = self.new_handler(ChannelRoutes::default(), &request.host, request.port);
+
+        let (handle, mut jump_handles, _) = self.establish(request, config, handler).await?;
 
         // Disconnect immediately after successful test
         let _ = handle
             .disconnect(Disconnect::ByApplication, "Connection test completed", "")
             .await;
+        while let Some(jump_handle) = jump_handles.pop() {
+            let _ = jump_handle
+                .disconnect(Disconnect::ByApplication, "Connection test completed", "")
+                .await;
+        }
 
         Ok(())
     }
+
+    /// Test SSH connection one stage at a time, reporting on each. Stops at
+    /// the first stage that fails.
+    pub async fn diagnose_connection(&self, request: &SshConnectRequest) -> ConnectionDiagnostics {
+        let mut report = Report::new(&request.host, request.port);
+        let mut handles = Vec::new();
+        self.run_diagnostics(request, &mut report, &mut handles)
+            .await;
+
+        while let Some(handle) = handles.pop() {
+            let _ = handle
+                .disconnect(Disconnect::ByApplication, "Connection test completed", "")
+                .await;
+        }
+        report.finish()
+    }
+
+    /// Run the stages of `diagnose_connection`, leaving the handles it opened
+    /// in `handles`. Returns `None` once a stage has failed.
+    async fn run_diagnostics(
+        &self,
+        request: &SshConnectRequest,
+        report: &mut Report,
+        handles: &mut Vec<client::Handle<SshClientHandler>>,
+    ) -> Option<()> {
+        let config = test_config(request).map_err(|e| report.fail(&e)).ok()?;
+        let credentials = Credentials::from_request(request)
+            .map_err(|e| report.fail(&e))
+            .ok()?;
+
+        if request.jump_hosts.is_empty() {
+            report.skip(DiagnosticStage::JumpHosts, "No jump hosts");
+        } else {
+            let jump_handles = report
+                .run(
+                    DiagnosticStage::JumpHosts,
+                    self.open_jump_hosts(request, &config),
+                    |jump_handles, _| {
+                        format!("Connected through {} jump hosts", jump_handles.len())
+                    },
+                )
+                .await?;
+            handles.extend(jump_handles);
+        }
+
+        let (host, port) = (request.host.as_str(), request.port);
+        let handler = self.new_handler(ChannelRoutes::default(), host, port);
+        let handshake = Handshake::new(&handler);
+        let banner = |_: &client::Handle<SshClientHandler>, _: Duration| {
+            handshake.capture.version().unwrap_or_default()
+        };
+        let handle = if let Some(previous) = handles.last() {
+            report.skip(DiagnosticStage::Resolve, "Resolved by the last jump host");
+            let channel = report
+                .run(
+                    DiagnosticStage::TcpConnect,
+                    open_tunnel(previous, host, port),
+                    |_, elapsed| {
+                        format!(
+                            "Tunnel opened through the last jump host in {} ms",
+                            elapsed.as_millis()
+                        )
+                    },
+                )
+                .await?;
+            let stream = channel.into_stream();
+            report
+                .run(
+                    DiagnosticStage::Banner,
+                    connect_ssh(config.clone(), stream, handler, &handshake),
+                    banner,
+                )
+                .await?
+        } else if let Some(proxy) = &request.proxy {
+            report.skip(DiagnosticStage::Resolve, "Resolved by the proxy");
+            let stream = report
+                .run(
+                    DiagnosticStage::TcpConnect,
+                    connect_proxy(proxy, host, port),
+                    |_, elapsed| {
+                        format!(
+                            "Connected through proxy {}:{} in {} ms",
+                            proxy.host,
+                            proxy.port,
+                            elapsed.as_millis()
+                        )
+                    },
+                )
+                .await?;
+            report
+                .run(
+                    DiagnosticStage::Banner,
+                    connect_ssh(config.clone(), stream, handler, &handshake),
+                    banner,
+                )
+                .await?
+        } else {
+            let addresses = report
+                .run(
+                    DiagnosticStage::Resolve,
+                    diagnostics::resolve(host, port),
+                    |addresses, _| {
+                        let addresses: Vec<String> = addresses
+                            .iter()
+                            .map(|address| address.ip().to_string())
+                            .collect();
+                        format!("Resolved to {}", addresses.join(", "))
+                    },
+                )
+                .await?;
+            let (stream, _) = report
+                .run(
+                    DiagnosticStage::TcpConnect,
+                    diagnostics::connect_tcp(&addresses),
+                    |(_, address), elapsed| {
+                        format!("Connected to {} in {} ms", address, elapsed.as_millis())
+                    },
+                )
+                .await?;
+            report
+                .run(
+                    DiagnosticStage::Banner,
+                    connect_ssh(config.clone(), stream, handler, &handshake),
+                    banner,
+                )
+                .await?
+        };
+        report.diagnostics_mut().server_version = handshake.capture.version();
+        handles.push(handle);
+        let handle = handles.last_mut()?;
+
+        let algorithms = report
+            .run(
+                DiagnosticStage::KeyExchange,
+                handshake.negotiated(&config),
+                |algorithms, _| {
+                    let mut detail = format!(
+                        "{}, {}, {}",
+                        algorithms.kex, algorithms.host_key, algorithms.cipher_client_to_server
+                    );
+                    if !algorithms.weak.is_empty() {
+                        detail += &format!(" (weak: {})", algorithms.weak.join(", "));
+                    }
+                    detail
+                },
+            )
+            .await?;
+        report.diagnostics_mut().algorithms = Some(algorithms);
+
+        // The first request waits for the key exchange, host key check included
+        let accepted = report
+            .run(
+                DiagnosticStage::HostKey,
+                async {
+                    handle
+                        .authenticate_none(request.username.as_str())
+                        .await
+                        .map_err(|e| handshake.explain(e.into(), &config))
+                },
+                |_, _| "Host key trusted".to_string(),
+            )
+            .await?;
+
+        if accepted {
+            report.pass(
+                DiagnosticStage::Authentication,
+                "The server accepts the user without authentication",
+            );
+            return Some(());
+        }
+        report
+            .run(
+                DiagnosticStage::Authentication,
+                self.authenticate(handle, host, &credentials),
+                |_, elapsed| {
+                    format!(
+                        "Authenticated as {} in {} ms",
+                        request.username,
+                        elapsed.as_millis()
+                    )
+                },
+            )
+            .await
+    }
+}
+
+/// Work through the targets of a batch job, a limited number at a time
+async fn run_batch(
+    service: Arc<SshService>,
+    job_id: String,
+    request: BatchJobRequest,
+    cancel: CancellationToken,
+    tx: tokio::sync::mpsc::UnboundedSender<BatchEvent>,
+) {
+    let total = request.targets.len();
+    let concurrency = request.concurrency.max(1);
+    let request = Arc::new(request);
+    // Host futures own what they use so the job can run as its own task
+    let mut results = futures::stream::iter(request.targets.clone().into_iter().enumerate())
+        .map(|(index, target)| {
+            let service = service.clone();
+            let request = request.clone();
+            let cancel = cancel.clone();
+            async move {
+                service
+                    .run_batch_target(index, &target, &request, &cancel)
+                    .await
+            }
+        })
+        .buffer_unordered(concurrency);
+
+    while let Some(result) = results.next().await {
+        let completed = service.batches.record(&job_id, result.clone());
+        let _ = tx.send(BatchEvent::Progress(BatchProgressEvent {
+            job_id: job_id.clone(),
+            completed,
+            total,
+            result,
+        }));
+    }
+    drop(results);
+
+    if let Some(summary) = service.batches.finish(&job_id) {
+        let _ = tx.send(BatchEvent::Finished(summary));
+    }
+}
+
+/// Follow the lifecycle events of a connection until it is closed for good
+async fn supervise(
+    service: Arc<SshService>,
+    connection: Arc<SshConnection>,
+    mut events: mpsc::UnboundedReceiver<ConnectionEvent>,
+) {
+    while let Some(event) = events.next().await {
+        match event {
+            // The shell ended, and so does its session
+            ConnectionEvent::ChannelClosed(session_id) => {
+                let _ = service.disconnect(&session_id).await;
+            }
+            ConnectionEvent::Closed(reason) => {
+                match service.connection_lost(&connection, reason).await {
+                    Some(next) => events = next,
+                    None => break,
+                }
+            }
+        }
+    }
+}
+
+/// Credentials used to authenticate one hop of a connection
+struct Credentials<'a> {
+    username: &'a str,
+    auth_type: SshAuthType,
+    password: Option<&'a str>,
+    private_key: Option<&'a str>,
+    passphrase: Option<&'a str>,
+    /// OpenSSH certificate presented with the private key
+    certificate: Option<&'a str>,
+}
+
+impl<'a> Credentials<'a> {
+    fn from_request(request: &'a SshConnectRequest) -> Result<Self> {
+        let auth_type = match request.auth_type.as_str() {
+            "password" => SshAuthType::Password,
+            "key" => SshAuthType::Key,
+            "interactive" => SshAuthType::Interactive,
+            "agent" => SshAuthType::Agent,
+            _ => {
+                return Err(fail(
+                    ConnectErrorKind::Config,
+                    "Unsupported authentication type",
+                ))
+            }
+        };
+        Ok(Self {
+            username: &request.username,
+            auth_type,
+            password: request.password.as_deref(),
+            private_key: request.private_key.as_deref(),
+            passphrase: request.passphrase.as_deref(),
+            certificate: request.certificate.as_deref(),
+        })
+    }
+
+    fn from_jump_host(jump: &'a JumpHostConfig) -> Self {
+        Self {
+            username: &jump.username,
+            auth_type: jump.auth_type,
+            password: jump.password.as_deref(),
+            private_key: jump.private_key.as_deref(),
+            passphrase: jump.passphrase.as_deref(),
+            certificate: jump.certificate.as_deref(),
+        }
+    }
+}
+
+/// Client configuration for interactive sessions
+fn client_config(request: &SshConnectRequest) -> Result<Arc<client::Config>> {
+    let keepalive = &request.keepalive;
+    let config = client::Config {
+        inactivity_timeout: Some(Duration::from_secs(3600)),
+        keepalive_interval: (keepalive.interval > 0)
+            .then(|| Duration::from_secs(keepalive.interval)),
+        keepalive_max: keepalive.max_missed,
+        preferred: algorithms::preferred(&request.algorithms)?,
+        ..Default::default()
+    };
+    Ok(Arc::new(config))
+}
+
+/// Client configuration for connection tests, with a shorter timeout
+fn test_config(request: &SshConnectRequest) -> Result<Arc<client::Config>> {
+    let config = client::Config {
+        inactivity_timeout: Some(Duration::from_secs(10)),
+        preferred: algorithms::preferred(&request.algorithms)?,
+        ..Default::default()
+    };
+    Ok(Arc::new(config))
+}
+
+/// Open the terminal channel of a session on `connection` and request its
+/// PTY. The shell is left for the caller to start.
+async fn open_terminal(
+    connection: &SshConnection,
+    route: ChannelRoute,
+    size: TerminalSize,
+    terminal: &TerminalOptions,
+) -> Result<Channel<client::Msg>> {
+    let modes = pty_modes(&terminal.pty_modes)?;
+
+    // Open a shell channel
+    let channel = connection
+        .handle
+        .read()
+        .await
+        .channel_open_session()
+        .await?;
+
+    // Route the channel's data to its session
+    let agent_forwarding = route.agent_forwarding;
+    connection.routes.write().insert(channel.id(), route);
+
+    // Ask the server to forward the local ssh-agent to this session
+    if agent_forwarding {
+        channel.agent_forward(false).await?;
+    }
+
+    // Request PTY
+    channel
+        .request_pty(false, &terminal.term, size.cols, size.rows, 0, 0, &modes)
+        .await?;
+
+    // Servers drop variables their AcceptEnv does not allow, so failures are ignored
+    for (name, value) in &terminal.env {
+        let _ = channel.set_env(false, name.as_str(), value.as_str()).await;
+    }
+
+    Ok(channel)
+}
+
+/// Read a terminal channel's output into `output` and write the session's
+/// input to it until the channel closes. While the terminal is behind, the
+/// channel is not read but input still goes through, so Ctrl-C can stop a
+/// flood. Output left in the channel when the session lets go of it (its
+/// connection was lost) is still passed on.
+async fn run_terminal(
+    mut channel: Channel<client::Msg>,
+    mut input: mpsc::UnboundedReceiver<TerminalInput>,
+    output: OutputSender,
+    recordings: Arc<RecordingManager>,
+    session_id: String,
+) {
+    let mut input_open = true;
+    loop {
+        tokio::select! {
+            message = channel.wait() => {
+                let data = match message {
+                    Some(ChannelMsg::Data { data })
+                    | Some(ChannelMsg::ExtendedData { data, .. }) => data,
+                    Some(_) => continue,
+                    None => return,
+                };
+                recordings.output(&session_id, &data);
+                let send = output.send(data.to_vec());
+                tokio::pin!(send);
+                loop {
+                    tokio::select! {
+                        sent = &mut send => {
+                            if sent {
+                                break;
+                            }
+                            // Nobody shows the output any more
+                            let _ = channel.close().await;
+                            return;
+                        }
+                        next = input.next(), if input_open => match next {
+                            Some(next) => {
+                                if !write_terminal(&channel, next).await {
+                                    return;
+                                }
+                            }
+                            None => input_open = false,
+                        },
+                    }
+                }
+            }
+            next = input.next(), if input_open => match next {
+                Some(next) => {
+                    if !write_terminal(&channel, next).await {
+                        return;
+                    }
+                }
+                None => input_open = false,
+            },
+        }
+    }
+}
+
+/// Pass input on to a terminal channel. Returns false once it is closed.
+async fn write_terminal(channel: &Channel<client::Msg>, input: TerminalInput) -> bool {
+    let result = match input {
+        TerminalInput::Data(data) => channel.data(&data[..]).await,
+        TerminalInput::Resize(size) => channel.window_change(size.cols, size.rows, 0, 0).await,
+        TerminalInput::Close => {
+            let _ = channel.close().await;
+            return false;
+        }
+    };
+    if let Err(e) = result {
+        log::warn!(
+            "Failed to write to terminal channel {}: {}",
+            channel.id(),
+            e
+        );
+    }
+    true
+}
+
+/// Start the shell of a terminal channel and type the startup commands.
+/// The PTY buffers them until the shell reads its input.
+async fn start_shell(channel: &Channel<client::Msg>, terminal: &TerminalOptions) -> Result<()> {
+    channel.request_shell(false).await?;
+    for command in &terminal.startup_commands {
+        channel.data(format!("{}\n", command).as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// PTY mode opcodes by name (RFC 4254 section 8, IUTF8 from RFC 8160)
+const PTY_MODES: &[(&str, u8)] = &[
+    ("VINTR", 1),
+    ("VQUIT", 2),
+    ("VERASE", 3),
+    ("VKILL", 4),
+    ("VEOF", 5),
+    ("VEOL", 6),
+    ("VEOL2", 7),
+    ("VSTART", 8),
+    ("VSTOP", 9),
+    ("VSUSP", 10),
+    ("VDSUSP", 11),
+    ("VREPRINT", 12),
+    ("VWERASE", 13),
+    ("VLNEXT", 14),
+    ("VFLUSH", 15),
+    ("VSWTCH", 16),
+    ("VSTATUS", 17),
+    ("VDISCARD", 18),
+    ("IGNPAR", 30),
+    ("PARMRK", 31),
+    ("INPCK", 32),
+    ("ISTRIP", 33),
+    ("INLCR", 34),
+    ("IGNCR", 35),
+    ("ICRNL", 36),
+    ("IUCLC", 37),
+    ("IXON", 38),
+    ("IXANY", 39),
+    ("IXOFF", 40),
+    ("IMAXBEL", 41),
+    ("IUTF8", 42),
+    ("ISIG", 50),
+    ("ICANON", 51),
+    ("XCASE", 52),
+    ("ECHO", 53),
+    ("ECHOE", 54),
+    ("ECHOK", 55),
+    ("ECHONL", 56),
+    ("NOFLSH", 57),
+    ("TOSTOP", 58),
+    ("IEXTEN", 59),
+    ("ECHOCTL", 60),
+    ("ECHOKE", 61),
+    ("PENDIN", 62),
+    ("OPOST", 70),
+    ("OLCUC", 71),
+    ("ONLCR", 72),
+    ("OCRNL", 73),
+    ("ONOCR", 74),
+    ("ONLRET", 75),
+    ("CS7", 90),
+    ("CS8", 91),
+    ("PARENB", 92),
+    ("PARODD", 93),
+    ("TTY_OP_ISPEED", 128),
+    ("TTY_OP_OSPEED", 129),
+];
+
+/// Resolve PTY modes given by name
+fn pty_modes(modes: &BTreeMap<String, u32>) -> Result<Vec<(Pty, u32)>> {
+    modes
+        .iter()
+        .map(|(name, value)| {
+            let mode = PTY_MODES
+                .iter()
+                .find(|(mode, _)| mode.eq_ignore_ascii_case(name))
+                .and_then(|(_, opcode)| Pty::from_u8(*opcode))
+                .ok_or_else(|| {
+                    fail(
+                        ConnectErrorKind::Config,
+                        format!("Unknown PTY mode {}", name),
+                    )
+                })?;
+            Ok((mode, *value))
+        })
+        .collect()
+}
+
+/// Time allowed for the server's version line and algorithm lists
+const BANNER_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Open an SSH transport to `host:port`, either through a direct-tcpip channel
+/// of the previous hop or over TCP. The proxy only applies to the first hop,
+/// i.e. when there is no previous hop. The key exchange carries on in the
+/// background; the returned `Handshake` explains requests that fail with it.
+async fn open_transport(
+    via: Option<&client::Handle<SshClientHandler>>,
+    proxy: Option<&ProxyConfig>,
+    host: &str,
+    port: u16,
+    config: Arc<client::Config>,
+    handler: SshClientHandler,
+) -> Result<(client::Handle<SshClientHandler>, Handshake)> {
+    let handshake = Handshake::new(&handler);
+    let handle = match via {
+        Some(previous) => {
+            let channel = open_tunnel(previous, host, port).await?;
+            connect_ssh(config, channel.into_stream(), handler, &handshake).await?
+        }
+        None => {
+            let stream = match proxy {
+                Some(proxy) => connect_proxy(proxy, host, port).await?,
+                None => {
+                    let addresses = diagnostics::resolve(host, port).await?;
+                    diagnostics::connect_tcp(&addresses).await?.0
+                }
+            };
+            connect_ssh(config, stream, handler, &handshake).await?
+        }
+    };
+    Ok((handle, handshake))
+}
+
+/// Open a direct-tcpip channel to `host:port` through the previous hop
+async fn open_tunnel(
+    previous: &client::Handle<SshClientHandler>,
+    host: &str,
+    port: u16,
+) -> Result<Channel<client::Msg>> {
+    previous
+        .channel_open_direct_tcpip(host, port as u32, "127.0.0.1", 0)
+        .await
+        .map_err(|e| {
+            fail(
+                ConnectErrorKind::Unreachable,
+                format!("Could not open tunnel to {}:{}: {}", host, port, e),
+            )
+        })
+}
+
+async fn connect_proxy(proxy: &ProxyConfig, host: &str, port: u16) -> Result<TcpStream> {
+    connect_via_proxy(proxy, host, port)
+        .await
+        .map_err(|e| fail(ConnectErrorKind::Proxy, e.to_string()))
+}
+
+/// Start the SSH protocol on `stream`. Returns once the server's version line
+/// has been read.
+async fn connect_ssh<S>(
+    config: Arc<client::Config>,
+    stream: S,
+    handler: SshClientHandler,
+    handshake: &Handshake,
+) -> Result<client::Handle<SshClientHandler>>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    let connect = client::connect_stream(config, handshake.capture.wrap(stream), handler);
+    match tokio::time::timeout(BANNER_TIMEOUT, connect).await {
+        Ok(Ok(handle)) => Ok(handle),
+        Ok(Err(e)) => {
+            // A peer that answers with anything but SSH is not an SSH server
+            let error = diagnostics::classify(&e);
+            if error.kind == ConnectErrorKind::Other {
+                return Err(fail(
+                    ConnectErrorKind::Protocol,
+                    format!("Not an SSH server: {}", error.message),
+                ));
+            }
+            Err(e)
+        }
+        Err(_) => Err(fail(
+            ConnectErrorKind::Timeout,
+            format!(
+                "The server sent no SSH version within {} s",
+                BANNER_TIMEOUT.as_secs()
+            ),
+        )),
+    }
+}
+
+/// Follows the key exchange russh runs in the background after connecting
+struct Handshake {
+    capture: KexCapture,
+    host_key_error: Arc<parking_lot::Mutex<Option<String>>>,
+}
+
+impl Handshake {
+    fn new(handler: &SshClientHandler) -> Self {
+        Self {
+            capture: KexCapture::default(),
+            host_key_error: handler.host_key_error.clone(),
+        }
+    }
+
+    /// Wait for the server's algorithm lists and negotiate with them
+    async fn negotiated(&self, config: &client::Config) -> Result<NegotiatedAlgorithms> {
+        let received = tokio::time::timeout(BANNER_TIMEOUT, self.capture.wait()).await;
+        if received.is_err() {
+            return Err(fail(
+                ConnectErrorKind::Timeout,
+                "The server did not start the key exchange",
+            ));
+        }
+        let server = self.capture.server().ok_or_else(|| {
+            fail(
+                ConnectErrorKind::Protocol,
+                "The server did not start the key exchange",
+            )
+        })?;
+        algorithms::negotiate(&KexInit::client(&config.preferred), &server)
+            .map_err(|message| fail(ConnectErrorKind::KeyExchange, message))
+    }
+
+    /// Replace the error of a request on the connection by the failed key
+    /// exchange behind it, if that is what ended the connection. russh only
+    /// reports that the connection is gone.
+    fn explain(&self, error: anyhow::Error, config: &client::Config) -> anyhow::Error {
+        if let Some(message) = self.host_key_error.lock().clone() {
+            return fail(ConnectErrorKind::HostKey, message);
+        }
+        if let Some(server) = self.capture.server() {
+            if let Err(message) =
+                algorithms::negotiate(&KexInit::client(&config.preferred), &server)
+            {
+                return fail(ConnectErrorKind::KeyExchange, message);
+            }
+        }
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{JumpHostConfig, ProxyConfig, ProxyType, SshAuthType};
+    use crate::services::terminal_output;
+    use crate::services::test_server::TestServer;
+
+    fn request() -> SshConnectRequest {
+        serde_json::from_value(serde_json::json!({
+            "connectionId": "web",
+            "host": "web.example.com",
+            "port": 22,
+            "username": "ops",
+            "authType": "password",
+            "password": "secret",
+        }))
+        .unwrap()
+    }
+
+    fn jump_host(host: &str) -> JumpHostConfig {
+        JumpHostConfig {
+            host: host.to_string(),
+            port: 22,
+            username: "ops".to_string(),
+            auth_type: SshAuthType::Password,
+            password: Some("hop".to_string()),
+            private_key: None,
+            passphrase: None,
+            certificate: None,
+        }
+    }
+
+    #[test]
+    fn test_connection_key() {
+        let key = ConnectionKey::new(&request());
+        let mut same = request();
+        same.host = "WEB.example.com".to_string();
+        same.connection_id = "other".to_string();
+        assert_eq!(ConnectionKey::new(&same), key);
+
+        // Another identity
+        let mut other = request();
+        other.password = Some("other".to_string());
+        assert_ne!(ConnectionKey::new(&other), key);
+        let mut other = request();
+        other.auth_type = "agent".to_string();
+        assert_ne!(ConnectionKey::new(&other), key);
+
+        // Another route
+        let mut hops = request();
+        hops.jump_hosts = vec![jump_host("bastion-a"), jump_host("bastion-b")];
+        let hops_key = ConnectionKey::new(&hops);
+        assert_ne!(hops_key, key);
+        assert_eq!(hops_key.route, ["ops@bastion-a:22", "ops@bastion-b:22"]);
+        let mut reversed = hops.clone();
+        reversed.jump_hosts.reverse();
+        assert_ne!(ConnectionKey::new(&reversed), hops_key);
+        let mut hop_password = hops.clone();
+        hop_password.jump_hosts[1].password = Some("other".to_string());
+        assert_ne!(ConnectionKey::new(&hop_password), hops_key);
+
+        let mut proxied = request();
+        proxied.proxy = Some(ProxyConfig {
+            proxy_type: ProxyType::Socks5,
+            host: "proxy".to_string(),
+            port: 1080,
+            username: None,
+            password: None,
+        });
+        let proxied_key = ConnectionKey::new(&proxied);
+        assert_ne!(proxied_key, key);
+        let mut http = proxied.clone();
+        http.proxy.as_mut().unwrap().proxy_type = ProxyType::Http;
+        assert_ne!(ConnectionKey::new(&http), proxied_key);
+
+        // Fields do not run into each other
+        let mut split = request();
+        split.password = Some("sec".to_string());
+        split.private_key = Some("ret".to_string());
+        assert_ne!(ConnectionKey::new(&split), key);
+
+        // Other connection settings
+        let mut legacy = request();
+        legacy.algorithms.kex = vec!["diffie-hellman-group14-sha1".to_string()];
+        assert_ne!(ConnectionKey::new(&legacy), key);
+        let mut keepalive = request();
+        keepalive.keepalive.interval = 5;
+        assert_ne!(ConnectionKey::new(&keepalive), key);
+        let mut reconnect = request();
+        reconnect.auto_reconnect = Some(ReconnectPolicy {
+            max_attempts: 3,
+            initial_delay_ms: 1000,
+            max_delay_ms: 10_000,
+        });
+        assert_ne!(ConnectionKey::new(&reconnect), key);
+        let mut agent = request();
+        agent.agent_forwarding = true;
+        assert_ne!(ConnectionKey::new(&agent), key);
+    }
+
+    #[tokio::test]
+    async fn test_connection_pool() {
+        let server = TestServer::start().await;
+        let service = Arc::new(SshService::new());
+        server.trust(&service);
+
+        let connect = || async {
+            let (tx, _rx) = terminal_output::channel();
+            service
+                .connect_with_password(server.request(), tx)
+                .await
+                .unwrap()
+        };
+        let first = connect().await;
+        let second = connect().await;
+        assert_ne!(first, second);
+
+        // Both sessions use one connection
+        assert_eq!(server.accepted(), 1);
+        let connection = |session_id: &str| {
+            let service = service.clone();
+            let session_id = session_id.to_string();
+            async move {
+                service.sessions.read().await[&session_id]
+                    .connection
+                    .clone()
+                    .unwrap()
+            }
+        };
+        let shared = connection(&first).await;
+        assert!(Arc::ptr_eq(&shared, &connection(&second).await));
+        assert_eq!(shared.sessions.lock().len(), 2);
+
+        // Releasing one session keeps the connection for the other
+        service.disconnect(&first).await.unwrap();
+        assert_eq!(shared.sessions.lock().len(), 1);
+        assert!(!shared.handle.read().await.is_closed());
+        let pooled = service.pool.lock()[&shared.key].clone();
+        assert!(pooled.lock().await.is_some());
+        assert_eq!(server.open(), 1);
+
+        // A new session joins it again
+        let third = connect().await;
+        assert!(Arc::ptr_eq(&shared, &connection(&third).await));
+        assert_eq!(server.accepted(), 1);
+
+        // Releasing the last session closes it
+        service.disconnect(&second).await.unwrap();
+        service.disconnect(&third).await.unwrap();
+        assert!(shared.sessions.lock().is_empty());
+        assert!(pooled.lock().await.is_none());
+        server.wait_open(0).await;
+
+        // ...and the next session connects anew
+        let fourth = connect().await;
+        assert_eq!(server.accepted(), 2);
+        assert!(!Arc::ptr_eq(&shared, &connection(&fourth).await));
+    }
+
+    #[tokio::test]
+    async fn test_connection_pool_reconnecting() {
+        let server = TestServer::start().await;
+        let service = Arc::new(SshService::new());
+        server.trust(&service);
+        let mut request = server.request();
+        request.auto_reconnect = Some(ReconnectPolicy {
+            max_attempts: 1,
+            initial_delay_ms: 60_000,
+            max_delay_ms: 60_000,
+        });
+
+        let (tx, _rx) = terminal_output::channel();
+        let first = service
+            .connect_with_password(request.clone(), tx)
+            .await
+            .unwrap();
+        let lost = service.sessions.read().await[&first]
+            .connection
+            .clone()
+            .unwrap();
+        server.drop_connections().await;
+        while !lost.handle.read().await.is_closed() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // A session opened while the connection is lost gets its own...
+        let (tx, _rx) = terminal_output::channel();
+        let second = service
+            .connect_with_password(request.clone(), tx)
+            .await
+            .unwrap();
+        let own = service.sessions.read().await[&second]
+            .connection
+            .clone()
+            .unwrap();
+        assert!(!Arc::ptr_eq(&lost, &own));
+        assert_eq!(server.accepted(), 2);
+
+        // ...and the pool keeps the lost one for the session waiting for it
+        let pooled = service.pool.lock()[&lost.key].clone();
+        assert!(Arc::ptr_eq(pooled.lock().await.as_ref().unwrap(), &lost));
+        assert_eq!(*lost.state.lock(), ConnectionState::Reconnecting);
+
+        // Releasing the unpooled connection leaves the pool alone
+        service.disconnect(&second).await.unwrap();
+        server.wait_open(0).await;
+        assert!(Arc::ptr_eq(pooled.lock().await.as_ref().unwrap(), &lost));
+
+        service.disconnect(&first).await.unwrap();
+        assert!(pooled.lock().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scrollback_lines() {
+        let server = TestServer::start().await;
+        let service = Arc::new(SshService::new());
+        server.trust(&service);
+
+        // Sizes out of range are refused before connecting
+        for lines in [0, MIN_SCROLLBACK_LINES - 1, MAX_SCROLLBACK_LINES + 1] {
+            let mut request = server.request();
+            request.terminal.scrollback_lines = lines;
+            let (tx, _rx) = terminal_output::channel();
+            let error = service
+                .connect_with_password(request, tx)
+                .await
+                .unwrap_err();
+            assert_eq!(diagnostics::classify(&error).kind, ConnectErrorKind::Config);
+        }
+        assert_eq!(server.accepted(), 0);
+
+        let mut request = server.request();
+        request.terminal.scrollback_lines = MAX_SCROLLBACK_LINES;
+        let (tx, _rx) = terminal_output::channel();
+        service.connect_with_password(request, tx).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_scrollback() {
+        let server = TestServer::start().await;
+        let service = Arc::new(SshService::new());
+        server.trust(&service);
+
+        let (tx, mut rx) = terminal_output::channel();
+        let session_id = service
+            .connect_with_password(server.request(), tx)
+            .await
+            .unwrap();
+        assert_eq!(rx.next_frame().await.unwrap(), b"$ ");
+
+        // The new session continues the old one's scrollback
+        let (tx, mut rx) = terminal_output::channel();
+        let new_session_id = service.reconnect(&session_id, tx).await.unwrap();
+        assert_eq!(rx.next_frame().await.unwrap(), b"$ ");
+        assert!(service.scrollback(&session_id).is_err());
+        let snapshot = service.scrollback(&new_session_id).unwrap();
+        assert_eq!((snapshot.offset, snapshot.end), (0, 4));
+
+        // It is dropped once the output has ended
+        service.output_ended(&new_session_id);
+        assert!(service.scrollback(&new_session_id).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_terminal_output() {
+        let server = TestServer::start().await;
+        let service = Arc::new(SshService::new());
+        server.trust(&service);
+
+        let (tx, mut rx) = terminal_output::channel();
+        let session_id = service
+            .connect_with_password(server.request(), tx)
+            .await
+            .unwrap();
+        assert_eq!(rx.next_frame().await.unwrap(), b"$ ");
+
+        // More output than the queue holds waits for a terminal that is behind
+        let size = 8 * 1024 * 1024;
+        service
+            .send_data(&session_id, format!("flood {}\n", size).as_bytes())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // ...and input still goes through meanwhile
+        service
+            .resize_terminal(
+                &session_id,
+                TerminalSize {
+                    cols: 100,
+                    rows: 30,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(server.first_resize().await, (100, 30));
+
+        // None of it is lost
+        let mut received = 0;
+        while received < size {
+            let frame = rx.next_frame().await.unwrap();
+            assert!(frame.iter().all(|&byte| byte == b'x'));
+            received += frame.len();
+        }
+        assert_eq!(received, size);
+
+        service.disconnect(&session_id).await.unwrap();
+        server.wait_open(0).await;
+    }
 }
@@ -0,0 +1,631 @@
+//! Telnet Sessions
+//!
+//! Terminal sessions over telnet (RFC 854) for devices without SSH. The
+//! client negotiates ECHO and SGA (RFC 857/858) for character-at-a-time
+//! input echoed by the server, TTYPE (RFC 1091) to report the terminal type
+//! and NAWS (RFC 1073) to report the window size, and can answer the login
+//! prompts itself. Sessions stream through the same data sender and session
+//! IDs as SSH terminals.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::models::{
+    SessionStatus, SshStatusEvent, TelnetConnectRequest, TelnetLoginStep, TerminalSize,
+};
+use crate::services::diagnostics;
+use crate::services::ssh_service::{SshEvent, SshEventSink};
+use crate::services::terminal_output::OutputSender;
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+const OPT_ECHO: u8 = 1;
+const OPT_SGA: u8 = 3;
+const OPT_TTYPE: u8 = 24;
+const OPT_NAWS: u8 = 31;
+
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+
+/// Options the server may enable on its side
+const REMOTE_OPTIONS: &[u8] = &[OPT_ECHO, OPT_SGA];
+
+/// Options the client enables when the server asks
+const LOCAL_OPTIONS: &[u8] = &[OPT_SGA, OPT_TTYPE, OPT_NAWS];
+
+/// Longest subnegotiation kept; longer ones are dropped
+const MAX_SUBNEGOTIATION: usize = 1024;
+
+/// Output kept for matching login prompts
+const MAX_PROMPT_TAIL: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseState {
+    Data,
+    Iac,
+    /// Waiting for the option of a WILL/WONT/DO/DONT
+    Negotiate(u8),
+    Subnegotiation,
+    SubnegotiationIac,
+}
+
+/// Output of the server split into terminal data and protocol replies
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Received {
+    data: Vec<u8>,
+    reply: Vec<u8>,
+}
+
+/// Client side of the telnet protocol, independent of the transport
+struct Telnet {
+    state: ParseState,
+    subnegotiation: Vec<u8>,
+    /// The previous data byte was a CR, so a NUL after it is padding
+    after_cr: bool,
+    /// Options enabled on our side
+    local: HashSet<u8>,
+    /// Options enabled on the server's side
+    remote: HashSet<u8>,
+    term: String,
+    size: TerminalSize,
+}
+
+impl Telnet {
+    fn new(term: &str, size: TerminalSize) -> Self {
+        Self {
+            state: ParseState::Data,
+            subnegotiation: Vec::new(),
+            after_cr: false,
+            local: HashSet::new(),
+            remote: HashSet::new(),
+            term: term.to_string(),
+            size,
+        }
+    }
+
+    /// Process bytes from the server
+    fn receive(&mut self, input: &[u8]) -> Received {
+        let mut received = Received::default();
+        for &byte in input {
+            self.state = match self.state {
+                ParseState::Data if byte == IAC => ParseState::Iac,
+                ParseState::Data => {
+                    if !(byte == 0 && self.after_cr) {
+                        received.data.push(byte);
+                    }
+                    self.after_cr = byte == b'\r';
+                    ParseState::Data
+                }
+                ParseState::Iac => match byte {
+                    IAC => {
+                        received.data.push(IAC);
+                        self.after_cr = false;
+                        ParseState::Data
+                    }
+                    WILL | WONT | DO | DONT => ParseState::Negotiate(byte),
+                    SB => {
+                        self.subnegotiation.clear();
+                        ParseState::Subnegotiation
+                    }
+                    // NOP, GA, and the other commands carry nothing for a terminal
+                    _ => ParseState::Data,
+                },
+                ParseState::Negotiate(verb) => {
+                    self.negotiate(verb, byte, &mut received.reply);
+                    ParseState::Data
+                }
+                ParseState::Subnegotiation if byte == IAC => ParseState::SubnegotiationIac,
+                ParseState::Subnegotiation => {
+                    if self.subnegotiation.len() < MAX_SUBNEGOTIATION {
+                        self.subnegotiation.push(byte);
+                    }
+                    ParseState::Subnegotiation
+                }
+                ParseState::SubnegotiationIac => match byte {
+                    SE => {
+                        self.subnegotiate(&mut received.reply);
+                        ParseState::Data
+                    }
+                    IAC => {
+                        if self.subnegotiation.len() < MAX_SUBNEGOTIATION {
+                            self.subnegotiation.push(IAC);
+                        }
+                        ParseState::Subnegotiation
+                    }
+                    _ => ParseState::Subnegotiation,
+                },
+            };
+        }
+        received
+    }
+
+    /// Answer a WILL/WONT/DO/DONT. Only requests that change the state of an
+    /// option are answered, which keeps both sides from looping.
+    fn negotiate(&mut self, verb: u8, option: u8, reply: &mut Vec<u8>) {
+        match verb {
+            WILL if !REMOTE_OPTIONS.contains(&option) => reply.extend([IAC, DONT, option]),
+            WILL if self.remote.insert(option) => reply.extend([IAC, DO, option]),
+            WONT if self.remote.remove(&option) => reply.extend([IAC, DONT, option]),
+            DO if !LOCAL_OPTIONS.contains(&option) => reply.extend([IAC, WONT, option]),
+            DO if self.local.insert(option) => {
+                reply.extend([IAC, WILL, option]);
+                if option == OPT_NAWS {
+                    reply.extend(self.window_size());
+                }
+            }
+            DONT if self.local.remove(&option) => reply.extend([IAC, WONT, option]),
+            _ => {}
+        }
+    }
+
+    fn subnegotiate(&mut self, reply: &mut Vec<u8>) {
+        if self.subnegotiation.as_slice() == [OPT_TTYPE, TTYPE_SEND]
+            && self.local.contains(&OPT_TTYPE)
+        {
+            reply.extend([IAC, SB, OPT_TTYPE, TTYPE_IS]);
+            reply.extend(self.term.bytes().filter(|&b| b != IAC));
+            reply.extend([IAC, SE]);
+        }
+    }
+
+    /// NAWS subnegotiation for the current size
+    fn window_size(&self) -> Vec<u8> {
+        let mut message = vec![IAC, SB, OPT_NAWS];
+        for value in [self.size.cols, self.size.rows] {
+            for byte in (value.min(u16::MAX as u32) as u16).to_be_bytes() {
+                message.push(byte);
+                if byte == IAC {
+                    message.push(IAC);
+                }
+            }
+        }
+        message.extend([IAC, SE]);
+        message
+    }
+
+    /// Record a new window size. Returns the message telling the server, if
+    /// it asked to be told.
+    fn resize(&mut self, size: TerminalSize) -> Option<Vec<u8>> {
+        self.size = size;
+        self.local.contains(&OPT_NAWS).then(|| self.window_size())
+    }
+
+    /// Encode terminal input for the wire. IAC is doubled and a bare CR
+    /// (the Enter key) is sent as CR NUL.
+    fn encode_input(&self, data: &[u8]) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(data.len() + 1);
+        let mut bytes = data.iter().peekable();
+        while let Some(&byte) = bytes.next() {
+            encoded.push(byte);
+            match byte {
+                IAC => encoded.push(IAC),
+                b'\r' if bytes.peek() != Some(&&b'\n') => encoded.push(0),
+                _ => {}
+            }
+        }
+        encoded
+    }
+}
+
+/// Answers login prompts as they appear in the output
+struct LoginScript {
+    steps: VecDeque<TelnetLoginStep>,
+    /// Output since the last answer
+    tail: String,
+}
+
+impl LoginScript {
+    /// Script of a request: its own steps, or the answers to the usual
+    /// user name and password prompts. `None` if there is nothing to answer.
+    fn from_request(request: &TelnetConnectRequest) -> Option<Self> {
+        let mut steps: VecDeque<TelnetLoginStep> = request.login_script.iter().cloned().collect();
+        if steps.is_empty() {
+            if let Some(username) = &request.username {
+                steps.push_back(TelnetLoginStep {
+                    prompts: vec!["login:".to_string(), "username:".to_string()],
+                    answer: username.clone(),
+                });
+            }
+            if let Some(password) = &request.password {
+                steps.push_back(TelnetLoginStep {
+                    prompts: vec!["password:".to_string()],
+                    answer: password.clone(),
+                });
+            }
+        }
+        (!steps.is_empty()).then(|| Self {
+            steps,
+            tail: String::new(),
+        })
+    }
+
+    /// Watch output for the next prompt. Returns the answer once it appears.
+    fn feed(&mut self, output: &[u8]) -> Option<String> {
+        self.tail.push_str(&String::from_utf8_lossy(output));
+        if self.tail.len() > MAX_PROMPT_TAIL {
+            let mut cut = self.tail.len() - MAX_PROMPT_TAIL;
+            while !self.tail.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.tail.drain(..cut);
+        }
+
+        let tail = self.tail.trim_end().to_lowercase();
+        let step = self.steps.front()?;
+        if !step
+            .prompts
+            .iter()
+            .any(|prompt| tail.ends_with(&prompt.trim().to_lowercase()))
+        {
+            return None;
+        }
+        self.tail.clear();
+        self.steps.pop_front().map(|step| step.answer)
+    }
+
+    fn finished(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+enum TelnetCommand {
+    Data(Vec<u8>),
+    Resize(TerminalSize),
+}
+
+/// Telnet session in progress
+struct TelnetSession {
+    commands: mpsc::UnboundedSender<TelnetCommand>,
+    cancel: CancellationToken,
+}
+
+/// Runs the telnet sessions
+pub struct TelnetManager {
+    events: SshEventSink,
+    /// session_id -> session
+    sessions: Arc<parking_lot::Mutex<HashMap<String, TelnetSession>>>,
+}
+
+impl TelnetManager {
+    pub fn new(events: SshEventSink) -> Self {
+        Self {
+            events,
+            sessions: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Connect to a telnet server and stream its output to `data_tx`, which
+    /// is dropped once the connection has closed. Returns the session ID.
+    pub async fn connect(
+        &self,
+        request: &TelnetConnectRequest,
+        data_tx: OutputSender,
+    ) -> Result<String> {
+        let addresses = diagnostics::resolve(&request.host, request.port).await?;
+        let (stream, _) = diagnostics::connect_tcp(&addresses).await?;
+        let _ = stream.set_nodelay(true);
+
+        let session_id = Uuid::new_v4().to_string();
+        let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+        let cancel = CancellationToken::new();
+        self.sessions.lock().insert(
+            session_id.clone(),
+            TelnetSession {
+                commands: commands_tx,
+                cancel: cancel.clone(),
+            },
+        );
+
+        let telnet = Telnet::new(&request.term, request.terminal_size);
+        let login = LoginScript::from_request(request);
+        let sessions = self.sessions.clone();
+        let events = self.events.clone();
+        let id = session_id.clone();
+        tokio::spawn(async move {
+            let message =
+                match run_session(stream, telnet, login, commands_rx, &data_tx, cancel).await {
+                    Ok(message) => message,
+                    Err(e) => format!("Telnet connection failed: {}", e),
+                };
+            // The only status event of the session. Closed sessions are
+            // removed beforehand and need no explanation.
+            let ended = sessions.lock().remove(&id).is_some();
+            events.emit(SshEvent::Status(SshStatusEvent {
+                session_id: id,
+                status: SessionStatus::Disconnected,
+                message: ended.then_some(message),
+            }));
+            // Ends the output only now, so the session is gone by then
+            drop(data_tx);
+        });
+
+        Ok(session_id)
+    }
+
+    pub fn contains(&self, session_id: &str) -> bool {
+        self.sessions.lock().contains_key(session_id)
+    }
+
+    /// Send terminal input to the server
+    pub fn send(&self, session_id: &str, data: &[u8]) -> Result<()> {
+        self.command(session_id, TelnetCommand::Data(data.to_vec()))
+    }
+
+    /// Resize the terminal, telling the server if it negotiated NAWS
+    pub fn resize(&self, session_id: &str, size: TerminalSize) -> Result<()> {
+        self.command(session_id, TelnetCommand::Resize(size))
+    }
+
+    /// Close a session. Returns whether there was one.
+    pub fn close(&self, session_id: &str) -> bool {
+        let Some(session) = self.sessions.lock().remove(session_id) else {
+            return false;
+        };
+        session.cancel.cancel();
+        true
+    }
+
+    fn command(&self, session_id: &str, command: TelnetCommand) -> Result<()> {
+        let sessions = self.sessions.lock();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        session
+            .commands
+            .send(command)
+            .map_err(|_| anyhow::anyhow!("Session is closed"))
+    }
+}
+
+/// Pump a telnet connection until either side closes it. Returns why it
+/// ended.
+async fn run_session(
+    mut stream: TcpStream,
+    mut telnet: Telnet,
+    mut login: Option<LoginScript>,
+    mut commands: mpsc::UnboundedReceiver<TelnetCommand>,
+    data_tx: &OutputSender,
+    cancel: CancellationToken,
+) -> Result<String> {
+    let (mut reader, mut writer) = stream.split();
+    let mut buffer = vec![0u8; 8192];
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return Ok("Disconnected".to_string()),
+            read = reader.read(&mut buffer) => {
+                let n = read?;
+                if n == 0 {
+                    return Ok("Connection closed by the server".to_string());
+                }
+                let received = telnet.receive(&buffer[..n]);
+                if !received.reply.is_empty() {
+                    writer.write_all(&received.reply).await?;
+                }
+                if received.data.is_empty() {
+                    continue;
+                }
+                if let Some(script) = login.as_mut() {
+                    if let Some(answer) = script.feed(&received.data) {
+                        let line = format!("{}\r", answer);
+                        writer.write_all(&telnet.encode_input(line.as_bytes())).await?;
+                    }
+                    if script.finished() {
+                        login = None;
+                    }
+                }
+                // Waiting for room in the queue stops the socket from being read
+                if !data_tx.send(received.data).await {
+                    return Ok("Terminal closed".to_string());
+                }
+            }
+            command = commands.recv() => match command {
+                Some(TelnetCommand::Data(data)) => {
+                    writer.write_all(&telnet.encode_input(&data)).await?;
+                }
+                Some(TelnetCommand::Resize(size)) => {
+                    if let Some(message) = telnet.resize(size) {
+                        writer.write_all(&message).await?;
+                    }
+                }
+                None => return Ok("Disconnected".to_string()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::terminal_output;
+    use futures::StreamExt;
+    use tokio::net::TcpListener;
+
+    fn request(port: u16) -> TelnetConnectRequest {
+        TelnetConnectRequest {
+            host: "127.0.0.1".to_string(),
+            port,
+            username: Some("admin".to_string()),
+            password: Some("secret".to_string()),
+            login_script: Vec::new(),
+            term: "vt100".to_string(),
+            terminal_size: TerminalSize { cols: 80, rows: 24 },
+        }
+    }
+
+    #[test]
+    fn test_negotiation() {
+        let mut telnet = Telnet::new(
+            "vt100",
+            TerminalSize {
+                cols: 300,
+                rows: 40,
+            },
+        );
+
+        // Data is split around commands, even across reads
+        let received = telnet.receive(&[b'a', IAC, WILL, OPT_ECHO, b'b', IAC]);
+        assert_eq!(received.data, b"ab");
+        assert_eq!(received.reply, [IAC, DO, OPT_ECHO]);
+        let received = telnet.receive(&[IAC, b'\r', 0, b'\n']);
+        assert_eq!(received.data, [IAC, b'\r', b'\n']);
+        assert!(received.reply.is_empty());
+
+        // Repeated requests are not answered again
+        assert!(telnet.receive(&[IAC, WILL, OPT_ECHO]).reply.is_empty());
+
+        // Unsupported options are refused
+        let received = telnet.receive(&[IAC, DO, 39, IAC, WILL, 42]);
+        assert_eq!(received.reply, [IAC, WONT, 39, IAC, DONT, 42]);
+
+        // NAWS reports the size right away; 300 columns = 0x012c
+        let received = telnet.receive(&[IAC, DO, OPT_NAWS]);
+        assert_eq!(
+            received.reply,
+            [IAC, WILL, OPT_NAWS, IAC, SB, OPT_NAWS, 1, 44, 0, 40, IAC, SE]
+        );
+        assert_eq!(
+            telnet.resize(TerminalSize {
+                cols: 255,
+                rows: 50
+            }),
+            Some(vec![IAC, SB, OPT_NAWS, 0, IAC, IAC, 0, 50, IAC, SE])
+        );
+
+        let received =
+            telnet.receive(&[IAC, DO, OPT_TTYPE, IAC, SB, OPT_TTYPE, TTYPE_SEND, IAC, SE]);
+        let mut expected = vec![IAC, WILL, OPT_TTYPE, IAC, SB, OPT_TTYPE, TTYPE_IS];
+        expected.extend(b"vt100");
+        expected.extend([IAC, SE]);
+        assert_eq!(received.reply, expected);
+
+        let received = telnet.receive(&[IAC, DONT, OPT_NAWS]);
+        assert_eq!(received.reply, [IAC, WONT, OPT_NAWS]);
+        assert_eq!(telnet.resize(TerminalSize { cols: 80, rows: 24 }), None);
+
+        assert_eq!(
+            telnet.encode_input(&[b'l', b's', b'\r', IAC, b'\r', b'\n']),
+            [b'l', b's', b'\r', 0, IAC, IAC, b'\r', b'\n']
+        );
+    }
+
+    #[test]
+    fn test_login_script() {
+        let mut script = LoginScript::from_request(&request(23)).unwrap();
+        assert_eq!(script.feed(b"Welcome\r\n"), None);
+        assert_eq!(script.feed(b"Router1 Username"), None);
+        assert_eq!(script.feed(b": ").as_deref(), Some("admin"));
+        // The echoed answer does not count as a prompt
+        assert_eq!(script.feed(b"admin\r\n"), None);
+        assert_eq!(script.feed(b"Password: ").as_deref(), Some("secret"));
+        assert!(script.finished());
+
+        let mut request = request(23);
+        request.username = None;
+        request.password = None;
+        assert!(LoginScript::from_request(&request).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // Mock server: negotiate, ask for the terminal type and log in
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket
+                .write_all(&[
+                    IAC, WILL, OPT_ECHO, IAC, DO, OPT_NAWS, IAC, DO, OPT_TTYPE, IAC, SB, OPT_TTYPE,
+                    TTYPE_SEND, IAC, SE,
+                ])
+                .await
+                .unwrap();
+            socket.write_all(b"login: ").await.unwrap();
+
+            let mut received = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !received.ends_with(b"admin\r\0") {
+                let n = socket.read(&mut buffer).await.unwrap();
+                assert!(n > 0);
+                received.extend(&buffer[..n]);
+            }
+            socket.write_all(b"\r\nPassword: ").await.unwrap();
+            while !received.ends_with(b"secret\r\0") {
+                let n = socket.read(&mut buffer).await.unwrap();
+                received.extend(&buffer[..n]);
+            }
+            socket.write_all(b"\r\nrouter> ").await.unwrap();
+
+            // Typed input, then a resize
+            while !received.ends_with(&[IAC, SB, OPT_NAWS, 0, 100, 0, 30, IAC, SE]) {
+                let n = socket.read(&mut buffer).await.unwrap();
+                assert!(n > 0);
+                received.extend(&buffer[..n]);
+            }
+            received
+        });
+
+        let events = SshEventSink::default();
+        let (events_tx, mut events_rx) = futures::channel::mpsc::unbounded();
+        events.set(events_tx);
+        let manager = TelnetManager::new(events);
+        let (tx, mut rx) = terminal_output::channel();
+        let session_id = manager.connect(&request(port), tx).await.unwrap();
+
+        let mut output = Vec::new();
+        while !output.ends_with(b"router> ") {
+            output.extend(rx.next_frame().await.unwrap());
+        }
+        assert_eq!(output, b"login: \r\nPassword: \r\nrouter> ");
+
+        manager.send(&session_id, b"show\r").unwrap();
+        manager
+            .resize(
+                &session_id,
+                TerminalSize {
+                    cols: 100,
+                    rows: 30,
+                },
+            )
+            .unwrap();
+        let received = server.await.unwrap();
+
+        let mut expected = vec![IAC, DO, OPT_ECHO, IAC, WILL, OPT_NAWS];
+        expected.extend([IAC, SB, OPT_NAWS, 0, 80, 0, 24, IAC, SE]);
+        expected.extend([IAC, WILL, OPT_TTYPE, IAC, SB, OPT_TTYPE, TTYPE_IS]);
+        expected.extend(b"vt100");
+        expected.extend([IAC, SE]);
+        expected.extend(b"admin\r\0secret\r\0show\r\0");
+        expected.extend([IAC, SB, OPT_NAWS, 0, 100, 0, 30, IAC, SE]);
+        assert_eq!(received, expected);
+
+        // The session ends when the server closes the connection
+        assert_eq!(rx.next_frame().await, None);
+        assert!(!manager.contains(&session_id));
+        assert!(!manager.close(&session_id));
+
+        // ...and is reported once, with the reason
+        let Some(SshEvent::Status(status)) = events_rx.next().await else {
+            panic!("expected a status event");
+        };
+        assert_eq!(status.status, SessionStatus::Disconnected);
+        assert_eq!(
+            status.message.as_deref(),
+            Some("Connection closed by the server")
+        );
+        assert!(!matches!(events_rx.try_next(), Ok(Some(_))));
+    }
+}
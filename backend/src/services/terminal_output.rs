@@ -0,0 +1,161 @@
+//! Terminal Output
+//!
+//! Carries the output of a terminal session from its source (SSH channel,
+//! local PTY, telnet socket) to the frontend. The queue is bounded: a source
+//! that outpaces the frontend waits for room and stops being read meanwhile.
+//! Output is coalesced into frames of one time slice, so a flood of small
+//! chunks becomes a few events, and every frame is kept in the session's
+//! scrollback.
+
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::services::scrollback::{Scrollback, SharedScrollback, DEFAULT_SCROLLBACK};
+
+/// Chunks queued before the source has to wait. Output that arrives before
+/// the frontend listens (e.g. the MOTD) must fit.
+const QUEUE_LENGTH: usize = 256;
+
+/// Output arriving within this time of the previous frame joins the next one
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Frames are sent early once this long
+const MAX_FRAME: usize = 64 * 1024;
+
+/// Create the output stream of a terminal session
+pub fn channel() -> (OutputSender, OutputReceiver) {
+    let (tx, rx) = mpsc::channel(QUEUE_LENGTH);
+    let scrollback = Scrollback::shared(DEFAULT_SCROLLBACK);
+    (
+        OutputSender {
+            tx,
+            scrollback: scrollback.clone(),
+        },
+        OutputReceiver {
+            rx,
+            scrollback,
+            last_frame: None,
+        },
+    )
+}
+
+/// Sending half of a terminal's output stream
+#[derive(Clone)]
+pub struct OutputSender {
+    tx: mpsc::Sender<Vec<u8>>,
+    scrollback: SharedScrollback,
+}
+
+impl OutputSender {
+    /// Queue output, waiting while the queue is full. Returns false once the
+    /// receiver is gone.
+    pub async fn send(&self, data: Vec<u8>) -> bool {
+        self.tx.send(data).await.is_ok()
+    }
+
+    /// `send` for threads outside the async runtime
+    pub fn blocking_send(&self, data: Vec<u8>) -> bool {
+        self.tx.blocking_send(data).is_ok()
+    }
+
+    /// Scrollback the output ends up in
+    pub fn scrollback(&self) -> SharedScrollback {
+        self.scrollback.clone()
+    }
+}
+
+/// Receiving half of a terminal's output stream
+pub struct OutputReceiver {
+    rx: mpsc::Receiver<Vec<u8>>,
+    scrollback: SharedScrollback,
+    last_frame: Option<Instant>,
+}
+
+impl OutputReceiver {
+    /// Wait for the next frame of output. Output after a pause is passed on
+    /// at once; during a burst it is collected until a time slice after the
+    /// previous frame. Returns `None` once every sender is gone.
+    pub async fn next_frame(&mut self) -> Option<Vec<u8>> {
+        let mut frame = self.rx.recv().await?;
+        let deadline = match self.last_frame {
+            Some(last_frame) => last_frame + FRAME_INTERVAL,
+            None => Instant::now(),
+        };
+        while frame.len() < MAX_FRAME {
+            // Polls the queue first, so queued output is taken even past the deadline
+            match tokio::time::timeout_at(deadline, self.rx.recv()).await {
+                Ok(Some(data)) => frame.extend(data),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        self.scrollback.lock().push(&frame);
+        self.last_frame = Some(Instant::now());
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_frames() {
+        let (tx, mut rx) = channel();
+
+        // Output after a pause is not held back
+        assert!(tx.send(b"$ ".to_vec()).await);
+        let started = Instant::now();
+        assert_eq!(rx.next_frame().await.unwrap(), b"$ ");
+        assert!(started.elapsed() < FRAME_INTERVAL);
+
+        // A burst is joined into frames
+        for line in 0..100 {
+            assert!(tx.send(format!("line {}\n", line).into_bytes()).await);
+        }
+        let frame = rx.next_frame().await.unwrap();
+        assert!(frame.starts_with(b"line 0\nline 1\n"));
+        assert!(frame.ends_with(b"line 99\n"));
+
+        // Large output is split into frames of about `MAX_FRAME`
+        for _ in 0..3 {
+            assert!(tx.send(vec![b'x'; MAX_FRAME / 2]).await);
+        }
+        assert_eq!(rx.next_frame().await.unwrap().len(), MAX_FRAME);
+        assert_eq!(rx.next_frame().await.unwrap().len(), MAX_FRAME / 2);
+
+        // Every frame is kept
+        let scrollback = tx.scrollback();
+        let snapshot = scrollback.lock().snapshot();
+        assert_eq!(snapshot.offset, 0);
+        assert_eq!(
+            snapshot.end,
+            2 + frame.len() as u64 + 3 * (MAX_FRAME / 2) as u64
+        );
+
+        drop(tx);
+        assert_eq!(rx.next_frame().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let (tx, mut rx) = channel();
+        for _ in 0..QUEUE_LENGTH {
+            assert!(tx.send(b"x".to_vec()).await);
+        }
+
+        // The source waits while the queue is full
+        let send = tx.send(b"y".to_vec());
+        assert!(tokio::time::timeout(Duration::from_millis(50), send)
+            .await
+            .is_err());
+
+        rx.next_frame().await.unwrap();
+        assert!(tx.send(b"y".to_vec()).await);
+
+        // ...and learns when nobody reads any more
+        drop(rx);
+        assert!(!tx.send(b"z".to_vec()).await);
+    }
+}
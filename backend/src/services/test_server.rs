@@ -0,0 +1,367 @@
+//! In-process SSH server for tests
+//!
+//! Accepts one user by password, opens shells and answers exec requests with
+//! a few canned commands, so connection pooling and command execution can be
+//! tested without a real server.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use russh::server::{self, Auth, Msg, Session};
+use russh::{client, Channel, ChannelId, CryptoVec, Disconnect, Pty, Sig};
+use russh_keys::key::{self, KeyPair};
+use russh_keys::PublicKeyBase64;
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+use crate::models::SshConnectRequest;
+use crate::services::ssh_service::SshService;
+
+pub const USERNAME: &str = "ops";
+pub const PASSWORD: &str = "secret";
+
+pub struct TestServer {
+    pub port: u16,
+    key: KeyPair,
+    stats: Arc<Stats>,
+}
+
+#[derive(Default)]
+struct Stats {
+    /// Connections accepted so far
+    accepted: AtomicUsize,
+    /// Connections still open
+    open: AtomicUsize,
+    /// Handles of the connections accepted so far
+    handles: parking_lot::Mutex<Vec<server::Handle>>,
+    /// Signals sent to exec channels, by name
+    signals: parking_lot::Mutex<Vec<String>>,
+    /// Terminal sizes requested for shells, as columns and rows
+    sizes: parking_lot::Mutex<Vec<(u32, u32)>>,
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let key = KeyPair::generate_ed25519();
+        let config = Arc::new(server::Config {
+            keys: vec![key.clone()],
+            auth_rejection_time: Duration::ZERO,
+            auth_rejection_time_initial: Some(Duration::ZERO),
+            ..Default::default()
+        });
+        let stats = Arc::new(Stats::default());
+
+        let accepting = stats.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                accepting.accepted.fetch_add(1, Ordering::SeqCst);
+                accepting.open.fetch_add(1, Ordering::SeqCst);
+                let config = config.clone();
+                let stats = accepting.clone();
+                tokio::spawn(async move {
+                    let handler = TestHandler {
+                        stats: stats.clone(),
+                        shells: HashSet::new(),
+                        cat: HashSet::new(),
+                    };
+                    if let Ok(session) = server::run_stream(config, stream, handler).await {
+                        stats.handles.lock().push(session.handle());
+                        let _ = session.await;
+                    }
+                    stats.open.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        Self { port, key, stats }
+    }
+
+    /// Request that logs in to this server
+    pub fn request(&self) -> SshConnectRequest {
+        serde_json::from_value(serde_json::json!({
+            "connectionId": "test",
+            "host": "127.0.0.1",
+            "port": self.port,
+            "username": USERNAME,
+            "authType": "password",
+            "password": PASSWORD,
+        }))
+        .unwrap()
+    }
+
+    /// Give `service` a data directory whose known_hosts trusts this server
+    pub fn trust(&self, service: &SshService) {
+        let dir = std::env::temp_dir().join(format!("opsbot-ssh-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key = self.key.clone_public_key().unwrap();
+        let line = format!(
+            "[127.0.0.1]:{} {} {}\n",
+            self.port,
+            key.name(),
+            key.public_key_base64()
+        );
+        std::fs::write(dir.join("known_hosts"), line).unwrap();
+        service.set_data_dir(dir).unwrap();
+    }
+
+    pub fn accepted(&self) -> usize {
+        self.stats.accepted.load(Ordering::SeqCst)
+    }
+
+    pub fn open(&self) -> usize {
+        self.stats.open.load(Ordering::SeqCst)
+    }
+
+    /// Log in over a new connection and open a session channel. The handle
+    /// keeps the connection open.
+    pub async fn channel(&self) -> (client::Handle<TestClient>, Channel<client::Msg>) {
+        let config = Arc::new(client::Config::default());
+        let mut handle = client::connect(config, ("127.0.0.1", self.port), TestClient)
+            .await
+            .unwrap();
+        assert!(handle
+            .authenticate_password(USERNAME, PASSWORD)
+            .await
+            .unwrap());
+        let channel = handle.channel_open_session().await.unwrap();
+        (handle, channel)
+    }
+
+    /// Close every connection from the server side
+    pub async fn drop_connections(&self) {
+        let handles = std::mem::take(&mut *self.stats.handles.lock());
+        for handle in handles {
+            let _ = handle
+                .disconnect(
+                    Disconnect::ByApplication,
+                    "dropped".to_string(),
+                    String::new(),
+                )
+                .await;
+        }
+    }
+
+    /// Wait until at most `count` connections are open
+    pub async fn wait_open(&self, count: usize) {
+        for _ in 0..200 {
+            if self.open() <= count {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{} connections still open", self.open());
+    }
+
+    /// Wait for the first signal sent to an exec channel
+    pub async fn first_signal(&self) -> String {
+        for _ in 0..200 {
+            if let Some(signal) = self.stats.signals.lock().first() {
+                return signal.clone();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("no signal received");
+    }
+
+    /// Wait for the first terminal size change of a shell
+    pub async fn first_resize(&self) -> (u32, u32) {
+        for _ in 0..200 {
+            if let Some(size) = self.stats.sizes.lock().first() {
+                return *size;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("no window change received");
+    }
+}
+
+/// Client handler that trusts the test server
+pub struct TestClient;
+
+#[async_trait]
+impl client::Handler for TestClient {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        _server_public_key: &key::PublicKey,
+    ) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+}
+
+struct TestHandler {
+    stats: Arc<Stats>,
+    /// Channels running a shell
+    shells: HashSet<ChannelId>,
+    /// Channels running `cat`
+    cat: HashSet<ChannelId>,
+}
+
+/// Send `size` bytes of output
+fn flood(session: &mut Session, channel: ChannelId, size: usize) {
+    for chunk in vec![b'x'; size].chunks(16 * 1024) {
+        session.data(channel, CryptoVec::from_slice(chunk));
+    }
+}
+
+/// End a command with `code`
+fn exit(session: &mut Session, channel: ChannelId, code: u32) {
+    session.exit_status_request(channel, code);
+    session.eof(channel);
+    session.close(channel);
+}
+
+#[async_trait]
+impl server::Handler for TestHandler {
+    type Error = russh::Error;
+
+    async fn auth_password(&mut self, user: &str, password: &str) -> Result<Auth, Self::Error> {
+        if user == USERNAME && password == PASSWORD {
+            Ok(Auth::Accept)
+        } else {
+            Ok(Auth::Reject {
+                proceed_with_methods: None,
+            })
+        }
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        _channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+
+    async fn pty_request(
+        &mut self,
+        channel: ChannelId,
+        _term: &str,
+        _col_width: u32,
+        _row_height: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _modes: &[(Pty, u32)],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        session.channel_success(channel);
+        Ok(())
+    }
+
+    async fn shell_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        session.channel_success(channel);
+        session.data(channel, CryptoVec::from_slice(b"$ "));
+        self.shells.insert(channel);
+        Ok(())
+    }
+
+    /// `echo hello`; `fail` (output on both streams, exit code 3); `cat`
+    /// (echoes stdin until EOF); `sleep` (never ends); `crash` (killed by
+    /// SIGSEGV); `flood N` (N bytes of output). Anything else is refused.
+    async fn exec_request(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let command = String::from_utf8_lossy(data);
+        let mut words = command.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("echo"), Some(text)) => {
+                session.channel_success(channel);
+                session.data(
+                    channel,
+                    CryptoVec::from_slice(format!("{}\n", text).as_bytes()),
+                );
+                exit(session, channel, 0);
+            }
+            (Some("fail"), None) => {
+                session.channel_success(channel);
+                session.data(channel, CryptoVec::from_slice(b"partial\n"));
+                session.extended_data(channel, 1, CryptoVec::from_slice(b"oops\n"));
+                exit(session, channel, 3);
+            }
+            (Some("cat"), None) => {
+                session.channel_success(channel);
+                self.cat.insert(channel);
+            }
+            (Some("sleep"), None) => session.channel_success(channel),
+            (Some("crash"), None) => {
+                session.channel_success(channel);
+                session.exit_signal_request(channel, Sig::SEGV, true, "Segmentation fault", "");
+                session.eof(channel);
+                session.close(channel);
+            }
+            (Some("flood"), Some(size)) => {
+                session.channel_success(channel);
+                flood(session, channel, size.parse().unwrap_or(0));
+                exit(session, channel, 0);
+            }
+            _ => session.channel_failure(channel),
+        }
+        Ok(())
+    }
+
+    async fn data(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        if self.cat.contains(&channel) {
+            session.data(channel, CryptoVec::from_slice(data));
+        }
+        // Shells only know `flood N`
+        if self.shells.contains(&channel) {
+            let line = String::from_utf8_lossy(data);
+            if let Some(size) = line.trim().strip_prefix("flood ") {
+                flood(session, channel, size.parse().unwrap_or(0));
+            }
+        }
+        Ok(())
+    }
+
+    async fn channel_eof(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        if self.cat.remove(&channel) {
+            exit(session, channel, 0);
+        }
+        Ok(())
+    }
+
+    async fn window_change_request(
+        &mut self,
+        _channel: ChannelId,
+        col_width: u32,
+        row_height: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.stats.sizes.lock().push((col_width, row_height));
+        Ok(())
+    }
+
+    async fn signal(
+        &mut self,
+        _channel: ChannelId,
+        signal: Sig,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.stats.signals.lock().push(format!("{:?}", signal));
+        Ok(())
+    }
+}
@@ -0,0 +1,647 @@
+//! Port Forwarding
+//!
+//! Port forwards on top of an established SSH connection:
+//! - local (`ssh -L`): a local listener, every accepted socket is relayed
+//!   over its own direct-tcpip channel
+//! - remote (`ssh -R`): the server listens (`tcpip-forward`) and opens a
+//!   forwarded-tcpip channel per connection, relayed to a local address
+//! - dynamic (`ssh -D`): a local SOCKS5 server, each CONNECT request opens a
+//!   direct-tcpip channel to the requested destination
+//!
+//! Tunnels belong to the SSH session rather than to the terminal view and are
+//! torn down when the session disconnects.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use russh::{client, Channel};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::models::{
+    DynamicForwardRequest, LocalForwardRequest, RemoteForwardRequest, TunnelInfo, TunnelKind,
+};
+use crate::services::ssh_service::SharedHandle;
+
+/// Relay buffer size per direction
+const RELAY_BUFFER_SIZE: usize = 32 * 1024;
+
+/// How long a SOCKS5 client may take to send its request
+const SOCKS5_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// SOCKS5 reply codes (RFC 1928)
+const SOCKS5_SUCCEEDED: u8 = 0x00;
+const SOCKS5_CONNECTION_REFUSED: u8 = 0x05;
+const SOCKS5_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const SOCKS5_ADDRESS_NOT_SUPPORTED: u8 = 0x08;
+
+/// Live traffic counters of a tunnel
+#[derive(Default)]
+pub struct TunnelCounters {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    active_connections: AtomicU64,
+    total_connections: AtomicU64,
+}
+
+struct Tunnel {
+    info: TunnelInfo,
+    counters: Arc<TunnelCounters>,
+    cancel: CancellationToken,
+    /// Connection holding the server-side listener of a remote forward
+    remote_listener: Option<SharedHandle>,
+}
+
+impl Tunnel {
+    fn new(info: TunnelInfo, remote_listener: Option<SharedHandle>) -> Self {
+        Self {
+            info,
+            counters: Arc::new(TunnelCounters::default()),
+            cancel: CancellationToken::new(),
+            remote_listener,
+        }
+    }
+
+    fn info(&self) -> TunnelInfo {
+        TunnelInfo {
+            bytes_sent: self.counters.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.counters.bytes_received.load(Ordering::Relaxed),
+            active_connections: self.counters.active_connections.load(Ordering::Relaxed),
+            total_connections: self.counters.total_connections.load(Ordering::Relaxed),
+            ..self.info.clone()
+        }
+    }
+}
+
+type TunnelMap = Arc<parking_lot::Mutex<HashMap<String, Tunnel>>>;
+
+/// Registry of active tunnels across all SSH sessions
+#[derive(Default)]
+pub struct TunnelManager {
+    tunnels: TunnelMap,
+}
+
+impl TunnelManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind a local listener and forward accepted connections through `handle`
+    pub async fn create_local(
+        &self,
+        handle: SharedHandle,
+        request: LocalForwardRequest,
+    ) -> Result<TunnelInfo> {
+        let listener = bind_listener(&request.bind_host, request.bind_port).await?;
+        let info = new_info(
+            request.session_id,
+            TunnelKind::Local,
+            request.bind_host,
+            listener.local_addr()?.port(),
+            request.remote_host,
+            request.remote_port,
+        );
+        Ok(self.start_listener(listener, handle, info))
+    }
+
+    /// Bind a local SOCKS5 server whose CONNECT requests are resolved through `handle`
+    pub async fn create_dynamic(
+        &self,
+        handle: SharedHandle,
+        request: DynamicForwardRequest,
+    ) -> Result<TunnelInfo> {
+        let listener = bind_listener(&request.bind_host, request.bind_port).await?;
+        let info = new_info(
+            request.session_id,
+            TunnelKind::Dynamic,
+            request.bind_host,
+            listener.local_addr()?.port(),
+            String::new(),
+            0,
+        );
+        Ok(self.start_listener(listener, handle, info))
+    }
+
+    fn start_listener(
+        &self,
+        listener: TcpListener,
+        handle: SharedHandle,
+        info: TunnelInfo,
+    ) -> TunnelInfo {
+        let tunnel = Tunnel::new(info.clone(), None);
+        let counters = tunnel.counters.clone();
+        let cancel = tunnel.cancel.clone();
+        self.tunnels.lock().insert(info.tunnel_id.clone(), tunnel);
+
+        tokio::spawn(accept_loop(
+            listener,
+            handle,
+            info.clone(),
+            counters,
+            cancel,
+            self.tunnels.clone(),
+        ));
+        info
+    }
+
+    /// Ask the server to listen and forward its connections to a local address
+    pub async fn create_remote(
+        &self,
+        handle: SharedHandle,
+        request: RemoteForwardRequest,
+    ) -> Result<TunnelInfo> {
+        let bound_port = handle
+            .write()
+            .await
+            .tcpip_forward(
+                request.remote_bind_host.as_str(),
+                request.remote_bind_port as u32,
+            )
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Server refused to listen on {}:{}: {}",
+                    request.remote_bind_host,
+                    request.remote_bind_port,
+                    e
+                )
+            })?;
+
+        // Servers only report the port when it was chosen by them
+        let bind_port = if request.remote_bind_port == 0 {
+            bound_port as u16
+        } else {
+            request.remote_bind_port
+        };
+
+        let info = new_info(
+            request.session_id,
+            TunnelKind::Remote,
+            request.remote_bind_host,
+            bind_port,
+            request.local_host,
+            request.local_port,
+        );
+        let tunnel = Tunnel::new(info.clone(), Some(handle));
+        self.tunnels.lock().insert(info.tunnel_id.clone(), tunnel);
+        Ok(info)
+    }
+
+    /// Relay a forwarded-tcpip channel opened by the server to the local
+    /// target of the matching remote forward
+    pub fn accept_forwarded(
+        &self,
+        session_ids: &[String],
+        channel: Channel<client::Msg>,
+        connected_address: &str,
+        connected_port: u32,
+    ) {
+        let route = {
+            let tunnels = self.tunnels.lock();
+            let matches: Vec<&Tunnel> = tunnels
+                .values()
+                .filter(|t| {
+                    t.info.kind == TunnelKind::Remote
+                        && session_ids.contains(&t.info.session_id)
+                        && t.info.bind_port as u32 == connected_port
+                })
+                .collect();
+            // Several listeners may share a port on different addresses
+            matches
+                .iter()
+                .find(|t| t.info.bind_host == connected_address)
+                .or(matches.first())
+                .map(|t| (t.info.clone(), t.counters.clone(), t.cancel.child_token()))
+        };
+
+        let Some((info, counters, cancel)) = route else {
+            log::warn!(
+                "No remote forward for {}:{}, rejecting channel",
+                connected_address,
+                connected_port
+            );
+            tokio::spawn(async move {
+                let _ = channel.close().await;
+            });
+            return;
+        };
+
+        tokio::spawn(async move {
+            let socket =
+                match TcpStream::connect((info.target_host.as_str(), info.target_port)).await {
+                    Ok(socket) => socket,
+                    Err(e) => {
+                        log::warn!(
+                            "Tunnel {} could not reach {}:{}: {}",
+                            info.tunnel_id,
+                            info.target_host,
+                            info.target_port,
+                            e
+                        );
+                        let _ = channel.close().await;
+                        return;
+                    }
+                };
+            track_connection(socket, channel, &counters, cancel).await;
+        });
+    }
+
+    /// List tunnels, optionally only those of one session
+    pub fn list(&self, session_id: Option<&str>) -> Vec<TunnelInfo> {
+        let mut tunnels: Vec<TunnelInfo> = self
+            .tunnels
+            .lock()
+            .values()
+            .filter(|t| session_id.is_none() || session_id == Some(t.info.session_id.as_str()))
+            .map(Tunnel::info)
+            .collect();
+        tunnels.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        tunnels
+    }
+
+    /// Close a tunnel and all connections relayed through it
+    pub async fn close(&self, tunnel_id: &str) -> Result<()> {
+        let tunnel = self
+            .tunnels
+            .lock()
+            .remove(tunnel_id)
+            .ok_or_else(|| anyhow!("Tunnel not found"))?;
+        tunnel.cancel.cancel();
+
+        if let Some(handle) = tunnel.remote_listener {
+            handle
+                .read()
+                .await
+                .cancel_tcpip_forward(tunnel.info.bind_host.as_str(), tunnel.info.bind_port as u32)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Ask the server to listen again for the remote forwards of a session
+    /// whose connection was re-established
+    pub async fn restore_remote(&self, session_id: &str) {
+        let listeners: Vec<(TunnelInfo, SharedHandle)> = self
+            .tunnels
+            .lock()
+            .values()
+            .filter(|t| t.info.session_id == session_id)
+            .filter_map(|t| Some((t.info.clone(), t.remote_listener.clone()?)))
+            .collect();
+
+        for (info, handle) in listeners {
+            let result = handle
+                .write()
+                .await
+                .tcpip_forward(info.bind_host.as_str(), info.bind_port as u32)
+                .await;
+            if let Err(e) = result {
+                log::warn!(
+                    "Tunnel {} could not listen on {}:{} again: {}",
+                    info.tunnel_id,
+                    info.bind_host,
+                    info.bind_port,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Close every tunnel of a session. Server-side listeners are cancelled
+    /// too, as the connection may be shared with other sessions.
+    pub async fn close_session(&self, session_id: &str) {
+        let closed: Vec<Tunnel> = {
+            let mut tunnels = self.tunnels.lock();
+            let ids: Vec<String> = tunnels
+                .values()
+                .filter(|t| t.info.session_id == session_id)
+                .map(|t| t.info.tunnel_id.clone())
+                .collect();
+            ids.iter().filter_map(|id| tunnels.remove(id)).collect()
+        };
+
+        for tunnel in closed {
+            tunnel.cancel.cancel();
+            if let Some(handle) = tunnel.remote_listener {
+                let handle = handle.read().await;
+                if !handle.is_closed() {
+                    let _ = handle
+                        .cancel_tcpip_forward(
+                            tunnel.info.bind_host.as_str(),
+                            tunnel.info.bind_port as u32,
+                        )
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+fn new_info(
+    session_id: String,
+    kind: TunnelKind,
+    bind_host: String,
+    bind_port: u16,
+    target_host: String,
+    target_port: u16,
+) -> TunnelInfo {
+    TunnelInfo {
+        tunnel_id: Uuid::new_v4().to_string(),
+        session_id,
+        kind,
+        bind_host,
+        bind_port,
+        target_host,
+        target_port,
+        bytes_sent: 0,
+        bytes_received: 0,
+        active_connections: 0,
+        total_connections: 0,
+        created_at: Utc::now().to_rfc3339(),
+    }
+}
+
+async fn bind_listener(host: &str, port: u16) -> Result<TcpListener> {
+    TcpListener::bind((host, port))
+        .await
+        .map_err(|e| anyhow!("Failed to listen on {}:{}: {}", host, port, e))
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    handle: SharedHandle,
+    info: TunnelInfo,
+    counters: Arc<TunnelCounters>,
+    cancel: CancellationToken,
+    tunnels: TunnelMap,
+) {
+    loop {
+        let (socket, peer) = tokio::select! {
+            _ = cancel.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    log::warn!("Tunnel {} stopped accepting: {}", info.tunnel_id, e);
+                    break;
+                }
+            },
+        };
+
+        // The SSH connection is down, possibly while it is being re-established
+        if handle.read().await.is_closed() {
+            log::warn!(
+                "Tunnel {}: SSH connection is down, rejecting {}",
+                info.tunnel_id,
+                peer
+            );
+            drop(socket);
+            continue;
+        }
+
+        let handle = handle.clone();
+        let info = info.clone();
+        let counters = counters.clone();
+        let cancel = cancel.child_token();
+        tokio::spawn(async move {
+            if let Err(e) = forward_accepted(socket, peer, &handle, &info, &counters, cancel).await
+            {
+                log::warn!("Tunnel {}: {}", info.tunnel_id, e);
+            }
+        });
+    }
+
+    cancel.cancel();
+    tunnels.lock().remove(&info.tunnel_id);
+}
+
+/// Open a direct-tcpip channel for a locally accepted socket and relay it.
+/// Dynamic tunnels first read the destination from a SOCKS5 request.
+async fn forward_accepted(
+    mut socket: TcpStream,
+    peer: SocketAddr,
+    handle: &SharedHandle,
+    info: &TunnelInfo,
+    counters: &TunnelCounters,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let dynamic = info.kind == TunnelKind::Dynamic;
+    let (host, port) = if dynamic {
+        tokio::time::timeout(SOCKS5_HANDSHAKE_TIMEOUT, socks5_accept(&mut socket))
+            .await
+            .map_err(|_| anyhow!("SOCKS5 handshake timed out"))??
+    } else {
+        (info.target_host.clone(), info.target_port)
+    };
+
+    let channel = handle
+        .read()
+        .await
+        .channel_open_direct_tcpip(
+            host.as_str(),
+            port as u32,
+            peer.ip().to_string(),
+            peer.port() as u32,
+        )
+        .await;
+    let channel = match channel {
+        Ok(channel) => channel,
+        Err(e) => {
+            if dynamic {
+                let _ = socks5_reply(&mut socket, SOCKS5_CONNECTION_REFUSED).await;
+            }
+            return Err(anyhow!("Could not reach {}:{}: {}", host, port, e));
+        }
+    };
+    if dynamic {
+        socks5_reply(&mut socket, SOCKS5_SUCCEEDED).await?;
+    }
+
+    track_connection(socket, channel, counters, cancel).await;
+    Ok(())
+}
+
+/// Read a SOCKS5 greeting and CONNECT request (no authentication) and
+/// return the requested destination
+async fn socks5_accept<S>(stream: &mut S) -> Result<(String, u16)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != 0x05 {
+        return Err(anyhow!("Not a SOCKS5 client"));
+    }
+    let mut methods = vec![0u8; header[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&0x00) {
+        stream.write_all(&[0x05, 0xFF]).await?;
+        return Err(anyhow!(
+            "SOCKS5 client does not offer \"no authentication\""
+        ));
+    }
+    stream.write_all(&[0x05, 0x00]).await?;
+
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await?;
+    if request[1] != 0x01 {
+        socks5_reply(stream, SOCKS5_COMMAND_NOT_SUPPORTED).await?;
+        return Err(anyhow!("Unsupported SOCKS5 command {}", request[1]));
+    }
+    let host = match request[3] {
+        0x01 => {
+            let mut ip = [0u8; 4];
+            stream.read_exact(&mut ip).await?;
+            Ipv4Addr::from(ip).to_string()
+        }
+        0x04 => {
+            let mut ip = [0u8; 16];
+            stream.read_exact(&mut ip).await?;
+            Ipv6Addr::from(ip).to_string()
+        }
+        0x03 => {
+            let mut length = [0u8; 1];
+            stream.read_exact(&mut length).await?;
+            let mut name = vec![0u8; length[0] as usize];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8(name).map_err(|_| anyhow!("Invalid SOCKS5 host name"))?
+        }
+        _ => {
+            socks5_reply(stream, SOCKS5_ADDRESS_NOT_SUPPORTED).await?;
+            return Err(anyhow!("Unsupported SOCKS5 address type {}", request[3]));
+        }
+    };
+    let mut port = [0u8; 2];
+    stream.read_exact(&mut port).await?;
+    Ok((host, u16::from_be_bytes(port)))
+}
+
+/// Send a SOCKS5 reply; the bound address is not meaningful for a tunnel
+async fn socks5_reply<S>(stream: &mut S, code: u8) -> Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    stream
+        .write_all(&[0x05, code, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+        .await?;
+    Ok(())
+}
+
+/// Relay one forwarded connection until it ends or the tunnel is closed
+async fn track_connection(
+    socket: TcpStream,
+    channel: Channel<client::Msg>,
+    counters: &TunnelCounters,
+    cancel: CancellationToken,
+) {
+    counters.total_connections.fetch_add(1, Ordering::Relaxed);
+    counters.active_connections.fetch_add(1, Ordering::Relaxed);
+    tokio::select! {
+        _ = cancel.cancelled() => {}
+        _ = relay(socket, channel.into_stream(), counters) => {}
+    }
+    counters.active_connections.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Copy data both ways between a local socket and a channel stream,
+/// counting bytes in each direction
+async fn relay<S>(socket: TcpStream, stream: S, counters: &TunnelCounters)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut local_read, mut local_write) = socket.into_split();
+    let (mut remote_read, mut remote_write) = tokio::io::split(stream);
+
+    let _ = tokio::join!(
+        pump(&mut local_read, &mut remote_write, &counters.bytes_sent),
+        pump(&mut remote_read, &mut local_write, &counters.bytes_received),
+    );
+}
+
+/// Copy until EOF, then propagate the EOF to the writer
+async fn pump<R, W>(reader: &mut R, writer: &mut W, counter: &AtomicU64) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; RELAY_BUFFER_SIZE];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n]).await?;
+        counter.fetch_add(n as u64, Ordering::Relaxed);
+    }
+    writer.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_relay_counts_both_directions() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
+
+        // The far end of the duplex stands in for the SSH channel
+        let (channel, mut remote) = tokio::io::duplex(1024);
+        let counters = Arc::new(TunnelCounters::default());
+        let relay_counters = counters.clone();
+        let relay_task = tokio::spawn(async move {
+            relay(socket, channel, &relay_counters).await;
+        });
+
+        client.write_all(b"GET / HTTP/1.0\r\n\r\n").await.unwrap();
+        let mut request = [0u8; 18];
+        remote.read_exact(&mut request).await.unwrap();
+        assert_eq!(&request, b"GET / HTTP/1.0\r\n\r\n");
+
+        remote.write_all(b"HTTP/1.0 200 OK").await.unwrap();
+        drop(remote);
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        assert_eq!(response, b"HTTP/1.0 200 OK");
+
+        client.shutdown().await.unwrap();
+        relay_task.await.unwrap();
+        assert_eq!(counters.bytes_sent.load(Ordering::Relaxed), 18);
+        assert_eq!(counters.bytes_received.load(Ordering::Relaxed), 15);
+    }
+
+    #[tokio::test]
+    async fn test_socks5_accept_with_proxy_client() {
+        use crate::models::{ProxyConfig, ProxyType};
+        use crate::services::proxy::connect_via_proxy;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let target = socks5_accept(&mut socket).await.unwrap();
+            socks5_reply(&mut socket, SOCKS5_SUCCEEDED).await.unwrap();
+            target
+        });
+
+        let proxy = ProxyConfig {
+            proxy_type: ProxyType::Socks5,
+            host: "127.0.0.1".to_string(),
+            port,
+            username: None,
+            password: None,
+        };
+        connect_via_proxy(&proxy, "intranet.local", 8080)
+            .await
+            .unwrap();
+        assert_eq!(server.await.unwrap(), ("intranet.local".to_string(), 8080));
+    }
+}
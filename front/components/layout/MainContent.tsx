@@ -14,6 +14,7 @@ import { DatabaseContainer } from '@/components/database'
 import { ERDiagramDesigner } from '@/components/database/designer'
 import { SshConnectionDialog } from '@/components/ssh'
 import { sshConnect } from '@/services'
+import type { SshConnectRequest } from '@/services'
 import type { Tab, SSHConnection, DatabaseConnection } from '@/types'
 
 // Error Boundary for catching render errors
@@ -146,16 +147,31 @@ export function MainContent({ className }: MainContentProps) {
     setConnectionStatus(tab.connectionId, 'connecting')
 
     // Build request object
-    const request = {
+    const request: SshConnectRequest = {
       connectionId: connection.id,
       host: connection.host || '',
       port: connection.port || 22,
       username: connection.username || '',
-      authType: (connection.authType || 'password') as 'password' | 'key',
+      authType: connection.authType || 'password',
       password: connection.password,
       privateKey: connection.privateKey,
       passphrase: connection.passphrase,
+      certificate: connection.certificate,
+      // Connections saved before multi-hop support carry a single jumpHost
+      jumpHosts: connection.jumpHosts ?? (connection.jumpHost ? [connection.jumpHost] : undefined),
+      proxy: connection.proxy && {
+        proxyType: connection.proxy.type,
+        host: connection.proxy.host,
+        port: connection.proxy.port,
+        username: connection.proxy.username,
+        password: connection.proxy.password,
+      },
+      agentForwarding: connection.agentForwarding,
+      keepalive: connection.keepalive,
+      autoReconnect: connection.autoReconnect,
       terminalSize: { cols: 80, rows: 24 },
+      terminal: connection.terminal,
+      algorithms: connection.algorithms,
     }
 
     // Debug: log request data
@@ -13,6 +13,7 @@ import { WebglAddon } from '@xterm/addon-webgl'
 import { invoke } from '@tauri-apps/api/core'
 import { listen, UnlistenFn } from '@tauri-apps/api/event'
 import { cn } from '@/lib/utils'
+import type { SshStatusEvent } from '@/services/ssh'
 import { useThemeStore } from '@/stores'
 import '@xterm/xterm/css/xterm.css'
 
@@ -231,11 +232,16 @@ export function Terminal({
       })
 
       // Listen for status events
-      const statusListener = await listen<string>(`ssh-status-${sessionId}`, (event) => {
-        if (event.payload === 'disconnected' && isMounted) {
+      const statusListener = await listen<SshStatusEvent>(`ssh-status-${sessionId}`, (event) => {
+        if (!isMounted) return
+        const { status, message } = event.payload
+        if (status === 'connecting' || status === 'connected') {
+          // Automatic reconnect in progress or done
+          terminalRef.current?.write(`\r\n\x1b[33m[${message ?? status}]\x1b[0m\r\n`)
+        } else {
           onDisconnected?.()
           if (terminalRef.current) {
-            terminalRef.current.write('\r\n\x1b[31m[Connection closed]\x1b[0m\r\n')
+            terminalRef.current.write(`\r\n\x1b[31m[${message ?? 'Connection closed'}]\x1b[0m\r\n`)
           }
         }
       })
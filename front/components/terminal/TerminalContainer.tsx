@@ -24,6 +24,7 @@ import {
   StopCircleIcon,
 } from 'lucide-react'
 import { cn } from '@/lib/utils'
+import type { SshStatusEvent } from '@/services/ssh'
 import { useTranslation } from 'react-i18next'
 import { useThemeStore } from '@/stores'
 import { TerminalToolbar } from './TerminalToolbar'
@@ -224,10 +225,15 @@ export function TerminalContainer({
       })
 
       // 设置状态监听
-      const statusListener = await listen<string>(`ssh-status-${sessionId}`, (event) => {
-        if (event.payload === 'disconnected' && isMounted) {
+      const statusListener = await listen<SshStatusEvent>(`ssh-status-${sessionId}`, (event) => {
+        if (!isMounted) return
+        const { status, message } = event.payload
+        if (status === 'connecting' || status === 'connected') {
+          // Automatic reconnect in progress or done
+          terminalRef.current?.write(`\r\n\x1b[33m[${message ?? status}]\x1b[0m\r\n`)
+        } else {
           onDisconnected?.()
-          terminalRef.current?.write('\r\n\x1b[31m[Connection closed]\x1b[0m\r\n')
+          terminalRef.current?.write(`\r\n\x1b[31m[${message ?? 'Connection closed'}]\x1b[0m\r\n`)
         }
       })
 
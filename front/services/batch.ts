@@ -0,0 +1,140 @@
+/**
+ * Batch Service
+ *
+ * Frontend service for running one command on many hosts.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen, UnlistenFn } from '@tauri-apps/api/event'
+import type { ExecResult } from './exec'
+import type { SshConnectRequest } from './ssh'
+
+/** Host to run on: an open session, or settings for an on-demand connection */
+export interface BatchTarget {
+  /** Display name, e.g. the saved connection name */
+  name: string
+  sessionId?: string
+  connection?: SshConnectRequest
+}
+
+export interface BatchJobRequest {
+  command: string
+  stdin?: string
+  targets: BatchTarget[]
+  /** Hosts worked on at the same time (default 10) */
+  concurrency?: number
+  /** Time allowed per host in milliseconds, connecting included (default 60000) */
+  timeoutMs?: number
+}
+
+export type BatchJobStatus = 'running' | 'completed' | 'cancelled'
+
+/** Exec result fields are absent if the command did not run; error says why */
+export interface BatchHostResult extends Partial<ExecResult> {
+  /** Position of the target in the request */
+  index: number
+  name: string
+  host: string
+  error?: string
+}
+
+export interface BatchJobSummary {
+  jobId: string
+  command: string
+  status: BatchJobStatus
+  startedAt: string
+  finishedAt?: string
+  total: number
+  /** Hosts where the command exited with status 0 */
+  succeeded: number
+  failed: number
+}
+
+export interface BatchJob {
+  jobId: string
+  command: string
+  status: BatchJobStatus
+  startedAt: string
+  finishedAt?: string
+  total: number
+  /** In the order hosts finished */
+  results: BatchHostResult[]
+}
+
+export interface BatchProgressEvent {
+  jobId: string
+  completed: number
+  total: number
+  result: BatchHostResult
+}
+
+/**
+ * Start a batch job; host results arrive through onProgress as they finish,
+ * onFinished fires once with the job summary
+ */
+export async function batchStart(
+  request: BatchJobRequest,
+  onProgress: (event: BatchProgressEvent) => void,
+  onFinished: (summary: BatchJobSummary) => void
+): Promise<{ jobId: string; unlisten: UnlistenFn }> {
+  // Events can arrive before invoke resolves, so buffer until the ID is known
+  let jobId: string | null = null
+  const early: BatchProgressEvent[] = []
+  const earlyFinished: BatchJobSummary[] = []
+  const unlistenProgress = await listen<BatchProgressEvent>('batch-progress', (event) => {
+    if (jobId === null) {
+      early.push(event.payload)
+    } else if (event.payload.jobId === jobId) {
+      onProgress(event.payload)
+    }
+  })
+  const unlistenFinished = await listen<BatchJobSummary>('batch-finished', (event) => {
+    if (jobId === null) {
+      earlyFinished.push(event.payload)
+    } else if (event.payload.jobId === jobId) {
+      onFinished(event.payload)
+    }
+  })
+  const unlisten = () => {
+    unlistenProgress()
+    unlistenFinished()
+  }
+
+  try {
+    jobId = await invoke<string>('ssh_batch_start', { request })
+  } catch (error) {
+    unlisten()
+    throw error
+  }
+  early.filter((event) => event.jobId === jobId).forEach(onProgress)
+  earlyFinished.filter((summary) => summary.jobId === jobId).forEach(onFinished)
+  return { jobId, unlisten }
+}
+
+/**
+ * Cancel a running batch job
+ */
+export async function batchCancel(jobId: string): Promise<void> {
+  await invoke('ssh_batch_cancel', { jobId })
+}
+
+/**
+ * List running and stored batch jobs, newest first
+ */
+export async function batchList(): Promise<BatchJobSummary[]> {
+  return invoke('ssh_batch_list')
+}
+
+/**
+ * Get a batch job with its host results
+ */
+export async function batchGet(jobId: string): Promise<BatchJob> {
+  return invoke('ssh_batch_get', { jobId })
+}
+
+/**
+ * Delete a stored batch job
+ */
+export async function batchDelete(jobId: string): Promise<void> {
+  await invoke('ssh_batch_delete', { jobId })
+}
@@ -0,0 +1,89 @@
+/**
+ * Broadcast Service
+ *
+ * Frontend service for typing into several SSH sessions at once.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+
+export interface BroadcastGroupInfo {
+  groupId: string
+  name: string
+  /** Member sessions in the order they joined */
+  sessionIds: string[]
+}
+
+export interface BroadcastFailure {
+  sessionId: string
+  error: string
+}
+
+export interface BroadcastReport {
+  delivered: string[]
+  failed: BroadcastFailure[]
+}
+
+/**
+ * Create a broadcast group from existing sessions
+ */
+export async function broadcastCreate(
+  name: string,
+  sessionIds: string[]
+): Promise<BroadcastGroupInfo> {
+  return invoke('ssh_broadcast_create', { name, sessionIds })
+}
+
+/**
+ * Add a session to a broadcast group
+ */
+export async function broadcastJoin(
+  groupId: string,
+  sessionId: string
+): Promise<BroadcastGroupInfo> {
+  return invoke('ssh_broadcast_join', { groupId, sessionId })
+}
+
+/**
+ * Remove a session from a broadcast group
+ */
+export async function broadcastLeave(
+  groupId: string,
+  sessionId: string
+): Promise<BroadcastGroupInfo> {
+  return invoke('ssh_broadcast_leave', { groupId, sessionId })
+}
+
+/**
+ * Delete a broadcast group (its sessions stay open)
+ */
+export async function broadcastDelete(groupId: string): Promise<void> {
+  await invoke('ssh_broadcast_delete', { groupId })
+}
+
+/**
+ * List broadcast groups
+ */
+export async function broadcastList(): Promise<BroadcastGroupInfo[]> {
+  return invoke('ssh_broadcast_list')
+}
+
+/**
+ * Send base64 encoded data to every session of a group
+ */
+export async function broadcastSendData(
+  groupId: string,
+  data: string
+): Promise<BroadcastReport> {
+  return invoke('ssh_broadcast_send_data', { groupId, data })
+}
+
+/**
+ * Resize the terminal of every session of a group
+ */
+export async function broadcastResize(
+  groupId: string,
+  cols: number,
+  rows: number
+): Promise<BroadcastReport> {
+  return invoke('ssh_broadcast_resize', { groupId, cols, rows })
+}
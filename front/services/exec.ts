@@ -0,0 +1,105 @@
+/**
+ * Exec Service
+ *
+ * Frontend service for running non-interactive commands over SSH.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen, UnlistenFn } from '@tauri-apps/api/event'
+
+export interface ExecRequest {
+  sessionId: string
+  command: string
+  /** Written to the command's stdin, which is closed afterwards */
+  stdin?: string
+  /** Kill the command after this many milliseconds */
+  timeoutMs?: number
+}
+
+export interface ExecStatus {
+  /** Absent if the server reported none (e.g. killed by a signal) */
+  exitCode?: number
+  /** Signal that terminated the command, e.g. "KILL" */
+  exitSignal?: string
+  durationMs: number
+  timedOut: boolean
+  cancelled: boolean
+}
+
+export interface ExecResult extends ExecStatus {
+  stdout: string
+  stderr: string
+  /** Output past the limit of what is kept was dropped */
+  truncated: boolean
+}
+
+export type ExecStreamName = 'stdout' | 'stderr'
+
+export interface ExecOutputEvent {
+  execId: string
+  stream: ExecStreamName
+  data: string
+}
+
+export interface ExecFinishedEvent extends Partial<ExecStatus> {
+  execId: string
+  /** Set if the command could not be run; status fields are absent then */
+  error?: string
+}
+
+/**
+ * Run a command and wait for it to exit
+ */
+export async function sshExec(request: ExecRequest): Promise<ExecResult> {
+  return invoke('ssh_exec', { request })
+}
+
+/**
+ * Run a command, delivering output through onOutput as it arrives;
+ * onFinished fires once with the exit status or an error
+ */
+export async function sshExecStream(
+  request: ExecRequest,
+  onOutput: (event: ExecOutputEvent) => void,
+  onFinished: (event: ExecFinishedEvent) => void
+): Promise<{ execId: string; unlisten: UnlistenFn }> {
+  // Events can arrive before invoke resolves, so buffer until the ID is known
+  let execId: string | null = null
+  const early: ExecOutputEvent[] = []
+  const earlyFinished: ExecFinishedEvent[] = []
+  const unlistenOutput = await listen<ExecOutputEvent>('ssh-exec-output', (event) => {
+    if (execId === null) {
+      early.push(event.payload)
+    } else if (event.payload.execId === execId) {
+      onOutput(event.payload)
+    }
+  })
+  const unlistenFinished = await listen<ExecFinishedEvent>('ssh-exec-finished', (event) => {
+    if (execId === null) {
+      earlyFinished.push(event.payload)
+    } else if (event.payload.execId === execId) {
+      onFinished(event.payload)
+    }
+  })
+  const unlisten = () => {
+    unlistenOutput()
+    unlistenFinished()
+  }
+
+  try {
+    execId = await invoke<string>('ssh_exec_stream', { request })
+  } catch (error) {
+    unlisten()
+    throw error
+  }
+  early.filter((event) => event.execId === execId).forEach(onOutput)
+  earlyFinished.filter((event) => event.execId === execId).forEach(onFinished)
+  return { execId, unlisten }
+}
+
+/**
+ * Cancel a streaming command
+ */
+export async function sshExecCancel(execId: string): Promise<void> {
+  await invoke('ssh_exec_cancel', { execId })
+}
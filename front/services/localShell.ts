@@ -0,0 +1,48 @@
+/**
+ * Local Shell Service
+ *
+ * Frontend service for terminals running a shell on this machine via Tauri.
+ * A local shell session streams the same `ssh-data-{id}` / `ssh-status-{id}`
+ * events as SSH sessions and is driven by sshSendData, sshResize and
+ * sshDisconnect, so the terminal component works with it unchanged.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+
+export interface LocalShellRequest {
+  /** Path or name of the shell, the user's default shell if absent */
+  shell?: string
+  args?: string[]
+  /** Working directory, the home directory if absent */
+  cwd?: string
+  /** Value of TERM (default xterm-256color) */
+  term?: string
+  /** Variables added to the inherited environment */
+  env?: Record<string, string>
+  terminalSize?: {
+    cols: number
+    rows: number
+  }
+}
+
+export interface LocalShellInfo {
+  /** Display name, e.g. bash, zsh, PowerShell */
+  name: string
+  path: string
+  /** Whether this is the user's default shell */
+  default: boolean
+}
+
+/**
+ * Start a local shell, returns the session ID
+ */
+export async function localShellOpen(request: LocalShellRequest = {}): Promise<string> {
+  return await invoke<string>('local_shell_open', { request })
+}
+
+/**
+ * List the shells installed on this machine, the default one first
+ */
+export async function localShellList(): Promise<LocalShellInfo[]> {
+  return await invoke<LocalShellInfo[]>('local_shell_list')
+}
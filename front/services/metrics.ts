@@ -0,0 +1,128 @@
+/**
+ * Metrics Service
+ *
+ * Frontend service for monitoring the resource usage of remote hosts.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen, UnlistenFn } from '@tauri-apps/api/event'
+
+export interface MetricsRequest {
+  sessionId: string
+  /** Seconds between samples (default 2) */
+  intervalSecs?: number
+}
+
+export interface MetricsMonitorInfo {
+  sessionId: string
+  intervalSecs: number
+  /** Samples kept in the history */
+  samples: number
+}
+
+export interface CpuMetrics {
+  /** Busy time of all cores, 0-100 */
+  usagePercent: number
+  iowaitPercent: number
+  /** Busy time of each core, 0-100 */
+  cores: number[]
+}
+
+export interface MemoryMetrics {
+  totalBytes: number
+  availableBytes: number
+  usedBytes: number
+  usagePercent: number
+  swapTotalBytes: number
+  swapUsedBytes: number
+}
+
+export interface DiskMetrics {
+  filesystem: string
+  mountPoint: string
+  totalBytes: number
+  usedBytes: number
+  availableBytes: number
+  usagePercent: number
+}
+
+export interface NetworkMetrics {
+  interface: string
+  rxBytes: number
+  txBytes: number
+  /** Null on the first sample or after a counter reset */
+  rxBytesPerSec: number | null
+  txBytesPerSec: number | null
+}
+
+export interface LoadMetrics {
+  one: number
+  five: number
+  fifteen: number
+  running: number
+  tasks: number
+}
+
+/** Parts the host did not report are null; cpu is null on the first sample */
+export interface MetricsSample {
+  sessionId: string
+  /** Milliseconds since the Unix epoch */
+  timestamp: number
+  uptimeSecs: number | null
+  cpu: CpuMetrics | null
+  memory: MemoryMetrics | null
+  load: LoadMetrics | null
+  disks: DiskMetrics[]
+  network: NetworkMetrics[]
+}
+
+export interface MetricsStoppedEvent {
+  sessionId: string
+  /** Why the collector failed, absent if it was stopped */
+  error?: string
+}
+
+/**
+ * Start collecting host metrics for a session; a running collector is kept
+ */
+export async function metricsStart(request: MetricsRequest): Promise<MetricsMonitorInfo> {
+  return invoke('ssh_metrics_start', { request })
+}
+
+/**
+ * Stop collecting host metrics for a session
+ */
+export async function metricsStop(sessionId: string): Promise<void> {
+  await invoke('ssh_metrics_stop', { sessionId })
+}
+
+/**
+ * Get the recent samples of a session, oldest first
+ */
+export async function metricsHistory(sessionId: string): Promise<MetricsSample[]> {
+  return invoke('ssh_metrics_history', { sessionId })
+}
+
+/**
+ * Listen for metrics samples of a session
+ */
+export async function listenMetrics(
+  sessionId: string,
+  callback: (sample: MetricsSample) => void
+): Promise<UnlistenFn> {
+  return await listen<MetricsSample>(`ssh-metrics-${sessionId}`, (event) => {
+    callback(event.payload)
+  })
+}
+
+/**
+ * Listen for the metrics collector of a session stopping
+ */
+export async function listenMetricsStopped(
+  sessionId: string,
+  callback: (event: MetricsStoppedEvent) => void
+): Promise<UnlistenFn> {
+  return await listen<MetricsStoppedEvent>(`ssh-metrics-stopped-${sessionId}`, (event) => {
+    callback(event.payload)
+  })
+}
@@ -0,0 +1,117 @@
+/**
+ * Recording Service
+ *
+ * Frontend service for asciicast v2 session recordings.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen, UnlistenFn } from '@tauri-apps/api/event'
+
+export interface RecordingOptions {
+  /** Also record keystrokes (may capture secrets) */
+  recordInput?: boolean
+}
+
+export interface RecordingInfo {
+  recordingId: string
+  title?: string
+  width: number
+  height: number
+  startedAt?: string
+  /** Seconds */
+  duration: number
+  /** Bytes */
+  size: number
+  active: boolean
+}
+
+export interface ReplayFrame {
+  replayId: string
+  time: number
+  /** 'o' output, 'r' resize ("COLSxROWS") */
+  kind: 'o' | 'r'
+  data: string
+}
+
+/**
+ * Start recording a connected SSH session
+ */
+export async function recordingStart(
+  sessionId: string,
+  options?: RecordingOptions
+): Promise<RecordingInfo> {
+  return invoke('ssh_recording_start', { sessionId, options })
+}
+
+/**
+ * Stop recording an SSH session
+ */
+export async function recordingStop(sessionId: string): Promise<void> {
+  await invoke('ssh_recording_stop', { sessionId })
+}
+
+/**
+ * List stored recordings, newest first
+ */
+export async function recordingList(): Promise<RecordingInfo[]> {
+  return invoke('ssh_recording_list')
+}
+
+/**
+ * Delete a stored recording
+ */
+export async function recordingDelete(recordingId: string): Promise<void> {
+  await invoke('ssh_recording_delete', { recordingId })
+}
+
+/**
+ * Replay a recording; frames arrive through onFrame, onFinished fires at the end
+ */
+export async function recordingReplay(
+  recordingId: string,
+  onFrame: (frame: ReplayFrame) => void,
+  onFinished: () => void,
+  speed = 1
+): Promise<{ replayId: string; unlisten: UnlistenFn }> {
+  // Frames can arrive before invoke resolves, so buffer until the ID is known
+  let replayId: string | null = null
+  const early: ReplayFrame[] = []
+  const earlyFinished: string[] = []
+  const unlistenFrame = await listen<ReplayFrame>('recording-replay', (event) => {
+    if (replayId === null) {
+      early.push(event.payload)
+    } else if (event.payload.replayId === replayId) {
+      onFrame(event.payload)
+    }
+  })
+  const unlistenFinished = await listen<string>('recording-replay-finished', (event) => {
+    if (replayId === null) {
+      earlyFinished.push(event.payload)
+    } else if (event.payload === replayId) {
+      onFinished()
+    }
+  })
+  const unlisten = () => {
+    unlistenFrame()
+    unlistenFinished()
+  }
+
+  try {
+    replayId = await invoke<string>('ssh_recording_replay', { recordingId, speed })
+  } catch (error) {
+    unlisten()
+    throw error
+  }
+  early.filter((frame) => frame.replayId === replayId).forEach(onFrame)
+  if (earlyFinished.includes(replayId)) {
+    onFinished()
+  }
+  return { replayId, unlisten }
+}
+
+/**
+ * Stop a running replay
+ */
+export async function recordingReplayStop(replayId: string): Promise<void> {
+  await invoke('ssh_recording_replay_stop', { replayId })
+}
@@ -0,0 +1,94 @@
+/**
+ * Scrollback Service
+ *
+ * Frontend service for the terminal output kept on the backend. Output of a
+ * session arrives in `ssh-data-{id}` events coalesced into frames; the latest
+ * output is also kept per session, both as raw bytes so a reattaching tab can
+ * restore its screen, and as lines of text without escape sequences that can
+ * be searched and exported.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+
+export interface ScrollbackSnapshot {
+  /** Output bytes, base64 encoded like `ssh-data-{id}` events */
+  data: string
+  /** Position of the first byte in the session's output */
+  offset: number
+  /** Length of the session's output so far, which `data` ends at */
+  end: number
+}
+
+export interface ScrollbackSearchRequest {
+  sessionId: string
+  query: string
+  /** Whether query is a regular expression rather than plain text */
+  regex?: boolean
+  caseSensitive?: boolean
+  /** Most matches returned, oldest first (default 1000) */
+  maxResults?: number
+}
+
+/** Match of a scrollback search; matches do not span lines */
+export interface ScrollbackMatch {
+  /** Number of the line in the session's output, counted from 0 */
+  line: number
+  /** Character offsets of the match in the line, end exclusive */
+  start: number
+  end: number
+  /** Text of the line */
+  text: string
+}
+
+export interface ScrollbackLines {
+  /** Number of the first line returned */
+  start: number
+  /** Number of the oldest line still kept */
+  firstLine: number
+  /** Number of lines so far; the last one may still be being written */
+  totalLines: number
+  lines: string[]
+}
+
+export interface ScrollbackExportRequest {
+  sessionId: string
+  /** Local file to write */
+  path: string
+  /** Plain text, or a page keeping the colors */
+  format: 'text' | 'html'
+}
+
+/**
+ * Get the latest output of a terminal session
+ */
+export async function sshScrollbackFetch(sessionId: string): Promise<ScrollbackSnapshot> {
+  return await invoke<ScrollbackSnapshot>('ssh_scrollback_fetch', { sessionId })
+}
+
+/**
+ * Search the scrollback text of a terminal session
+ */
+export async function sshScrollbackSearch(
+  request: ScrollbackSearchRequest
+): Promise<ScrollbackMatch[]> {
+  return await invoke<ScrollbackMatch[]>('ssh_scrollback_search', { request })
+}
+
+/**
+ * Get up to `count` lines of the scrollback text of a terminal session,
+ * from line number `start` on
+ */
+export async function sshScrollbackLines(
+  sessionId: string,
+  start: number,
+  count: number
+): Promise<ScrollbackLines> {
+  return await invoke<ScrollbackLines>('ssh_scrollback_lines', { sessionId, start, count })
+}
+
+/**
+ * Export the scrollback text of a terminal session to a file
+ */
+export async function sshScrollbackExport(request: ScrollbackExportRequest): Promise<void> {
+  await invoke('ssh_scrollback_export', { request })
+}
@@ -11,10 +11,42 @@ export interface JumpHostConfig {
   host: string
   port: number
   username: string
-  authType: 'password' | 'key'
+  authType: 'password' | 'key' | 'interactive' | 'agent'
   password?: string
   privateKey?: string
   passphrase?: string
+  /** OpenSSH certificate for the private key (contents of *-cert.pub) */
+  certificate?: string
+}
+
+export interface ProxyConfig {
+  proxyType: 'socks5' | 'http'
+  host: string
+  port: number
+  username?: string
+  password?: string
+}
+
+/** SSH algorithms, most preferred first; an empty or missing list keeps the defaults */
+export interface AlgorithmPreferences {
+  kex?: string[]
+  hostKey?: string[]
+  cipher?: string[]
+  mac?: string[]
+}
+
+/** Terminal type, environment and PTY modes of a session's shell */
+export interface TerminalOptions {
+  /** Terminal type sent with the PTY request (default xterm-256color) */
+  term?: string
+  /** Environment variables; servers only set the names their AcceptEnv allows */
+  env?: Record<string, string>
+  /** PTY modes by RFC 4254 name, e.g. { ECHO: 1, TTY_OP_ISPEED: 38400 } */
+  ptyModes?: Record<string, number>
+  /** Commands typed into the shell once it has started, in order */
+  startupCommands?: string[]
+  /** Lines of output kept on the backend for search and export (100 to 100000, default 10000) */
+  scrollbackLines?: number
 }
 
 export interface SshConnectRequest {
@@ -22,31 +54,170 @@ export interface SshConnectRequest {
   host: string
   port: number
   username: string
-  authType: 'password' | 'key'
+  authType: 'password' | 'key' | 'interactive' | 'agent'
   password?: string
   privateKey?: string
   passphrase?: string
-  jumpHost?: JumpHostConfig
+  /** OpenSSH certificate for the private key (contents of *-cert.pub) */
+  certificate?: string
+  /** Jump hosts in hop order (first entry is dialed directly) */
+  jumpHosts?: JumpHostConfig[]
+  /** SOCKS5/HTTP proxy used to reach the first hop */
+  proxy?: ProxyConfig
+  /** Let the remote host use the local ssh-agent */
+  agentForwarding?: boolean
+  /** Keepalive interval in seconds (0 disables) and missed replies before the peer is dead */
+  keepalive?: {
+    interval?: number
+    maxMissed?: number
+  }
+  /** Algorithm preferences for legacy devices, used for every hop */
+  algorithms?: AlgorithmPreferences
+  /** Reconnect automatically with exponential backoff when the connection drops */
+  autoReconnect?: {
+    maxAttempts?: number
+    initialDelayMs?: number
+    maxDelayMs?: number
+  }
+  /** Record the session to an asciicast file */
+  recording?: {
+    recordInput?: boolean
+  }
   terminalSize?: {
     cols: number
     rows: number
   }
+  terminal?: TerminalOptions
 }
 
+export type SshSessionStatus = 'connecting' | 'connected' | 'disconnected' | 'error'
+
 export interface SshSessionInfo {
   sessionId: string
   connectionId: string
-  status: 'connecting' | 'connected' | 'disconnected' | 'error'
+  status: SshSessionStatus
   connectedAt?: string
   host: string
   username: string
+  /** Certificate presented with key authentication */
+  certificate?: CertificateInfo
+  /** Algorithms negotiated with the host */
+  algorithms?: NegotiatedAlgorithms
+}
+
+export interface NegotiatedAlgorithms {
+  kex: string
+  hostKey: string
+  cipherClientToServer: string
+  cipherServerToClient: string
+  /** Absent for AEAD ciphers, which authenticate the data themselves */
+  macClientToServer?: string
+  macServerToClient?: string
+  /** Negotiated algorithms considered weak (SHA-1, MD5, CBC modes...) */
+  weak: string[]
+}
+
+export interface CertificateInfo {
+  keyId: string
+  serial: number
+  /** Users the certificate is valid for; empty means any */
+  principals: string[]
+  /** Start of validity (RFC 3339), absent if valid since forever */
+  validAfter: string | null
+  /** End of validity (RFC 3339), absent if it never expires */
+  validBefore: string | null
+  caFingerprint: string
+}
+
+/** Category of a failed connection attempt */
+export type ConnectErrorKind =
+  | 'config'
+  | 'resolve'
+  | 'refused'
+  | 'unreachable'
+  | 'timeout'
+  | 'proxy'
+  | 'protocol'
+  | 'keyExchange'
+  | 'hostKey'
+  | 'auth'
+  | 'disconnected'
+  | 'other'
+
+export interface ConnectError {
+  kind: ConnectErrorKind
+  message: string
+  /** Jump host the error happened on, absent for the target */
+  hop?: string
+}
+
+export type DiagnosticStage =
+  | 'jumpHosts'
+  | 'resolve'
+  | 'tcpConnect'
+  | 'banner'
+  | 'keyExchange'
+  | 'hostKey'
+  | 'authentication'
+
+export interface StageReport {
+  stage: DiagnosticStage
+  status: 'ok' | 'failed' | 'skipped'
+  /** Time the stage took, absent if it was skipped */
+  durationMs?: number
+  detail?: string
+  error?: ConnectError
+}
+
+/** Staged report of a connection test; stages after the first failure are not run */
+export interface ConnectionDiagnostics {
+  host: string
+  port: number
+  success: boolean
+  stages: StageReport[]
+  /** Version line the server sent, e.g. SSH-2.0-OpenSSH_9.6 */
+  serverVersion?: string
+  algorithms?: NegotiatedAlgorithms
+  /** Error that ended the test */
+  error?: ConnectError
+}
+
+/**
+ * Error thrown by connects, carrying the category reported by the backend
+ */
+export class SshConnectError extends Error {
+  kind: ConnectErrorKind
+  hop?: string
+
+  constructor(error: ConnectError) {
+    super(error.message)
+    this.name = 'SshConnectError'
+    this.kind = error.kind
+    this.hop = error.hop
+  }
+
+  // Shown as is where errors are turned into strings
+  toString(): string {
+    return this.message
+  }
+}
+
+export function toConnectError(err: unknown): unknown {
+  if (err && typeof err === 'object' && 'kind' in err && 'message' in err) {
+    return new SshConnectError(err as ConnectError)
+  }
+  return err
 }
 
 /**
  * Connect to SSH server
  */
 export async function sshConnect(request: SshConnectRequest): Promise<string> {
-  return await invoke<string>('ssh_connect', { request })
+  try {
+    return await invoke<string>('ssh_connect', { request })
+  } catch (err) {
+    throw toConnectError(err)
+  }
 }
 
 /**
@@ -78,7 +249,11 @@ export async function sshDisconnect(sessionId: string): Promise<void> {
  * Reconnect SSH session
  */
 export async function sshReconnect(sessionId: string): Promise<string> {
-  return await invoke<string>('ssh_reconnect', { sessionId })
+  try {
+    return await invoke<string>('ssh_reconnect', { sessionId })
+  } catch (err) {
+    throw toConnectError(err)
+  }
 }
 
 /**
@@ -109,17 +284,35 @@ export interface SshTestRequest {
   host: string
   port: number
   username: string
-  authType: 'password' | 'key' | 'interactive'
+  authType: 'password' | 'key' | 'interactive' | 'agent'
   password?: string
   privateKey?: string
   passphrase?: string
+  /** OpenSSH certificate for the private key (contents of *-cert.pub) */
+  certificate?: string
+  /** Algorithm preferences for legacy devices */
+  algorithms?: AlgorithmPreferences
 }
 
 /**
  * Test SSH connection without creating a session
  */
 export async function sshTestConnection(request: SshTestRequest): Promise<void> {
-  await invoke('ssh_test_connection', { request })
+  try {
+    await invoke('ssh_test_connection', { request })
+  } catch (err) {
+    throw toConnectError(err)
+  }
+}
+
+/**
+ * Test SSH connection stage by stage (DNS, TCP, banner, key exchange, host key,
+ * authentication) and report on each
+ */
+export async function sshDiagnoseConnection(
+  request: SshTestRequest | SshConnectRequest
+): Promise<ConnectionDiagnostics> {
+  return await invoke<ConnectionDiagnostics>('ssh_diagnose_connection', { request })
 }
 
 /**
@@ -134,14 +327,101 @@ export async function listenSshData(
   })
 }
 
+export interface SshStatusEvent {
+  sessionId: string
+  status: SshSessionStatus
+  message?: string
+}
+
 /**
  * Listen for SSH status events
  */
 export async function listenSshStatus(
   sessionId: string,
-  callback: (status: string) => void
+  callback: (status: SshStatusEvent) => void
+): Promise<UnlistenFn> {
+  return await listen<SshStatusEvent>(`ssh-status-${sessionId}`, (event) => {
+    callback(event.payload)
+  })
+}
+
+export interface HostKeyPromptEvent {
+  requestId: string
+  host: string
+  port: number
+  keyType: string
+  fingerprint: string
+  status: 'unknown' | 'changed'
+  previousFingerprint?: string
+}
+
+export interface KnownHostEntry {
+  line: number
+  hosts: string
+  hashed: boolean
+  marker?: string
+  keyType: string
+  fingerprint: string
+}
+
+/**
+ * Listen for host key verification prompts (unknown or changed server keys)
+ */
+export async function listenHostKeyPrompt(
+  callback: (prompt: HostKeyPromptEvent) => void
+): Promise<UnlistenFn> {
+  return await listen<HostKeyPromptEvent>('ssh-host-key-prompt', (event) => {
+    callback(event.payload)
+  })
+}
+
+/**
+ * Accept or reject a host key prompt
+ */
+export async function sshHostKeyRespond(requestId: string, accept: boolean): Promise<void> {
+  await invoke('ssh_host_key_respond', { requestId, accept })
+}
+
+/**
+ * List known_hosts entries
+ */
+export async function sshKnownHostsList(): Promise<KnownHostEntry[]> {
+  return await invoke<KnownHostEntry[]>('ssh_known_hosts_list')
+}
+
+/**
+ * Forget recorded host keys so the host can be trusted again
+ */
+export async function sshKnownHostsRemove(host: string, port: number): Promise<number> {
+  return await invoke<number>('ssh_known_hosts_remove', { host, port })
+}
+
+export interface KeyboardInteractivePromptEvent {
+  requestId: string
+  host: string
+  username: string
+  name: string
+  instructions: string
+  prompts: { prompt: string; echo: boolean }[]
+}
+
+/**
+ * Listen for keyboard-interactive prompts (OTP / 2FA challenges)
+ */
+export async function listenAuthPrompt(
+  callback: (prompt: KeyboardInteractivePromptEvent) => void
 ): Promise<UnlistenFn> {
-  return await listen<string>(`ssh-status-${sessionId}`, (event) => {
+  return await listen<KeyboardInteractivePromptEvent>('ssh-auth-prompt', (event) => {
     callback(event.payload)
   })
 }
+
+/**
+ * Answer a keyboard-interactive prompt; pass null to cancel authentication
+ */
+export async function sshAuthPromptRespond(
+  requestId: string,
+  responses: string[] | null
+): Promise<void> {
+  await invoke('ssh_auth_prompt_respond', { requestId, responses })
+}
@@ -0,0 +1,86 @@
+/**
+ * SSH Config Import Service
+ *
+ * Frontend service for importing connections from an OpenSSH client config.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { ModuleType, SSHConnection } from '@/types'
+import { AlgorithmPreferences, JumpHostConfig, ProxyConfig } from './ssh'
+
+/** Connection settings built from one Host alias */
+export interface SshConfigConnection {
+  id: string
+  name: string
+  host: string
+  port: number
+  username: string
+  authType: 'password' | 'key' | 'interactive' | 'agent'
+  privateKey?: string
+  certificate?: string
+  jumpHosts?: JumpHostConfig[]
+  proxy?: ProxyConfig
+  agentForwarding: boolean
+  keepalive: {
+    interval: number
+    maxMissed: number
+  }
+  algorithms: AlgorithmPreferences
+}
+
+export interface SshConfigEntry {
+  /** Host alias the entry was built from */
+  alias: string
+  config: SshConfigConnection
+  /** Settings of this host that could not be imported */
+  warnings: string[]
+}
+
+export interface SshConfigImport {
+  /** Config file that was read */
+  path: string
+  entries: SshConfigEntry[]
+  /** Problems not tied to one host */
+  warnings: string[]
+}
+
+/**
+ * Read an OpenSSH client config (~/.ssh/config by default) for preview;
+ * nothing is saved
+ */
+export async function sshConfigImportPreview(path?: string): Promise<SshConfigImport> {
+  return invoke('ssh_config_import_preview', { path: path ?? null })
+}
+
+/**
+ * Turn a previewed entry into connection data for the connection store
+ */
+export function sshConfigEntryToConnection(
+  entry: SshConfigEntry,
+  folderId: string | null
+): Omit<SSHConnection, 'id' | 'createdAt' | 'updatedAt'> {
+  const { config } = entry
+  return {
+    name: config.name,
+    moduleType: ModuleType.SSH,
+    folderId,
+    order: 0,
+    tags: [],
+    lastConnectedAt: null,
+    host: config.host,
+    port: config.port,
+    username: config.username,
+    authType: config.authType,
+    privateKey: config.privateKey,
+    certificate: config.certificate,
+    jumpHosts: config.jumpHosts,
+    proxy: config.proxy && {
+      type: config.proxy.proxyType,
+      host: config.proxy.host,
+      port: config.proxy.port,
+    },
+    agentForwarding: config.agentForwarding,
+    keepalive: config.keepalive,
+    algorithms: config.algorithms,
+  }
+}
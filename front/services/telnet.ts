@@ -0,0 +1,48 @@
+/**
+ * Telnet Service
+ *
+ * Frontend service for telnet terminals via Tauri. A telnet session streams
+ * the same `ssh-data-{id}` / `ssh-status-{id}` events as SSH sessions and is
+ * driven by sshSendData, sshResize and sshDisconnect, so the terminal
+ * component works with it unchanged.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { toConnectError } from './ssh'
+
+export interface TelnetLoginStep {
+  /** Texts the output must end with (case-insensitive), e.g. ['login:', 'username:'] */
+  prompts: string[]
+  /** Sent followed by Enter */
+  answer: string
+}
+
+export interface TelnetConnectRequest {
+  host: string
+  /** Default 23 */
+  port?: number
+  /** Answer to the login prompt, used when loginScript is empty */
+  username?: string
+  /** Answer to the password prompt, used when loginScript is empty */
+  password?: string
+  /** Prompts to answer in order once they appear */
+  loginScript?: TelnetLoginStep[]
+  /** Terminal type reported to the server (default xterm-256color) */
+  term?: string
+  terminalSize?: {
+    cols: number
+    rows: number
+  }
+}
+
+/**
+ * Connect to a telnet server, returns the session ID. Fails with an
+ * SshConnectError.
+ */
+export async function telnetConnect(request: TelnetConnectRequest): Promise<string> {
+  try {
+    return await invoke<string>('telnet_connect', { request })
+  } catch (err) {
+    throw toConnectError(err)
+  }
+}
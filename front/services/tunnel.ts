@@ -0,0 +1,90 @@
+/**
+ * Tunnel Service
+ *
+ * Frontend service for SSH port forwarding.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+
+export type TunnelKind = 'local' | 'remote' | 'dynamic'
+
+export interface LocalForwardRequest {
+  sessionId: string
+  /** Defaults to 127.0.0.1 */
+  bindHost?: string
+  /** 0 or omitted picks a free port */
+  bindPort?: number
+  remoteHost: string
+  remotePort: number
+}
+
+export interface RemoteForwardRequest {
+  sessionId: string
+  /** Address the server listens on, defaults to localhost ('' = all interfaces) */
+  remoteBindHost?: string
+  /** 0 or omitted lets the server pick a port */
+  remoteBindPort?: number
+  localHost: string
+  localPort: number
+}
+
+export interface DynamicForwardRequest {
+  sessionId: string
+  /** Defaults to 127.0.0.1 */
+  bindHost?: string
+  /** 0 or omitted picks a free port */
+  bindPort?: number
+}
+
+export interface TunnelInfo {
+  tunnelId: string
+  sessionId: string
+  kind: TunnelKind
+  /** Listening side: local for 'local', on the server for 'remote' */
+  bindHost: string
+  bindPort: number
+  /** Empty for 'dynamic' tunnels */
+  targetHost: string
+  targetPort: number
+  /** Bytes sent / received over the SSH connection */
+  bytesSent: number
+  bytesReceived: number
+  activeConnections: number
+  totalConnections: number
+  createdAt: string
+}
+
+/**
+ * Open a local port forward (ssh -L) on an existing SSH session
+ */
+export async function tunnelCreateLocal(request: LocalForwardRequest): Promise<TunnelInfo> {
+  return invoke('ssh_tunnel_create_local', { request })
+}
+
+/**
+ * Open a remote port forward (ssh -R): the server listens and forwards to a local address
+ */
+export async function tunnelCreateRemote(request: RemoteForwardRequest): Promise<TunnelInfo> {
+  return invoke('ssh_tunnel_create_remote', { request })
+}
+
+/**
+ * Start a local SOCKS5 server tunnelled through the session (ssh -D)
+ */
+export async function tunnelCreateDynamic(request: DynamicForwardRequest): Promise<TunnelInfo> {
+  return invoke('ssh_tunnel_create_dynamic', { request })
+}
+
+/**
+ * List tunnels with traffic counters (all sessions if sessionId is omitted)
+ */
+export async function tunnelList(sessionId?: string): Promise<TunnelInfo[]> {
+  return invoke('ssh_tunnel_list', { sessionId })
+}
+
+/**
+ * Close a tunnel
+ */
+export async function tunnelClose(tunnelId: string): Promise<void> {
+  await invoke('ssh_tunnel_close', { tunnelId })
+}
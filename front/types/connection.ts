@@ -44,7 +44,7 @@ export interface ConnectionBase {
 }
 
 // 认证方式
-export type AuthType = 'password' | 'key' | 'interactive'
+export type AuthType = 'password' | 'key' | 'interactive' | 'agent'
 
 // SSH连接配置
 export interface SSHConnection extends ConnectionBase {
@@ -56,6 +56,8 @@ export interface SSHConnection extends ConnectionBase {
   password?: string
   privateKey?: string
   passphrase?: string
+  /** OpenSSH certificate for the private key (contents of *-cert.pub) */
+  certificate?: string
   jumpHost?: {
     host: string
     port: number
@@ -64,6 +66,17 @@ export interface SSHConnection extends ConnectionBase {
     password?: string
     privateKey?: string
   }
+  /** Jump hosts in connection order; takes the place of jumpHost */
+  jumpHosts?: Array<{
+    host: string
+    port: number
+    username: string
+    authType: AuthType
+    password?: string
+    privateKey?: string
+    passphrase?: string
+    certificate?: string
+  }>
   proxy?: {
     type: 'socks5' | 'http'
     host: string
@@ -71,6 +84,30 @@ export interface SSHConnection extends ConnectionBase {
     username?: string
     password?: string
   }
+  agentForwarding?: boolean
+  /** Terminal type, environment, PTY modes and startup commands */
+  terminal?: {
+    term?: string
+    env?: Record<string, string>
+    ptyModes?: Record<string, number>
+    startupCommands?: string[]
+  }
+  keepalive?: {
+    interval?: number
+    maxMissed?: number
+  }
+  /** SSH algorithms, most preferred first (kex, host key, cipher, MAC) */
+  algorithms?: {
+    kex?: string[]
+    hostKey?: string[]
+    cipher?: string[]
+    mac?: string[]
+  }
+  autoReconnect?: {
+    maxAttempts?: number
+    initialDelayMs?: number
+    maxDelayMs?: number
+  }
   terminalSettings?: {
     fontSize: number
     fontFamily: string